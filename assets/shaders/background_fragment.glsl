@@ -0,0 +1,28 @@
+#version 150
+
+// the backgrounds of src/background.rs, one of the BACKGROUND_ keywords is defined
+#include "environment.glsl"
+
+in vec3 v_direction;
+
+out vec4 color;
+
+uniform float u_intensity;
+uniform vec3 u_top_color;
+uniform vec3 u_bottom_color;
+uniform samplerCube u_skybox;
+uniform sampler2D u_panorama;
+
+void main() {
+    vec3 direction = normalize(v_direction);
+#if defined(BACKGROUND_GRADIENT)
+    vec3 background = mix(u_bottom_color, u_top_color, direction.y * 0.5 + 0.5);
+#elif defined(BACKGROUND_SKYBOX)
+    vec3 background = texture(u_skybox, direction).rgb;
+#else
+    // the derivatives of the uvs jump where the panorama wraps around, the first level avoids a
+    // seam of blurry pixels there
+    vec3 background = textureLod(u_panorama, equirectangular_uv(direction), 0.0).rgb;
+#endif
+    color = vec4(background * u_intensity, 1.0);
+}
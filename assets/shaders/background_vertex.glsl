@@ -0,0 +1,16 @@
+#version 150
+
+// a triangle covering the screen on the far plane, with the direction seen through each pixel
+out vec3 v_direction;
+
+uniform mat4 view;
+uniform mat4 perspective;
+
+void main() {
+    vec2 corner = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+    gl_Position = vec4(corner, 1.0, 1.0);
+    // the translation of the camera is left out, the background is infinitely far
+    mat4 inverse_view_projection = inverse(perspective * mat4(mat3(view)));
+    vec4 world = inverse_view_projection * gl_Position;
+    v_direction = world.xyz / world.w;
+}
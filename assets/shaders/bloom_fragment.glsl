@@ -0,0 +1,67 @@
+#version 150
+
+// the passes of the bloom, see src/post_processing.rs
+// 0: keeps the bright pixels while halving the resolution
+// 1: halves the resolution
+// 2: blurs a level into the larger one, which is blended additively
+// 3: adds the bloom to the image
+in vec2 v_tex_coord;
+
+out vec4 color;
+
+uniform sampler2D u_source;
+uniform sampler2D u_bloom;
+uniform vec2 u_texel_size;
+uniform int u_bloom_pass;
+uniform float u_threshold;
+// how gradually the pixels below the threshold are faded out
+uniform float u_knee;
+uniform float u_radius;
+uniform float u_intensity;
+
+// four bilinear samples, so sixteen texels
+vec3 downsample(vec2 uv) {
+    vec4 offset = u_texel_size.xyxy * vec4(-1.0, -1.0, 1.0, 1.0);
+    vec3 sum = texture(u_source, uv + offset.xy).rgb;
+    sum += texture(u_source, uv + offset.zy).rgb;
+    sum += texture(u_source, uv + offset.xw).rgb;
+    sum += texture(u_source, uv + offset.zw).rgb;
+    return sum * 0.25;
+}
+
+// 3x3 tent filter
+vec3 upsample(vec2 uv) {
+    vec4 offset = u_texel_size.xyxy * vec4(1.0, 1.0, -1.0, 0.0) * u_radius;
+    vec3 sum = texture(u_source, uv - offset.xy).rgb;
+    sum += texture(u_source, uv - offset.wy).rgb * 2.0;
+    sum += texture(u_source, uv - offset.zy).rgb;
+    sum += texture(u_source, uv + offset.zw).rgb * 2.0;
+    sum += texture(u_source, uv).rgb * 4.0;
+    sum += texture(u_source, uv + offset.xw).rgb * 2.0;
+    sum += texture(u_source, uv + offset.zy).rgb;
+    sum += texture(u_source, uv + offset.wy).rgb * 2.0;
+    sum += texture(u_source, uv + offset.xy).rgb;
+    return sum / 16.0;
+}
+
+// soft threshold, quadratic below the knee
+vec3 prefilter(vec3 c) {
+    float brightness = max(c.r, max(c.g, c.b));
+    float soft = clamp(brightness - u_threshold + u_knee, 0.0, 2.0 * u_knee);
+    soft = soft * soft / (4.0 * u_knee + 1e-5);
+    float contribution = max(soft, brightness - u_threshold) / max(brightness, 1e-5);
+    return c * contribution;
+}
+
+void main() {
+    if (u_bloom_pass == 0) {
+        color = vec4(prefilter(downsample(v_tex_coord)), 1.0);
+    } else if (u_bloom_pass == 1) {
+        color = vec4(downsample(v_tex_coord), 1.0);
+    } else if (u_bloom_pass == 2) {
+        color = vec4(upsample(v_tex_coord), 1.0);
+    } else {
+        vec4 source = texture(u_source, v_tex_coord);
+        color = vec4(source.rgb + texture(u_bloom, v_tex_coord).rgb * u_intensity, source.a);
+    }
+}
@@ -0,0 +1,29 @@
+#version 150
+
+// remaps the colours through a lookup table, a strip of size x size squares, one square per
+// value of blue, red going right and green going up in every square, see src/post_processing.rs
+in vec2 v_tex_coord;
+
+out vec4 color;
+
+uniform sampler2D u_source;
+uniform sampler2D u_lut;
+uniform float u_lut_size;
+uniform float u_contribution;
+
+vec3 lookup(vec3 c) {
+    float size = u_lut_size;
+    float blue = c.b * (size - 1.0);
+    float slice = floor(blue);
+    float next = min(slice + 1.0, size - 1.0);
+    vec2 cell = (c.rg * (size - 1.0) + 0.5) / vec2(size * size, size);
+    vec3 low = texture(u_lut, cell + vec2(slice / size, 0.0)).rgb;
+    vec3 high = texture(u_lut, cell + vec2(next / size, 0.0)).rgb;
+    return mix(low, high, blue - slice);
+}
+
+void main() {
+    vec4 source = texture(u_source, v_tex_coord);
+    vec3 graded = lookup(clamp(source.rgb, 0.0, 1.0));
+    color = vec4(mix(source.rgb, graded, u_contribution), source.a);
+}
@@ -0,0 +1,13 @@
+#version 150
+
+// copies a mip level of a 2d texture into a face of a cubemap, see src/texture.rs
+in vec2 v_tex_coord;
+
+out vec4 color;
+
+uniform sampler2D u_face;
+uniform float u_level;
+
+void main() {
+    color = textureLod(u_face, v_tex_coord, u_level);
+}
@@ -0,0 +1,17 @@
+#version 150
+
+// the lines of the debug drawing and of the normals of the models, see src/debug_draw.rs and
+// src/debug_view.rs
+in vec3 position;
+in vec4 color;
+
+out vec4 v_color;
+
+uniform mat4 matrix;
+uniform mat4 view;
+uniform mat4 perspective;
+
+void main() {
+    v_color = color;
+    gl_Position = perspective * view * matrix * vec4(position, 1.0);
+}
@@ -0,0 +1,11 @@
+// included by the builtin fragment shaders, see DebugView::LightingOnly in src/debug_view.rs
+
+// replaces the albedo by white so that only the lighting shows
+uniform bool u_lighting_only;
+
+vec3 debug_albedo(vec3 albedo) {
+    if (u_lighting_only) {
+        return vec3(1.0);
+    }
+    return albedo;
+}
@@ -0,0 +1,35 @@
+#version 150
+
+// the debug views which replace the materials, see src/debug_view.rs
+// the values of u_debug_view must match DebugView::shader_index
+in vec3 v_position;
+in vec3 v_normal;
+in vec2 v_tex_coord;
+in vec4 v_color;
+
+out vec4 color;
+
+uniform int u_debug_view;
+// the lines of the wireframe and the increment of the overdraw
+uniform vec4 u_debug_color;
+uniform vec3 u_camera_position;
+// near and far planes of the camera
+uniform vec2 u_depth_range;
+
+void main() {
+    if (u_debug_view == 1) {
+        // an 8 by 8 checker tinted by the coordinates, so that both the stretching and the
+        // direction of the uvs show
+        vec2 cell = floor(v_tex_coord * 8.0);
+        float checker = mod(cell.x + cell.y, 2.0);
+        vec3 tint = mix(vec3(fract(v_tex_coord), 0.0), vec3(1.0), 0.5);
+        color = vec4(tint * mix(0.35, 1.0, checker), 1.0);
+    } else if (u_debug_view == 2) {
+        // logarithmic so that the near objects aren't all the same shade, white is near
+        float distance = max(length(v_position - u_camera_position), u_depth_range.x);
+        float depth = log(distance / u_depth_range.x) / log(u_depth_range.y / u_depth_range.x);
+        color = vec4(vec3(1.0 - clamp(depth, 0.0, 1.0)), 1.0);
+    } else {
+        color = u_debug_color;
+    }
+}
@@ -0,0 +1,14 @@
+#version 150
+
+in vec2 v_tex_coord;
+in vec4 v_color;
+
+out vec4 color;
+
+// egui blends in gamma space, the texture and the colours are premultiplied srgb values which are
+// written as they are
+uniform sampler2D u_texture;
+
+void main() {
+    color = v_color * texture(u_texture, v_tex_coord);
+}
@@ -0,0 +1,18 @@
+#version 150
+
+// the meshes of the egui interface, see src/egui_integration.rs
+in vec2 position;
+in vec2 tex_coord;
+in vec4 color;
+
+out vec2 v_tex_coord;
+out vec4 v_color;
+
+// the size of the window in points
+uniform vec2 u_screen_size;
+
+void main() {
+    v_tex_coord = tex_coord;
+    v_color = color;
+    gl_Position = vec4(2.0 * position.x / u_screen_size.x - 1.0, 1.0 - 2.0 * position.y / u_screen_size.y, 0.0, 1.0);
+}
@@ -0,0 +1,42 @@
+// image based lighting from an equirectangular environment, see src/environment.rs
+#define PI 3.14159265359
+
+uniform bool u_use_environment;
+uniform float u_environment_intensity;
+// the mip levels are blurrier and blurrier, the last one being used for rough surfaces
+uniform sampler2D u_environment_map;
+uniform float u_environment_max_lod;
+// irradiance as 9 spherical harmonics coefficients
+uniform vec3 u_irradiance_sh[9];
+// scale and bias applied to the fresnel term, indexed by (n.v, roughness)
+uniform sampler2D u_brdf_lut;
+
+vec2 equirectangular_uv(vec3 direction) {
+    vec3 d = normalize(direction);
+    float u = atan(d.z, d.x) / (2.0 * PI) + 0.5;
+    float v = 1.0 - acos(clamp(d.y, -1.0, 1.0)) / PI;
+    return vec2(u, v);
+}
+
+vec3 environment_irradiance(vec3 n) {
+    const float c1 = 0.429043;
+    const float c2 = 0.511664;
+    const float c3 = 0.743125;
+    const float c4 = 0.886227;
+    const float c5 = 0.247708;
+    return c1 * u_irradiance_sh[8] * (n.x * n.x - n.y * n.y)
+        + c3 * u_irradiance_sh[6] * n.z * n.z
+        + c4 * u_irradiance_sh[0]
+        - c5 * u_irradiance_sh[6]
+        + 2.0 * c1 * (u_irradiance_sh[4] * n.x * n.y
+                      + u_irradiance_sh[7] * n.x * n.z
+                      + u_irradiance_sh[5] * n.y * n.z)
+        + 2.0 * c2 * (u_irradiance_sh[3] * n.x
+                      + u_irradiance_sh[1] * n.y
+                      + u_irradiance_sh[2] * n.z);
+}
+
+vec3 environment_specular(vec3 direction, float roughness) {
+    return textureLod(u_environment_map, equirectangular_uv(direction),
+                      roughness * u_environment_max_lod).rgb;
+}
@@ -1,19 +1,58 @@
-#version 140
+#version 150
 
-in vec3 v_normal; 
+#include "lighting.glsl"
+#include "normal_mapping.glsl"
+#include "debug_view.glsl"
+
+in vec3 v_position;
+in vec3 v_normal;
 in vec2 v_tex_coord;
+in vec4 v_tangent;
+// the colour of the graphic component
+in vec4 v_color;
 
 out vec4 color;
 
-uniform vec3 u_light; 
 uniform sampler2D tex;
 uniform float brightness;
+uniform sampler2D u_normal_map;
+uniform float u_normal_scale;
+
+uniform vec4 u_diffuse_color;
+uniform vec4 u_specular_color;
+uniform float u_shininess;
+uniform vec3 u_camera_position;
+
+#ifdef ALPHA_TEST
+uniform float u_alpha_cutoff;
+#endif
+
+#ifdef LOD_FADE
+#include "lod_fade.glsl"
+#endif
 
 void main() {
-    //float brightness = dot(normalize(v_normal), normalize(u_light));
-    vec4 dark_colour = vec4(0.0, 0.0, 0.0, 1.0);
-    vec4 regular_colour = texture(tex, v_tex_coord);
-    //color = vec4(mix(dark_color, regular_color, brightness));
-    color = vec4(mix(dark_colour, regular_colour, brightness));
-    //color = regular_colour;
+#ifdef LOD_FADE
+    if (!lod_fade_keeps(gl_FragCoord.xy)) {
+        discard;
+    }
+#endif
+    vec4 albedo = texture(tex, v_tex_coord) * u_diffuse_color * v_color;
+#ifdef ALPHA_TEST
+    // the materials of the alpha test queue, see src/render_state.rs
+    if (albedo.a < u_alpha_cutoff) {
+        discard;
+    }
+#endif
+    albedo.rgb = debug_albedo(albedo.rgb);
+    vec3 normal = normalize(v_normal);
+    // lights the back faces as if they were facing us
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+    normal = perturb_normal(u_normal_map, u_normal_scale, normal, v_tangent, v_position, v_tex_coord);
+    vec3 to_eye = normalize(u_camera_position - v_position);
+    vec3 lit = compute_lighting(v_position, normal, to_eye,
+                                albedo.rgb, u_specular_color.rgb, u_shininess);
+    color = vec4(lit * brightness, albedo.a);
 }
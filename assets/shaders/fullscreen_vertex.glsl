@@ -0,0 +1,10 @@
+#version 150
+
+// a single triangle covering the whole screen, drawn without any vertex buffer
+out vec2 v_tex_coord;
+
+void main() {
+    vec2 corner = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    v_tex_coord = corner;
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,50 @@
+#version 150
+
+// fast approximate anti-aliasing (Lottes 2009), blurs the pixels along the edges it finds in the
+// luminance of the image, see src/post_processing.rs
+in vec2 v_tex_coord;
+
+out vec4 color;
+
+uniform sampler2D u_source;
+uniform vec2 u_texel_size;
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float SPAN_MAX = 8.0;
+
+float luma(vec3 c) {
+    return dot(c, vec3(0.299, 0.587, 0.114));
+}
+
+void main() {
+    vec4 center = texture(u_source, v_tex_coord);
+    float luma_nw = luma(texture(u_source, v_tex_coord + vec2(-1.0, -1.0) * u_texel_size).rgb);
+    float luma_ne = luma(texture(u_source, v_tex_coord + vec2(1.0, -1.0) * u_texel_size).rgb);
+    float luma_sw = luma(texture(u_source, v_tex_coord + vec2(-1.0, 1.0) * u_texel_size).rgb);
+    float luma_se = luma(texture(u_source, v_tex_coord + vec2(1.0, 1.0) * u_texel_size).rgb);
+    float luma_m = luma(center.rgb);
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // perpendicular to the gradient of the luminance
+    vec2 direction = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * u_texel_size;
+
+    vec3 near = 0.5 * (
+        texture(u_source, v_tex_coord + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(u_source, v_tex_coord + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 far = near * 0.5 + 0.25 * (
+        texture(u_source, v_tex_coord - direction * 0.5).rgb +
+        texture(u_source, v_tex_coord + direction * 0.5).rgb
+    );
+    // the wider blur is only kept when it doesn't cross another edge
+    float luma_far = luma(far);
+    color = vec4((luma_far < luma_min || luma_far > luma_max) ? near : far, center.a);
+}
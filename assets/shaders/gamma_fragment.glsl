@@ -0,0 +1,15 @@
+#version 150
+
+// gamma correction, for displays which don't convert the linear colours themselves, see
+// src/post_processing.rs
+in vec2 v_tex_coord;
+
+out vec4 color;
+
+uniform sampler2D u_source;
+uniform float u_gamma;
+
+void main() {
+    vec4 source = texture(u_source, v_tex_coord);
+    color = vec4(pow(max(source.rgb, vec3(0.0)), vec3(1.0 / u_gamma)), source.a);
+}
@@ -0,0 +1,23 @@
+// blinn-phong shading of the lights declared in lights.glsl
+#include "lights.glsl"
+
+vec3 blinn_phong(vec3 to_light, vec3 radiance, vec3 normal, vec3 to_eye,
+                 vec3 diffuse, vec3 specular, float shininess) {
+    float n_dot_l = max(dot(normal, to_light), 0.0);
+    vec3 half_dir = normalize(to_light + to_eye);
+    float spec = n_dot_l > 0.0 ? pow(max(dot(normal, half_dir), 0.0), shininess) : 0.0;
+    return radiance * (diffuse * n_dot_l + specular * spec);
+}
+
+// the light reflected towards the eye by every light of the scene
+vec3 compute_lighting(vec3 position, vec3 normal, vec3 to_eye,
+                      vec3 diffuse, vec3 specular, float shininess) {
+    vec3 result = u_ambient_light * diffuse;
+    for (int i = 0; i < light_count(); i++) {
+        vec3 to_light;
+        vec3 radiance;
+        light_at(i, position, normal, to_light, radiance);
+        result += blinn_phong(to_light, radiance, normal, to_eye, diffuse, specular, shininess);
+    }
+    return result;
+}
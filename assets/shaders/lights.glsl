@@ -0,0 +1,155 @@
+// must be kept in sync with the constants of src/light.rs and src/shadow.rs
+#define MAX_DIRECTIONAL_LIGHTS 4
+#define MAX_POINT_LIGHTS 8
+#define MAX_SPOT_LIGHTS 4
+#define MAX_SHADOW_CASCADES 4
+#define MAX_SPOT_SHADOWS 2
+
+struct DirectionalLight {
+    vec3 direction;
+    vec3 color;
+    float intensity;
+};
+
+struct PointLight {
+    vec3 position;
+    vec3 color;
+    float intensity;
+    float range;
+};
+
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+    vec3 color;
+    float intensity;
+    float range;
+    float cos_inner;
+    float cos_outer;
+    // index of the light's shadow map, -1 if it has none
+    int shadow_index;
+};
+
+uniform vec3 u_ambient_light;
+
+uniform DirectionalLight u_directional_lights[MAX_DIRECTIONAL_LIGHTS];
+uniform int u_directional_light_count;
+
+uniform PointLight u_point_lights[MAX_POINT_LIGHTS];
+uniform int u_point_light_count;
+
+uniform SpotLight u_spot_lights[MAX_SPOT_LIGHTS];
+uniform int u_spot_light_count;
+
+uniform mat4 view;
+uniform bool u_receives_shadows;
+uniform float u_shadow_bias;
+uniform float u_shadow_normal_bias;
+
+// cascaded shadow map of a single directional light
+uniform int u_shadowed_directional_light;
+uniform int u_cascade_count;
+uniform float u_cascade_splits[MAX_SHADOW_CASCADES];
+uniform mat4 u_cascade_matrices[MAX_SHADOW_CASCADES];
+uniform sampler2DShadow u_cascade_map0;
+uniform sampler2DShadow u_cascade_map1;
+uniform sampler2DShadow u_cascade_map2;
+uniform sampler2DShadow u_cascade_map3;
+
+uniform mat4 u_spot_shadow_matrices[MAX_SPOT_SHADOWS];
+uniform sampler2DShadow u_spot_shadow_map0;
+uniform sampler2DShadow u_spot_shadow_map1;
+
+// fraction of the light reaching the position, filtered over 3x3 texels (PCF)
+float sample_shadow_map(sampler2DShadow map, mat4 light_matrix, vec3 position, vec3 normal) {
+    vec4 clip = light_matrix * vec4(position + normal * u_shadow_normal_bias, 1.0);
+    vec3 coords = clip.xyz / clip.w * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 1.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(map, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(map, vec3(coords.xy + vec2(x, y) * texel, coords.z - u_shadow_bias));
+        }
+    }
+    return lit / 9.0;
+}
+
+float directional_shadow(vec3 position, vec3 normal) {
+    float depth = (view * vec4(position, 1.0)).z;
+    int cascade = u_cascade_count;
+    for (int i = u_cascade_count - 1; i >= 0; i--) {
+        if (depth < u_cascade_splits[i]) {
+            cascade = i;
+        }
+    }
+    if (cascade == 0) {
+        return sample_shadow_map(u_cascade_map0, u_cascade_matrices[0], position, normal);
+    } else if (cascade == 1) {
+        return sample_shadow_map(u_cascade_map1, u_cascade_matrices[1], position, normal);
+    } else if (cascade == 2) {
+        return sample_shadow_map(u_cascade_map2, u_cascade_matrices[2], position, normal);
+    } else if (cascade == 3) {
+        return sample_shadow_map(u_cascade_map3, u_cascade_matrices[3], position, normal);
+    }
+    // further than the last cascade
+    return 1.0;
+}
+
+float spot_shadow(int index, vec3 position, vec3 normal) {
+    if (index == 0) {
+        return sample_shadow_map(u_spot_shadow_map0, u_spot_shadow_matrices[0], position, normal);
+    } else if (index == 1) {
+        return sample_shadow_map(u_spot_shadow_map1, u_spot_shadow_matrices[1], position, normal);
+    }
+    return 1.0;
+}
+
+// smooth falloff which reaches exactly zero at range
+float range_attenuation(float distance, float range) {
+    float ratio = distance / max(range, 0.0001);
+    float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+int light_count() {
+    return u_directional_light_count + u_point_light_count + u_spot_light_count;
+}
+
+// direction towards the light and light reaching the position, shadows included
+// the directional lights come first, then the point lights and finally the spot lights
+void light_at(int index, vec3 position, vec3 normal, out vec3 to_light, out vec3 radiance) {
+    if (index < u_directional_light_count) {
+        DirectionalLight light = u_directional_lights[index];
+        to_light = -normalize(light.direction);
+        radiance = light.color * light.intensity;
+        if (u_receives_shadows && index == u_shadowed_directional_light) {
+            radiance *= directional_shadow(position, normal);
+        }
+        return;
+    }
+    index -= u_directional_light_count;
+
+    if (index < u_point_light_count) {
+        PointLight light = u_point_lights[index];
+        to_light = light.position - position;
+        float distance = length(to_light);
+        to_light /= max(distance, 0.0001);
+        radiance = light.color * light.intensity * range_attenuation(distance, light.range);
+        return;
+    }
+    index -= u_point_light_count;
+
+    SpotLight light = u_spot_lights[index];
+    to_light = light.position - position;
+    float distance = length(to_light);
+    to_light /= max(distance, 0.0001);
+    float cos_angle = dot(-to_light, normalize(light.direction));
+    float cone = smoothstep(light.cos_outer, light.cos_inner, cos_angle);
+    radiance = light.color * light.intensity * cone * range_attenuation(distance, light.range);
+    if (u_receives_shadows && light.shadow_index >= 0) {
+        radiance *= spot_shadow(light.shadow_index, position, normal);
+    }
+}
@@ -0,0 +1,22 @@
+// dithered cross fade between two levels of detail, see src/lod.rs
+// a positive u_lod_fade keeps that fraction of the pixels, a negative one keeps the others, so
+// that the level fading out and the one fading in never cover the same pixel
+
+uniform float u_lod_fade;
+
+// 4x4 ordered dither, the same pattern repeats over the screen
+const float BAYER[16] = float[16](
+    0.0, 8.0, 2.0, 10.0,
+    12.0, 4.0, 14.0, 6.0,
+    3.0, 11.0, 1.0, 9.0,
+    15.0, 7.0, 13.0, 5.0
+);
+
+bool lod_fade_keeps(vec2 frag_coord) {
+    ivec2 cell = ivec2(mod(frag_coord, 4.0));
+    float noise = (BAYER[cell.x + cell.y * 4] + 0.5) / 16.0;
+    if (u_lod_fade >= 0.0) {
+        return noise < u_lod_fade;
+    }
+    return noise >= 1.0 + u_lod_fade;
+}
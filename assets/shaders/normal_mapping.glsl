@@ -0,0 +1,32 @@
+// tangent space normal maps, the vertex tangents come from load_model (mikktspace)
+
+// tangent frame built from the screen space derivatives, for meshes without tangents
+mat3 cotangent_frame(vec3 n, vec3 p, vec2 uv) {
+    vec3 dp1 = dFdx(p);
+    vec3 dp2 = dFdy(p);
+    vec2 duv1 = dFdx(uv);
+    vec2 duv2 = dFdy(uv);
+    vec3 dp2perp = cross(dp2, n);
+    vec3 dp1perp = cross(n, dp1);
+    vec3 t = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 b = dp2perp * duv1.y + dp1perp * duv2.y;
+    float invmax = inversesqrt(max(max(dot(t, t), dot(b, b)), 1e-8));
+    return mat3(t * invmax, b * invmax, n);
+}
+
+// the tangent is interpolated so it has to be orthogonalised against the normal again
+mat3 tangent_frame(vec3 n, vec4 tangent, vec3 p, vec2 uv) {
+    if (dot(tangent.xyz, tangent.xyz) < 1e-8) {
+        return cotangent_frame(n, p, uv);
+    }
+    vec3 t = normalize(tangent.xyz - n * dot(n, tangent.xyz));
+    vec3 b = cross(n, t) * (tangent.w < 0.0 ? -1.0 : 1.0);
+    return mat3(t, b, n);
+}
+
+// the normal to shade with, n is the geometric normal already facing the eye
+vec3 perturb_normal(sampler2D normal_map, float scale, vec3 n, vec4 tangent, vec3 p, vec2 uv) {
+    vec3 tangent_normal = texture(normal_map, uv).xyz * 2.0 - 1.0;
+    tangent_normal.xy *= scale;
+    return normalize(tangent_frame(n, tangent, p, uv) * tangent_normal);
+}
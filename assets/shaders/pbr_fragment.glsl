@@ -0,0 +1,106 @@
+#version 150
+
+// metallic-roughness shading, the maps follow the gltf conventions
+#include "lights.glsl"
+#include "environment.glsl"
+#include "normal_mapping.glsl"
+#include "debug_view.glsl"
+
+in vec3 v_position;
+in vec3 v_normal;
+in vec2 v_tex_coord;
+in vec4 v_tangent;
+// the colour of the graphic component
+in vec4 v_color;
+
+out vec4 color;
+
+uniform vec3 u_camera_position;
+
+uniform sampler2D u_albedo_map;
+// roughness in the green channel and metalness in the blue one
+uniform sampler2D u_metallic_roughness_map;
+uniform sampler2D u_normal_map;
+uniform sampler2D u_occlusion_map;
+uniform sampler2D u_emissive_map;
+
+uniform vec4 u_albedo_color;
+uniform float u_metallic;
+uniform float u_roughness;
+uniform vec3 u_emissive_color;
+uniform float u_normal_scale;
+uniform float u_occlusion_strength;
+
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0)
+        * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+#ifdef ALPHA_TEST
+uniform float u_alpha_cutoff;
+#endif
+
+#ifdef LOD_FADE
+#include "lod_fade.glsl"
+#endif
+
+void main() {
+#ifdef LOD_FADE
+    if (!lod_fade_keeps(gl_FragCoord.xy)) {
+        discard;
+    }
+#endif
+    vec4 albedo = texture(u_albedo_map, v_tex_coord) * u_albedo_color * v_color;
+#ifdef ALPHA_TEST
+    // the materials of the alpha test queue, see src/render_state.rs
+    if (albedo.a < u_alpha_cutoff) {
+        discard;
+    }
+#endif
+    albedo.rgb = debug_albedo(albedo.rgb);
+    vec4 metallic_roughness = texture(u_metallic_roughness_map, v_tex_coord);
+    float metallic = clamp(metallic_roughness.b * u_metallic, 0.0, 1.0);
+    float roughness = clamp(metallic_roughness.g * u_roughness, 0.04, 1.0);
+    float occlusion = mix(1.0, texture(u_occlusion_map, v_tex_coord).r, u_occlusion_strength);
+    vec3 emissive = texture(u_emissive_map, v_tex_coord).rgb * u_emissive_color;
+
+    vec3 geometric_normal = normalize(v_normal);
+    if (!gl_FrontFacing) {
+        geometric_normal = -geometric_normal;
+    }
+    vec3 n = perturb_normal(u_normal_map, u_normal_scale, geometric_normal, v_tangent,
+                            v_position, v_tex_coord);
+
+    vec3 v = normalize(u_camera_position - v_position);
+    float n_dot_v = max(dot(n, v), 1e-4);
+    vec3 f0 = mix(vec3(0.04), albedo.rgb, metallic);
+
+    vec3 lo = vec3(0.0);
+    for (int i = 0; i < light_count(); i++) {
+        vec3 l;
+        vec3 radiance;
+        light_at(i, v_position, n, l, radiance);
+        vec3 h = normalize(v + l);
+        float n_dot_l = max(dot(n, l), 0.0);
+        float d = distribution_ggx(max(dot(n, h), 0.0), roughness);
+        float g = geometry_smith(n_dot_v, n_dot_l, roughness);
+        vec3 f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+        vec3 specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 1e-4);
+        vec3 diffuse = (1.0 - f) * (1.0 - metallic) * albedo.rgb / PI;
+        lo += (diffuse + specular) * radiance * n_dot_l;
+    }
+
+    vec3 ambient;
+    if (u_use_environment) {
+        vec3 f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+        vec3 diffuse = (1.0 - f) * (1.0 - metallic) * albedo.rgb
+            * environment_irradiance(n) / PI;
+        vec2 brdf = texture(u_brdf_lut, vec2(n_dot_v, roughness)).rg;
+        vec3 specular = environment_specular(reflect(-v, n), roughness) * (f * brdf.x + brdf.y);
+        ambient = (diffuse + specular) * u_environment_intensity;
+    } else {
+        ambient = u_ambient_light * albedo.rgb;
+    }
+
+    color = vec4(lo + ambient * occlusion + emissive, albedo.a);
+}
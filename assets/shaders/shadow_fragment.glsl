@@ -0,0 +1,5 @@
+#version 150
+
+// only the depth is written
+void main() {
+}
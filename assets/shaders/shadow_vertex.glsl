@@ -0,0 +1,10 @@
+#version 150
+
+in vec3 position;
+
+uniform mat4 matrix;
+uniform mat4 u_light_matrix;
+
+void main() {
+    gl_Position = u_light_matrix * matrix * vec4(position, 1.0);
+}
@@ -0,0 +1,71 @@
+#version 150
+
+// screen space ambient occlusion, darkens the creases by looking for the depth of the pixels
+// around every pixel, see src/post_processing.rs
+in vec2 v_tex_coord;
+
+out vec4 color;
+
+uniform sampler2D u_source;
+uniform sampler2D u_depth;
+uniform mat4 u_projection;
+uniform mat4 u_inverse_projection;
+uniform float u_radius;
+uniform float u_intensity;
+uniform float u_bias;
+
+const int SAMPLE_COUNT = 16;
+
+// position in view space from the depth buffer
+vec3 view_position(vec2 uv) {
+    float depth = texture(u_depth, uv).r;
+    vec4 ndc = vec4(uv * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+    vec4 position = u_inverse_projection * ndc;
+    return position.xyz / position.w;
+}
+
+// interleaved gradient noise (Jimenez 2014), rotates the samples from one pixel to the next
+float noise(vec2 p) {
+    return fract(52.9829189 * fract(dot(p, vec2(0.06711056, 0.00583715))));
+}
+
+vec3 hemisphere_sample(int i, float angle) {
+    // spiral over the hemisphere, denser near its center
+    float t = (float(i) + 0.5) / float(SAMPLE_COUNT);
+    float phi = float(i) * 2.39996323 + angle;
+    float z = sqrt(1.0 - t);
+    float r = sqrt(t);
+    float scale = mix(0.1, 1.0, t * t);
+    return vec3(cos(phi) * r, sin(phi) * r, z) * scale;
+}
+
+void main() {
+    vec4 source = texture(u_source, v_tex_coord);
+    // the background has no occlusion
+    if (texture(u_depth, v_tex_coord).r >= 1.0) {
+        color = source;
+        return;
+    }
+    vec3 position = view_position(v_tex_coord);
+    // the view space looks towards +z, the normal points back to the camera
+    vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+    if (normal.z > 0.0) {
+        normal = -normal;
+    }
+    vec3 tangent = normalize(abs(normal.y) < 0.99 ? cross(normal, vec3(0.0, 1.0, 0.0)) : cross(normal, vec3(1.0, 0.0, 0.0)));
+    mat3 frame = mat3(tangent, cross(normal, tangent), normal);
+    float angle = noise(gl_FragCoord.xy) * 6.2831853;
+
+    float occlusion = 0.0;
+    for (int i = 0; i < SAMPLE_COUNT; i++) {
+        vec3 sample_position = position + frame * hemisphere_sample(i, angle) * u_radius;
+        vec4 projected = u_projection * vec4(sample_position, 1.0);
+        vec2 uv = projected.xy / projected.w * 0.5 + 0.5;
+        float scene_depth = view_position(uv).z;
+        // the objects far in front of the sample don't occlude it
+        float range = smoothstep(0.0, 1.0, u_radius / abs(position.z - scene_depth));
+        occlusion += (scene_depth <= sample_position.z - u_bias ? 1.0 : 0.0) * range;
+    }
+    float ambient = clamp(1.0 - occlusion / float(SAMPLE_COUNT) * u_intensity, 0.0, 1.0);
+    color = vec4(source.rgb * ambient, source.a);
+}
@@ -0,0 +1,17 @@
+#version 150
+
+in vec2 v_tex_coord;
+in vec4 v_color;
+
+out vec4 color;
+
+// the coverage of the glyphs is in the alpha channel
+uniform sampler2D u_atlas;
+
+void main() {
+    float coverage = texture(u_atlas, v_tex_coord).a;
+    if (coverage <= 0.0) {
+        discard;
+    }
+    color = vec4(v_color.rgb, v_color.a * coverage);
+}
@@ -0,0 +1,18 @@
+#version 150
+
+// the quads of the glyphs, see src/text.rs, and of the interface, see src/ui.rs
+in vec3 position;
+in vec2 tex_coord;
+in vec4 color;
+
+out vec2 v_tex_coord;
+out vec4 v_color;
+
+// the projection of the screen in pixels, or perspective * view for the texts in the world
+uniform mat4 u_matrix;
+
+void main() {
+    v_tex_coord = tex_coord;
+    v_color = color;
+    gl_Position = u_matrix * vec4(position, 1.0);
+}
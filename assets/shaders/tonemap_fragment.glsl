@@ -0,0 +1,34 @@
+#version 150
+
+in vec2 v_tex_coord;
+
+out vec4 color;
+
+uniform sampler2D u_hdr_color;
+uniform float u_exposure;
+// 0: none, 1: reinhard, 2: aces, must match src/tonemapping.rs
+uniform int u_tonemapping;
+
+// fit of the aces filmic curve by Krzysztof Narkowicz
+vec3 aces(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+void main() {
+    vec4 hdr = texture(u_hdr_color, v_tex_coord);
+    vec3 exposed = hdr.rgb * u_exposure;
+    vec3 mapped;
+    if (u_tonemapping == 1) {
+        mapped = exposed / (exposed + 1.0);
+    } else if (u_tonemapping == 2) {
+        mapped = aces(exposed);
+    } else {
+        mapped = clamp(exposed, 0.0, 1.0);
+    }
+    color = vec4(mapped, hdr.a);
+}
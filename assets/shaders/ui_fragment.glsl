@@ -0,0 +1,17 @@
+#version 150
+
+// the quads of the interface, see src/ui.rs, the panels sample a white texture and the texts the
+// atlas of their font, which is white with the coverage in alpha
+in vec2 v_tex_coord;
+in vec4 v_color;
+
+out vec4 color;
+
+uniform sampler2D u_texture;
+
+void main() {
+    color = v_color * texture(u_texture, v_tex_coord);
+    if (color.a <= 0.0) {
+        discard;
+    }
+}
@@ -3,18 +3,38 @@
 in vec3 position;
 in vec3 normal;
 in vec2 tex_coord;
+in vec4 tangent;
 
+// world space, which is where the lighting is computed
+out vec3 v_position;
 out vec3 v_normal;
 out vec2 v_tex_coord;
+out vec4 v_tangent;
+out vec4 v_color;
 
+#ifdef INSTANCING
+// per instance attributes, see src/instancing.rs
+in mat4 i_matrix;
+in vec4 i_color;
+#else
 uniform mat4 matrix;
+uniform vec4 u_object_color;
+#endif
 uniform mat4 perspective;
 uniform mat4 view;
 //uniform mat4 resize;
 
 void main() {
-    mat4 modelview = view * matrix;
+#ifdef INSTANCING
+    mat4 matrix = i_matrix;
+    v_color = i_color;
+#else
+    v_color = u_object_color;
+#endif
+    vec4 world_position = matrix * vec4(position, 1.0);
+    v_position = world_position.xyz;
     v_tex_coord = tex_coord;
-    v_normal = transpose(inverse(mat3(modelview))) * normal;
-    gl_Position = perspective * modelview * vec4(position, 1.0);
+    v_normal = transpose(inverse(mat3(matrix))) * normal;
+    v_tangent = vec4(mat3(matrix) * tangent.xyz, tangent.w);
+    gl_Position = perspective * view * world_position;
 }
@@ -0,0 +1,19 @@
+#version 150
+
+// darkens the borders of the image, see src/post_processing.rs
+in vec2 v_tex_coord;
+
+out vec4 color;
+
+uniform sampler2D u_source;
+uniform float u_intensity;
+// how far from the corners the darkening starts, 1 starts at the center
+uniform float u_smoothness;
+
+void main() {
+    vec4 source = texture(u_source, v_tex_coord);
+    // 0 at the center and 1 in the corners
+    float distance = length(v_tex_coord - 0.5) * 1.41421356;
+    float vignette = 1.0 - u_intensity * smoothstep(1.0 - u_smoothness, 1.0, distance);
+    color = vec4(source.rgb * vignette, source.a);
+}
@@ -0,0 +1,259 @@
+#![allow(dead_code)]
+
+use std::f32::consts::PI;
+
+use glium::index::NoIndices;
+use glium::index::PrimitiveType;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
+use glium::uniforms::SamplerBehavior;
+use glium::uniforms::SamplerWrapFunction;
+use glium::uniforms::UniformValue;
+use glium::vertex::EmptyVertexAttributes;
+use glium::Program;
+use glium::Surface;
+
+use crate::environment::Environment;
+use crate::environment::HdrImage;
+use crate::error::EngineError;
+use crate::shader::ShaderVariant;
+use crate::texture::Texture;
+use crate::texture::TextureSettings;
+use crate::uniforms::UniformBag;
+
+pub const BACKGROUND_VERTEX_SHADER: &str = "builtin/background_vertex.glsl";
+pub const BACKGROUND_FRAGMENT_SHADER: &str = "builtin/background_fragment.glsl";
+
+// size of the image the colour and gradient backgrounds are turned into when they light the scene
+const LIGHTING_IMAGE_WIDTH: u32 = 64;
+
+// what is seen where no object is drawn, set for a whole scene with Scene::set_background or for
+// a single camera with its background field
+#[derive(Clone, Debug, PartialEq)]
+pub enum Background {
+    // the screen is simply cleared with this colour
+    Color([f32; 3]),
+    // blends from the colour of the sky straight up to the colour of the ground straight down,
+    // it moves with the camera like a real sky would
+    Gradient { top: [f32; 3], bottom: [f32; 3] },
+    // a cubemap texture (a dds or ktx2 file with six faces)
+    Skybox { path: String, intensity: f32 },
+    // an equirectangular image, usually an hdr or exr file
+    Panorama { path: String, intensity: f32 },
+    // the image given to Scene::set_environment, so that the sky matches the reflections
+    Environment,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        return Background::Color([0.0, 0.0, 1.0]);
+    }
+}
+
+impl Background {
+    pub fn skybox(path: &str) -> Self {
+        return Background::Skybox {
+            path: path.to_string(),
+            intensity: 1.0,
+        };
+    }
+
+    pub fn panorama(path: &str) -> Self {
+        return Background::Panorama {
+            path: path.to_string(),
+            intensity: 1.0,
+        };
+    }
+
+    // the colour the target is cleared with before the objects are drawn
+    pub fn clear_color(&self) -> (f32, f32, f32, f32) {
+        match self {
+            Background::Color([r, g, b]) => (*r, *g, *b, 1.0),
+            _ => (0.0, 0.0, 0.0, 1.0),
+        }
+    }
+
+    // the texture of the background, it is loaded in the texture cache of the scene
+    pub fn texture_path(&self) -> Option<&str> {
+        match self {
+            Background::Skybox { path, .. } | Background::Panorama { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    // skyboxes and panoramas are colour images, the float and compressed formats ignore this
+    pub fn texture_settings(&self) -> TextureSettings {
+        return TextureSettings::default().with_srgb(true);
+    }
+
+    // the variant drawing the background after the objects, the solid colour doesn't need one
+    pub fn shader_variant(&self) -> Option<ShaderVariant> {
+        let keyword = match self {
+            Background::Color(_) => return None,
+            Background::Gradient { .. } => "BACKGROUND_GRADIENT",
+            Background::Skybox { .. } => "BACKGROUND_SKYBOX",
+            Background::Panorama { .. } | Background::Environment => "BACKGROUND_PANORAMA",
+        };
+        let variant = ShaderVariant::new(BACKGROUND_VERTEX_SHADER, BACKGROUND_FRAGMENT_SHADER);
+        return Some(variant.with_keyword(keyword));
+    }
+
+    // every variant which may be needed, they are compiled with the scene
+    pub fn shader_variants() -> Vec<ShaderVariant> {
+        let backgrounds = [
+            Background::Gradient {
+                top: [0.0; 3],
+                bottom: [0.0; 3],
+            },
+            Background::skybox(""),
+            Background::panorama(""),
+        ];
+        return backgrounds.iter().filter_map(|b| b.shader_variant()).collect();
+    }
+
+    // an equirectangular image of the background, for the colours and the gradients only since
+    // the other backgrounds are already images
+    pub fn to_hdr_image(&self) -> Option<HdrImage> {
+        let (top, bottom) = match self {
+            Background::Color(color) => (*color, *color),
+            Background::Gradient { top, bottom } => (*top, *bottom),
+            _ => return None,
+        };
+        let (width, height) = (LIGHTING_IMAGE_WIDTH, LIGHTING_IMAGE_WIDTH / 2);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            // same mapping as the shader, the first row is straight up
+            let up = (PI * (y as f32 + 0.5) / height as f32).cos();
+            let t = up * 0.5 + 0.5;
+            let color = [0, 1, 2].map(|c| bottom[c] + (top[c] - bottom[c]) * t);
+            pixels.extend(std::iter::repeat_n(color, width as usize));
+        }
+        return Some(HdrImage {
+            width,
+            height,
+            pixels,
+        });
+    }
+}
+
+fn skybox_sampler() -> SamplerBehavior {
+    SamplerBehavior {
+        wrap_function: (
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+        ),
+        minify_filter: MinifySamplerFilter::Linear,
+        magnify_filter: MagnifySamplerFilter::Linear,
+        ..Default::default()
+    }
+}
+
+fn panorama_sampler() -> SamplerBehavior {
+    SamplerBehavior {
+        wrap_function: (
+            SamplerWrapFunction::Repeat,
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+        ),
+        minify_filter: MinifySamplerFilter::Linear,
+        magnify_filter: MagnifySamplerFilter::Linear,
+        ..Default::default()
+    }
+}
+
+// draws the background behind the objects, on a surface which already holds them and their depth
+// the background is drawn on the far plane so only the pixels no object covers are touched
+#[allow(clippy::too_many_arguments)]
+pub fn draw_background<S: Surface>(
+    surface: &mut S,
+    background: &Background,
+    program: &Program,
+    texture: Option<&Texture>,
+    environment: Option<&Environment>,
+    view: [[f32; 4]; 4],
+    perspective: [[f32; 4]; 4],
+) -> Result<(), EngineError> {
+    let mut uniforms = UniformBag::new();
+    uniforms.set("view", UniformValue::Mat4(view));
+    uniforms.set("perspective", UniformValue::Mat4(perspective));
+    match background {
+        Background::Color(_) => return Ok(()),
+        Background::Gradient { top, bottom } => {
+            uniforms.set("u_top_color", UniformValue::Vec3(*top));
+            uniforms.set("u_bottom_color", UniformValue::Vec3(*bottom));
+            uniforms.set("u_intensity", UniformValue::Float(1.0));
+        }
+        Background::Skybox { intensity, .. } => {
+            let Some(texture) = texture.filter(|texture| texture.is_cubemap()) else {
+                return Err(EngineError::Draw {
+                    message: "the skybox texture is not a cubemap".to_string(),
+                });
+            };
+            uniforms.set("u_skybox", texture.uniform_value(Some(skybox_sampler())));
+            uniforms.set("u_intensity", UniformValue::Float(*intensity));
+        }
+        Background::Panorama { intensity, .. } => {
+            let Some(texture) = texture.filter(|texture| !texture.is_cubemap()) else {
+                return Err(EngineError::Draw {
+                    message: "the panorama texture is missing or is a cubemap".to_string(),
+                });
+            };
+            uniforms.set("u_panorama", texture.uniform_value(Some(panorama_sampler())));
+            uniforms.set("u_intensity", UniformValue::Float(*intensity));
+        }
+        Background::Environment => {
+            // nothing to draw until the environment is loaded
+            let Some(environment) = environment else {
+                return Ok(());
+            };
+            let specular = UniformValue::Texture2d(&environment.specular, Some(panorama_sampler()));
+            uniforms.set("u_panorama", specular);
+            uniforms.set("u_intensity", UniformValue::Float(environment.intensity));
+        }
+    }
+
+    let params = glium::DrawParameters {
+        depth: glium::Depth {
+            test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+            write: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    surface.draw(
+        EmptyVertexAttributes { len: 3 },
+        NoIndices(PrimitiveType::TrianglesList),
+        program,
+        &uniforms,
+        &params,
+    )?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_image_goes_from_top_to_bottom() {
+        let background = Background::Gradient {
+            top: [1.0, 0.0, 0.0],
+            bottom: [0.0, 0.0, 1.0],
+        };
+        let image = background.to_hdr_image().unwrap();
+        assert_eq!(image.pixels.len(), (image.width * image.height) as usize);
+        let first = image.pixels[0];
+        let last = image.pixels[image.pixels.len() - 1];
+        assert!(first[0] > 0.99 && first[2] < 0.01);
+        assert!(last[2] > 0.99 && last[0] < 0.01);
+        assert!(Background::skybox("sky.dds").to_hdr_image().is_none());
+    }
+
+    #[test]
+    fn only_the_solid_colour_has_no_pass() {
+        assert!(Background::Color([0.0; 3]).shader_variant().is_none());
+        assert!(Background::Environment.shader_variant().is_some());
+        assert_eq!(Background::shader_variants().len(), 3);
+    }
+}
@@ -0,0 +1,244 @@
+#![allow(dead_code)]
+
+use cgmath::Matrix4;
+use cgmath::Vector4;
+
+// axis aligned bounding box
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    // an empty mesh gets an empty box at the origin
+    pub fn from_points(points: &[[f32; 3]]) -> Self {
+        let Some(first) = points.first() else {
+            return Aabb {
+                min: [0.0; 3],
+                max: [0.0; 3],
+            };
+        };
+        let mut aabb = Aabb {
+            min: *first,
+            max: *first,
+        };
+        for point in points {
+            aabb.min = [0, 1, 2].map(|i| aabb.min[i].min(point[i]));
+            aabb.max = [0, 1, 2].map(|i| aabb.max[i].max(point[i]));
+        }
+        return aabb;
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        return [0, 1, 2].map(|i| (self.min[i] + self.max[i]) * 0.5);
+    }
+
+    // half of the size along every axis
+    pub fn extents(&self) -> [f32; 3] {
+        return [0, 1, 2].map(|i| (self.max[i] - self.min[i]) * 0.5);
+    }
+
+    // the box containing this one once transformed, by projecting the extents on the axes of the
+    // matrix (Arvo 1990), it is larger than the transformed box when there is a rotation
+    // the matrix is column major, like the ones we send to the shaders
+    pub fn transformed(&self, matrix: &[[f32; 4]; 4]) -> Aabb {
+        let center = self.center();
+        let extents = self.extents();
+        let mut min = [0.0; 3];
+        let mut max = [0.0; 3];
+        for row in 0..3 {
+            let mut c = matrix[3][row];
+            let mut e = 0.0;
+            for column in 0..3 {
+                c += matrix[column][row] * center[column];
+                e += matrix[column][row].abs() * extents[column];
+            }
+            min[row] = c - e;
+            max[row] = c + e;
+        }
+        return Aabb { min, max };
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    // centered on the box of the points, which is not the smallest sphere but is close enough
+    pub fn from_points(points: &[[f32; 3]]) -> Self {
+        let center = Aabb::from_points(points).center();
+        let radius = points
+            .iter()
+            .map(|p| distance_squared(*p, center))
+            .fold(0.0f32, f32::max)
+            .sqrt();
+        return BoundingSphere { center, radius };
+    }
+
+    // the radius grows with the largest scale of the matrix so that the sphere still contains
+    // the transformed points when the scale isn't uniform
+    pub fn transformed(&self, matrix: &[[f32; 4]; 4]) -> BoundingSphere {
+        let m = Matrix4::from(*matrix);
+        let [x, y, z] = self.center;
+        let center = m * Vector4::new(x, y, z, 1.0);
+        let scale = (0..3)
+            .map(|column| {
+                let axis = matrix[column];
+                (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt()
+            })
+            .fold(0.0f32, f32::max);
+        return BoundingSphere {
+            center: [center.x, center.y, center.z],
+            radius: self.radius * scale,
+        };
+    }
+}
+
+fn distance_squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    return (0..3).map(|i| (a[i] - b[i]) * (a[i] - b[i])).sum();
+}
+
+// the six planes bounding what a camera sees, their normals point inwards
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+    // a, b, c, d with ax + by + cz + d >= 0 inside, (a, b, c) being of unit length
+    pub planes: [[f32; 4]; 6],
+}
+
+impl Frustum {
+    // extracts the planes from projection * view (Gribb and Hartmann), the depth range of the
+    // projection is [-1, 1] as in opengl, the matrix is column major
+    pub fn from_matrix(matrix: &[[f32; 4]; 4]) -> Self {
+        let row = |i: usize| [matrix[0][i], matrix[1][i], matrix[2][i], matrix[3][i]];
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let combine = |a: [f32; 4], b: [f32; 4], sign: f32| [0, 1, 2, 3].map(|i| a[i] + sign * b[i]);
+        let mut planes = [
+            combine(w, x, 1.0),
+            combine(w, x, -1.0),
+            combine(w, y, 1.0),
+            combine(w, y, -1.0),
+            combine(w, z, 1.0),
+            combine(w, z, -1.0),
+        ];
+        for plane in planes.iter_mut() {
+            let length = (plane[0] * plane[0] + plane[1] * plane[1] + plane[2] * plane[2]).sqrt();
+            if length > 0.0 {
+                *plane = plane.map(|c| c / length);
+            }
+        }
+        return Frustum { planes };
+    }
+
+    pub fn from_view_projection(view: &[[f32; 4]; 4], projection: &[[f32; 4]; 4]) -> Self {
+        let matrix: [[f32; 4]; 4] = (Matrix4::from(*projection) * Matrix4::from(*view)).into();
+        return Frustum::from_matrix(&matrix);
+    }
+
+    fn distance(plane: &[f32; 4], point: [f32; 3]) -> f32 {
+        return plane[0] * point[0] + plane[1] * point[1] + plane[2] * point[2] + plane[3];
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        return self
+            .planes
+            .iter()
+            .all(|plane| Frustum::distance(plane, sphere.center) >= -sphere.radius);
+    }
+
+    // conservative, some boxes near the corners of the frustum are kept although outside
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        return self.planes.iter().all(|plane| {
+            // the corner furthest along the normal of the plane
+            let corner = [0, 1, 2].map(|i| if plane[i] >= 0.0 { aabb.max[i] } else { aabb.min[i] });
+            Frustum::distance(plane, corner) >= 0.0
+        });
+    }
+}
+
+// what the last frame of a scene submitted, see Scene::render_stats
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub drawn: usize,
+    // outside of the frustum of the camera or past the last level of their lod group
+    pub culled: usize,
+    // the instanced objects of a batch share a single call
+    pub draw_calls: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::camera::Camera;
+
+    fn unit_cube() -> Aabb {
+        return Aabb {
+            min: [-1.0; 3],
+            max: [1.0; 3],
+        };
+    }
+
+    fn translation(x: f32, y: f32, z: f32) -> [[f32; 4]; 4] {
+        return [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [x, y, z, 1.0],
+        ];
+    }
+
+    #[test]
+    fn bounds_of_points() {
+        let points = [[1.0, 0.0, 0.0], [-1.0, 2.0, 0.0], [0.0, 0.0, 3.0]];
+        let aabb = Aabb::from_points(&points);
+        assert_eq!(aabb.min, [-1.0, 0.0, 0.0]);
+        assert_eq!(aabb.max, [1.0, 2.0, 3.0]);
+        let sphere = BoundingSphere::from_points(&points);
+        for point in points {
+            assert!(distance_squared(point, sphere.center).sqrt() <= sphere.radius + 1e-5);
+        }
+    }
+
+    #[test]
+    fn transformed_bounds() {
+        let moved = unit_cube().transformed(&translation(5.0, 0.0, 0.0));
+        assert_eq!(moved.min, [4.0, -1.0, -1.0]);
+        assert_eq!(moved.max, [6.0, 1.0, 1.0]);
+        // 45 degrees around y, the box grows to contain the rotated corners
+        let (s, c) = std::f32::consts::FRAC_PI_4.sin_cos();
+        let rotation = [
+            [c, 0.0, -s, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [s, 0.0, c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let rotated = unit_cube().transformed(&rotation);
+        assert!((rotated.max[0] - 2.0f32.sqrt()).abs() < 1e-5);
+        assert!((rotated.max[1] - 1.0).abs() < 1e-5);
+        let mut scaled = translation(0.0, 0.0, 0.0);
+        scaled[1][1] = 3.0;
+        let sphere = BoundingSphere {
+            center: [0.0; 3],
+            radius: 1.0,
+        };
+        assert_eq!(sphere.transformed(&scaled).radius, 3.0);
+    }
+
+    #[test]
+    fn camera_frustum() {
+        // the default camera is at z = -5 and looks towards +z
+        let camera = Camera::new();
+        let frustum = Frustum::from_view_projection(&camera.view_matrix(), &camera.perspective_matrix(800, 600));
+        let sphere = |center: [f32; 3]| BoundingSphere { center, radius: 1.0 };
+        assert!(frustum.intersects_sphere(&sphere([0.0, 0.0, 0.0])));
+        assert!(!frustum.intersects_sphere(&sphere([0.0, 0.0, -10.0])));
+        assert!(!frustum.intersects_sphere(&sphere([100.0, 0.0, 0.0])));
+        assert!(!frustum.intersects_sphere(&sphere([0.0, 0.0, 2000.0])));
+        assert!(frustum.intersects_aabb(&unit_cube()));
+        assert!(!frustum.intersects_aabb(&unit_cube().transformed(&translation(0.0, 50.0, 0.0))));
+    }
+}
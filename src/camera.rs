@@ -4,14 +4,32 @@
 use cgmath::Quaternion;
 use cgmath::Vector3;
 
+use crate::background::Background;
+use crate::bounds::Frustum;
+use crate::debug_view::DebugView;
+use crate::post_processing::PostEffect;
 use crate::transform::rotation_to_direction;
 use crate::transform::v3_normalised;
+use crate::tonemapping::Tonemapping;
 use crate::transform::Transform;
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct Camera {
     pub transform: Transform,
     pub fov: f64,
+    // distances of the near and far clipping planes
+    pub znear: f32,
+    pub zfar: f32,
+    // when set, the scene is rendered in a floating point target then tonemapped
+    pub hdr: bool,
+    pub exposure: f32,
+    pub tonemapping: Tonemapping,
+    // replaces the background of the scene for this camera
+    pub background: Option<Background>,
+    // full screen passes run on the image before it is presented, see post_processing
+    pub post_effects: Vec<PostEffect>,
+    // how the objects are drawn, to inspect the models and the lighting, see debug_view
+    pub debug_view: DebugView,
 }
 
 impl Camera {
@@ -23,28 +41,61 @@ impl Camera {
                 Vector3::new(1.0, 1.0, 1.0),
             ),
             fov: 1.0,
+            znear: 0.1,
+            zfar: 1024.0,
+            hdr: false,
+            exposure: 1.0,
+            tonemapping: Tonemapping::Aces,
+            background: None,
+            post_effects: Vec::new(),
+            debug_view: DebugView::Shaded,
         }
     }
 
-    pub fn get_transform(self) -> Transform {
+    pub fn add_post_effect(&mut self, effect: PostEffect) {
+        self.post_effects.push(effect);
+    }
+
+    pub fn set_debug_view(&mut self, view: DebugView) {
+        self.debug_view = view;
+    }
+
+    pub fn get_transform(&self) -> Transform {
         return self.transform;
     }
 
-    pub fn set_position(mut self, new_pos: Vector3<f32>) -> () {
+    pub fn set_position(&mut self, new_pos: Vector3<f32>) -> () {
         self.transform.set_position(new_pos);
     }
 
-    pub fn get_fwd(self) -> Vector3<f32> {
+    pub fn get_fwd(&self) -> Vector3<f32> {
         let fwd = Vector3::new(0.0, 0.0, 1.0);
         return rotation_to_direction(self.transform.get_qrot(), fwd);
     }
 
-    pub fn get_up(self) -> Vector3<f32> {
+    pub fn get_up(&self) -> Vector3<f32> {
         let up = Vector3::new(0.0, 1.0, 0.0);
         return rotation_to_direction(self.transform.get_qrot(), up);
     }
 
-    pub fn view_matrix(self) -> [[f32; 4]; 4] {
+    pub fn perspective_matrix(&self, width: u32, height: u32) -> [[f32; 4]; 4] {
+        let aspect_ratio = height as f32 / width as f32;
+
+        let fov = self.fov as f32;
+        let zfar = self.zfar;
+        let znear = self.znear;
+
+        let f = 1.0 / (fov / 2.0).tan();
+
+        [
+            [f * aspect_ratio, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [0.0, 0.0,  (zfar + znear) / (zfar - znear), 1.0],
+            [0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0],
+        ]
+    }
+
+    pub fn view_matrix(&self) -> [[f32; 4]; 4] {
         let fwd = Vector3::new(0.0, 0.0, 1.0);
         let up = Vector3::new(0.0, 1.0, 0.0);
         let pos = self.transform.get_position();
@@ -80,4 +131,9 @@ impl Camera {
 
         return res;
     }
+
+    // what the camera sees on a target of the given size, for the culling
+    pub fn frustum(&self, width: u32, height: u32) -> Frustum {
+        return Frustum::from_view_projection(&self.view_matrix(), &self.perspective_matrix(width, height));
+    }
 }
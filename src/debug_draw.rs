@@ -0,0 +1,458 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+use std::time::Instant;
+
+use glium::implement_vertex;
+
+use crate::bounds::Aabb;
+use crate::shader::ShaderVariant;
+use crate::transform::Transform;
+
+#[cfg(feature = "debug-draw")]
+use glium::index::NoIndices;
+#[cfg(feature = "debug-draw")]
+use glium::index::PrimitiveType;
+#[cfg(feature = "debug-draw")]
+use glium::uniform;
+#[cfg(feature = "debug-draw")]
+use glium::Surface;
+#[cfg(feature = "debug-draw")]
+use glium::VertexBuffer;
+
+#[cfg(feature = "debug-draw")]
+use crate::error::EngineError;
+#[cfg(feature = "debug-draw")]
+use crate::render_graph::FrameInfo;
+#[cfg(feature = "debug-draw")]
+use crate::render_graph::PassBuilder;
+#[cfg(feature = "debug-draw")]
+use crate::render_graph::PassContext;
+#[cfg(feature = "debug-draw")]
+use crate::render_graph::RenderPass;
+#[cfg(feature = "debug-draw")]
+use crate::render_graph::SCENE_DEPTH;
+
+pub const DEBUG_VERTEX_SHADER: &str = "builtin/debug_vertex.glsl";
+pub const DEBUG_FRAGMENT_SHADER: &str = "builtin/debug_fragment.glsl";
+
+// the pass of RenderGraph::forward which draws the shapes
+pub const DEBUG_DRAW_PASS: &str = "debug_draw";
+
+// without the debug-draw feature the shapes are dropped as soon as they are submitted, so that
+// the calls can stay in the systems of a release build
+const ENABLED: bool = cfg!(feature = "debug-draw");
+
+const SPHERE_SEGMENTS: usize = 32;
+
+// the shapes are already in world space
+#[cfg(feature = "debug-draw")]
+const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+pub fn debug_variant() -> ShaderVariant {
+    return ShaderVariant::new(DEBUG_VERTEX_SHADER, DEBUG_FRAGMENT_SHADER);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+implement_vertex!(DebugVertex, position, color);
+
+#[derive(Clone, Debug, PartialEq)]
+struct DebugLine {
+    start: [f32; 3],
+    end: [f32; 3],
+    color: [f32; 4],
+    // none for the shapes drawn for a single frame
+    expires: Option<Instant>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct DebugText {
+    position: [f32; 3],
+    text: String,
+    color: [f32; 4],
+    size: f32,
+    expires: Option<Instant>,
+}
+
+// immediate mode shapes drawn over the scene, the systems get it as a resource and the scene
+// keeps it between frames, see Scene::debug_draw
+// every shape stays for the given duration in seconds, or for the next frame only when it is 0
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+    texts: Vec<DebugText>,
+    // when false the shapes are visible through the objects
+    pub depth_test: bool,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        return DebugDraw::new();
+    }
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        return DebugDraw {
+            lines: Vec::new(),
+            texts: Vec::new(),
+            depth_test: true,
+        };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.lines.is_empty() && self.texts.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.texts.clear();
+    }
+
+    pub fn line(&mut self, start: [f32; 3], end: [f32; 3], color: [f32; 4], duration: f32) {
+        if !ENABLED {
+            return;
+        }
+        self.lines.push(DebugLine {
+            start,
+            end,
+            color,
+            expires: expiry(duration),
+        });
+    }
+
+    // from the origin to origin + direction, the length of the direction is kept
+    pub fn ray(&mut self, origin: [f32; 3], direction: [f32; 3], color: [f32; 4], duration: f32) {
+        let end = [0, 1, 2].map(|i| origin[i] + direction[i]);
+        self.line(origin, end, color, duration);
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 4], duration: f32) {
+        if !ENABLED {
+            return;
+        }
+        let corner = |i: usize| {
+            [0, 1, 2].map(|axis| if i & (1 << axis) == 0 { aabb.min[axis] } else { aabb.max[axis] })
+        };
+        // the corners which differ by a single axis are joined
+        for i in 0..8 {
+            for axis in 0..3 {
+                if i & (1 << axis) == 0 {
+                    self.line(corner(i), corner(i | (1 << axis)), color, duration);
+                }
+            }
+        }
+    }
+
+    // a circle around each axis
+    pub fn sphere(&mut self, center: [f32; 3], radius: f32, color: [f32; 4], duration: f32) {
+        if !ENABLED {
+            return;
+        }
+        for axis in 0..3 {
+            let point = |segment: usize| {
+                let angle = segment as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+                let (sin, cos) = angle.sin_cos();
+                let mut point = center;
+                point[(axis + 1) % 3] += radius * cos;
+                point[(axis + 2) % 3] += radius * sin;
+                point
+            };
+            for segment in 0..SPHERE_SEGMENTS {
+                self.line(point(segment), point(segment + 1), color, duration);
+            }
+        }
+    }
+
+    // the local axes of the transform, x in red, y in green and z in blue
+    pub fn axes(&mut self, transform: &Transform, size: f32, duration: f32) {
+        if !ENABLED {
+            return;
+        }
+        let origin: [f32; 3] = transform.get_position().into();
+        let (x, y, z) = transform.local_axes();
+        let colors = [[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]];
+        for (axis, color) in [x, y, z].into_iter().zip(colors) {
+            self.ray(origin, (axis * size).into(), color, duration);
+        }
+    }
+
+    // a square grid on the horizontal plane going through the center, size is its full width
+    pub fn grid(&mut self, center: [f32; 3], size: f32, divisions: u32, color: [f32; 4], duration: f32) {
+        if !ENABLED {
+            return;
+        }
+        let divisions = divisions.max(1);
+        let half = size * 0.5;
+        for i in 0..=divisions {
+            let offset = -half + size * i as f32 / divisions as f32;
+            let [x, y, z] = center;
+            self.line([x + offset, y, z - half], [x + offset, y, z + half], color, duration);
+            self.line([x - half, y, z + offset], [x + half, y, z + offset], color, duration);
+        }
+    }
+
+    // text facing the camera, its lower left corner at the position, size is the height of the
+    // letters in world units
+    // the letters are drawn with line segments, see glyph_segments for the supported characters
+    pub fn text3d(&mut self, position: [f32; 3], text: &str, color: [f32; 4], size: f32, duration: f32) {
+        if !ENABLED {
+            return;
+        }
+        self.texts.push(DebugText {
+            position,
+            text: text.to_string(),
+            color,
+            size,
+            expires: expiry(duration),
+        });
+    }
+
+    // the lines of every shape, the texts are laid out along the right and up vectors of the camera
+    pub fn vertices(&self, right: [f32; 3], up: [f32; 3]) -> Vec<DebugVertex> {
+        let mut vertices = Vec::with_capacity(self.lines.len() * 2);
+        for line in &self.lines {
+            vertices.push(DebugVertex {
+                position: line.start,
+                color: line.color,
+            });
+            vertices.push(DebugVertex {
+                position: line.end,
+                color: line.color,
+            });
+        }
+        for text in &self.texts {
+            // the glyphs are 2 units high
+            let scale = text.size * 0.5;
+            let place = |x: f32, y: f32| [0, 1, 2].map(|i| text.position[i] + (right[i] * x + up[i] * y) * scale);
+            let (mut column, mut row) = (0.0, 0.0);
+            for character in text.text.chars() {
+                if character == '\n' {
+                    column = 0.0;
+                    row -= 3.0;
+                    continue;
+                }
+                for (start, end) in glyph_segments(character) {
+                    for point in [start, end] {
+                        vertices.push(DebugVertex {
+                            position: place(column + point[0], row + point[1]),
+                            color: text.color,
+                        });
+                    }
+                }
+                column += 3.0;
+            }
+        }
+        return vertices;
+    }
+
+    // removes the shapes of a single frame and the ones whose duration is over, called once they
+    // have been drawn
+    pub fn expire(&mut self, now: Instant) {
+        let alive = |expires: Option<Instant>| expires.map(|expires| expires > now).unwrap_or(false);
+        self.lines.retain(|line| alive(line.expires));
+        self.texts.retain(|text| alive(text.expires));
+    }
+}
+
+fn expiry(duration: f32) -> Option<Instant> {
+    if duration <= 0.0 {
+        return None;
+    }
+    return Some(Instant::now() + Duration::from_secs_f32(duration));
+}
+
+// the segments of a sixteen segment display, on a grid 2 units wide and 2 units high
+const SEGMENTS: [(&str, [f32; 2], [f32; 2]); 16] = [
+    ("a1", [0.0, 2.0], [1.0, 2.0]),
+    ("a2", [1.0, 2.0], [2.0, 2.0]),
+    ("b", [2.0, 2.0], [2.0, 1.0]),
+    ("c", [2.0, 1.0], [2.0, 0.0]),
+    ("d1", [0.0, 0.0], [1.0, 0.0]),
+    ("d2", [1.0, 0.0], [2.0, 0.0]),
+    ("e", [0.0, 0.0], [0.0, 1.0]),
+    ("f", [0.0, 1.0], [0.0, 2.0]),
+    ("g1", [0.0, 1.0], [1.0, 1.0]),
+    ("g2", [1.0, 1.0], [2.0, 1.0]),
+    ("h", [0.0, 2.0], [1.0, 1.0]),
+    ("i", [1.0, 2.0], [1.0, 1.0]),
+    ("j", [2.0, 2.0], [1.0, 1.0]),
+    ("k", [1.0, 1.0], [0.0, 0.0]),
+    ("l", [1.0, 1.0], [1.0, 0.0]),
+    ("m", [1.0, 1.0], [2.0, 0.0]),
+];
+
+// the lit segments of every character, the lowercase letters use the uppercase ones and the
+// other characters are left blank
+const GLYPHS: &[(char, &str)] = &[
+    ('0', "a1 a2 b c d1 d2 e f j k"),
+    ('1', "b c j"),
+    ('2', "a1 a2 b g1 g2 e d1 d2"),
+    ('3', "a1 a2 b c d1 d2 g2"),
+    ('4', "f g1 g2 b c"),
+    ('5', "a1 a2 f g1 g2 c d1 d2"),
+    ('6', "a1 a2 f e d1 d2 c g1 g2"),
+    ('7', "a1 a2 b c"),
+    ('8', "a1 a2 b c d1 d2 e f g1 g2"),
+    ('9', "a1 a2 b c d1 d2 f g1 g2"),
+    ('A', "a1 a2 b c e f g1 g2"),
+    ('B', "a1 a2 b c d1 d2 i l g2"),
+    ('C', "a1 a2 f e d1 d2"),
+    ('D', "a1 a2 b c d1 d2 i l"),
+    ('E', "a1 a2 f e d1 d2 g1"),
+    ('F', "a1 a2 f e g1"),
+    ('G', "a1 a2 f e d1 d2 c g2"),
+    ('H', "f e b c g1 g2"),
+    ('I', "a1 a2 i l d1 d2"),
+    ('J', "b c d1 d2 e"),
+    ('K', "f e g1 j m"),
+    ('L', "f e d1 d2"),
+    ('M', "f e b c h j"),
+    ('N', "f e b c h m"),
+    ('O', "a1 a2 b c d1 d2 e f"),
+    ('P', "a1 a2 b f e g1 g2"),
+    ('Q', "a1 a2 b c d1 d2 e f m"),
+    ('R', "a1 a2 b f e g1 g2 m"),
+    ('S', "a1 a2 f g1 g2 c d1 d2"),
+    ('T', "a1 a2 i l"),
+    ('U', "f e d1 d2 c b"),
+    ('V', "f e k j"),
+    ('W', "f e b c k m"),
+    ('X', "h j k m"),
+    ('Y', "h j l"),
+    ('Z', "a1 a2 j k d1 d2"),
+    ('-', "g1 g2"),
+    ('+', "g1 g2 i l"),
+    ('=', "g1 g2 d1 d2"),
+    ('_', "d1 d2"),
+    ('.', "d1"),
+    (',', "k"),
+    ('/', "j k"),
+    ('|', "i l"),
+    ('(', "j m"),
+    (')', "h k"),
+    ('*', "g1 g2 h i j k l m"),
+];
+
+fn glyph_segments(character: char) -> impl Iterator<Item = ([f32; 2], [f32; 2])> {
+    let character = character.to_ascii_uppercase();
+    let lit = GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == character)
+        .map(|(_, segments)| *segments)
+        .unwrap_or("");
+    return lit.split_whitespace().filter_map(|name| {
+        SEGMENTS
+            .iter()
+            .find(|(segment, _, _)| *segment == name)
+            .map(|(_, start, end)| (*start, *end))
+    });
+}
+
+// draws the shapes of the scene over its objects, before the post processing so that they are
+// hidden by the objects in front of them
+#[cfg(feature = "debug-draw")]
+pub struct DebugDrawPass;
+
+#[cfg(feature = "debug-draw")]
+impl RenderPass for DebugDrawPass {
+    fn name(&self) -> &str {
+        return DEBUG_DRAW_PASS;
+    }
+
+    fn setup(&self, info: &FrameInfo, builder: &mut PassBuilder) {
+        builder.write_scene(info);
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<(), EngineError> {
+        let PassContext {
+            scene,
+            display,
+            frame,
+            info,
+            resources,
+        } = context;
+        let view = info.camera.view_matrix();
+        let perspective = info.camera.perspective_matrix(info.width, info.height);
+        // the rows of the view matrix are the axes of the camera
+        let right = [view[0][0], view[1][0], view[2][0]];
+        let up = [view[0][1], view[1][1], view[2][1]];
+        let debug_draw = scene.debug_draw();
+        let vertices = debug_draw.vertices(right, up);
+        let depth_test = debug_draw.depth_test;
+        debug_draw.expire(Instant::now());
+        let Some(program) = scene.programs.get(&debug_variant().cache_key()) else {
+            return Ok(());
+        };
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        let buffer = VertexBuffer::new(*display, &vertices).map_err(|err| EngineError::upload("debug lines", err))?;
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: if depth_test {
+                    glium::draw_parameters::DepthTest::IfLessOrEqual
+                } else {
+                    glium::draw_parameters::DepthTest::Overwrite
+                },
+                write: false,
+                ..Default::default()
+            },
+            blend: glium::Blend::alpha_blending(),
+            ..Default::default()
+        };
+        let mut target = resources.target(*display, frame, info.scene_target(), Some(SCENE_DEPTH))?;
+        target.draw(
+            &buffer,
+            NoIndices(PrimitiveType::LinesList),
+            program,
+            &uniform! {
+                matrix: IDENTITY,
+                view: view,
+                perspective: perspective,
+            },
+            &params,
+        )?;
+        return Ok(());
+    }
+}
+
+#[cfg(all(test, feature = "debug-draw"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_expire() {
+        let mut debug_draw = DebugDraw::new();
+        let white = [1.0; 4];
+        debug_draw.line([0.0; 3], [1.0, 0.0, 0.0], white, 0.0);
+        debug_draw.aabb(&Aabb::from_points(&[[0.0; 3], [1.0; 3]]), white, 10.0);
+        assert_eq!(debug_draw.vertices([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]).len(), 2 + 12 * 2);
+        // the line was only for a frame
+        debug_draw.expire(Instant::now());
+        assert_eq!(debug_draw.vertices([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]).len(), 12 * 2);
+        debug_draw.expire(Instant::now() + Duration::from_secs(11));
+        assert!(debug_draw.is_empty());
+    }
+
+    #[test]
+    fn text_is_made_of_segments() {
+        let mut debug_draw = DebugDraw::new();
+        debug_draw.text3d([0.0; 3], "Hi ?", [1.0; 4], 1.0, 0.0);
+        // six segments for the h, six for the i and nothing for the others
+        let vertices = debug_draw.vertices([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        assert_eq!(vertices.len(), 24);
+        // the i starts one letter and a space further, and the letters are one unit high
+        let max = vertices.iter().fold([f32::MIN; 3], |max, v| [0, 1, 2].map(|i| max[i].max(v.position[i])));
+        assert_eq!((max[0], max[1]), (5.0 * 0.5, 1.0));
+    }
+}
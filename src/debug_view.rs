@@ -0,0 +1,204 @@
+#![allow(dead_code)]
+
+use glium::draw_parameters::DepthTest;
+use glium::draw_parameters::PolygonOffset;
+use glium::BackfaceCullingMode;
+use glium::Blend;
+use glium::BlendingFunction;
+use glium::DrawParameters;
+use glium::LinearBlendingFactor;
+use glium::PolygonMode;
+
+use crate::debug_draw::DebugVertex;
+use crate::instancing::instanced_variant;
+use crate::mesh::Mesh;
+use crate::shader::ShaderVariant;
+use crate::shader::DEFAULT_VERTEX_SHADER;
+
+pub const DEBUG_VIEW_FRAGMENT_SHADER: &str = "builtin/debug_view_fragment.glsl";
+
+// the colour of the lines of the wireframe
+const WIREFRAME_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+// added by every fragment of the overdraw view, a pixel drawn ten times is white
+const OVERDRAW_COLOR: [f32; 4] = [0.1, 0.04, 0.02, 1.0];
+// the length of the normals of the normals view, relative to the radius of the model
+const NORMAL_LENGTH: f32 = 0.1;
+
+// how the camera draws the objects, to find out what is wrong with a model, see Camera::debug_view
+// the views other than Shaded, LightingOnly and the overlays ignore the materials
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Shaded,
+    // the edges of the triangles alone
+    Wireframe,
+    WireframeOverShaded,
+    // a line along the normal of every vertex, over the shaded objects
+    Normals,
+    // a checker showing how the texture coordinates are laid out
+    UvChecker,
+    // the distance to the camera, white is near
+    Depth,
+    // the materials are white so that only the lights and shadows show
+    LightingOnly,
+    // how many times each pixel is drawn, from black to white
+    Overdraw,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 8] = [
+        DebugView::Shaded,
+        DebugView::Wireframe,
+        DebugView::WireframeOverShaded,
+        DebugView::Normals,
+        DebugView::UvChecker,
+        DebugView::Depth,
+        DebugView::LightingOnly,
+        DebugView::Overdraw,
+    ];
+
+    // the view after this one, for the debug key of the game
+    pub fn next(self) -> Self {
+        let index = DebugView::ALL.iter().position(|view| *view == self).unwrap_or(0);
+        return DebugView::ALL[(index + 1) % DebugView::ALL.len()];
+    }
+
+    // the objects are first drawn with their materials
+    pub fn draws_shaded(self) -> bool {
+        return matches!(
+            self,
+            DebugView::Shaded | DebugView::WireframeOverShaded | DebugView::Normals | DebugView::LightingOnly
+        );
+    }
+
+    // the views drawn by the passes of the draw list, one after the other
+    pub fn layers(self) -> Vec<DebugView> {
+        match self {
+            DebugView::WireframeOverShaded => vec![DebugView::Shaded, DebugView::Wireframe],
+            DebugView::Normals => vec![DebugView::Shaded],
+            view => vec![view],
+        }
+    }
+
+    // the value of u_debug_view for the views drawn with the debug program instead of the
+    // materials, the values must match assets/shaders/debug_view_fragment.glsl
+    pub fn shader_index(self) -> Option<i32> {
+        match self {
+            DebugView::Wireframe | DebugView::Overdraw => Some(0),
+            DebugView::UvChecker => Some(1),
+            DebugView::Depth => Some(2),
+            _ => None,
+        }
+    }
+
+    pub fn debug_color(self) -> [f32; 4] {
+        match self {
+            DebugView::Overdraw => OVERDRAW_COLOR,
+            _ => WIREFRAME_COLOR,
+        }
+    }
+
+    // the parameters of the objects drawn with the debug program, from the ones of their material
+    pub fn draw_parameters(self, material: DrawParameters<'static>) -> DrawParameters<'static> {
+        match self {
+            // pulled towards the camera so that the lines win over the faces they were drawn on
+            DebugView::Wireframe => DrawParameters {
+                polygon_mode: PolygonMode::Line,
+                polygon_offset: PolygonOffset {
+                    factor: -1.0,
+                    units: -1.0,
+                    line: true,
+                    ..Default::default()
+                },
+                depth: glium::Depth {
+                    test: DepthTest::IfLessOrEqual,
+                    ..material.depth
+                },
+                blend: Blend::default(),
+                backface_culling: BackfaceCullingMode::CullingDisabled,
+                ..material
+            },
+            // every fragment counts, hidden or not
+            DebugView::Overdraw => DrawParameters {
+                depth: glium::Depth {
+                    test: DepthTest::Overwrite,
+                    write: false,
+                    ..Default::default()
+                },
+                blend: additive_blending(),
+                ..material
+            },
+            _ => DrawParameters {
+                blend: Blend::default(),
+                ..material
+            },
+        }
+    }
+}
+
+fn additive_blending() -> Blend {
+    let add = BlendingFunction::Addition {
+        source: LinearBlendingFactor::One,
+        destination: LinearBlendingFactor::One,
+    };
+    return Blend {
+        color: add,
+        alpha: add,
+        constant_value: (0.0, 0.0, 0.0, 0.0),
+    };
+}
+
+// the program of the views which ignore the materials, it reads the same vertices as the default
+// material
+pub fn debug_view_variant(instanced: bool) -> ShaderVariant {
+    let variant = ShaderVariant::new(DEFAULT_VERTEX_SHADER, DEBUG_VIEW_FRAGMENT_SHADER);
+    if instanced {
+        return instanced_variant(&variant);
+    }
+    return variant;
+}
+
+// a line from every vertex along its normal, in model space and coloured by the direction of the
+// normal, empty when the mesh has no normals
+pub fn normal_lines(mesh: &Mesh, radius: f32) -> Vec<DebugVertex> {
+    let Some(normals) = &mesh.normals else {
+        return Vec::new();
+    };
+    let length = radius * NORMAL_LENGTH;
+    let mut vertices = Vec::with_capacity(mesh.positions.len() * 2);
+    for (position, normal) in mesh.positions.iter().zip(normals) {
+        let color = [normal[0] * 0.5 + 0.5, normal[1] * 0.5 + 0.5, normal[2] * 0.5 + 0.5, 1.0];
+        let end = [0, 1, 2].map(|i| position[i] + normal[i] * length);
+        vertices.push(DebugVertex {
+            position: *position,
+            color,
+        });
+        vertices.push(DebugVertex { position: end, color });
+    }
+    return vertices;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_debug_key_cycles_through_every_view() {
+        let mut view = DebugView::Shaded;
+        for expected in DebugView::ALL.iter().skip(1) {
+            view = view.next();
+            assert_eq!(view, *expected);
+        }
+        assert_eq!(view.next(), DebugView::Shaded);
+    }
+
+    #[test]
+    fn normals_become_lines() {
+        let mesh = Mesh::from_positions_indices(vec![[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], vec![0, 1, 2])
+            .with_normals(vec![[0.0, 0.0, 1.0]; 3]);
+        let lines = normal_lines(&mesh, 2.0);
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[3].position, [1.0, 0.0, 0.2]);
+        assert!(normal_lines(&Mesh::from_positions_indices(vec![[0.0; 3]], vec![]), 1.0).is_empty());
+    }
+}
@@ -0,0 +1,351 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use egui::epaint::Primitive;
+use egui::epaint::Vertex;
+use egui::ClippedPrimitive;
+use egui::Context;
+use egui::ImageData;
+use egui::TextureFilter;
+use egui::TextureId;
+use egui::TextureOptions;
+use egui::TextureWrapMode;
+use egui::TexturesDelta;
+use egui::ViewportId;
+
+use glium::glutin::surface::WindowSurface;
+use glium::implement_vertex;
+use glium::index::PrimitiveType;
+use glium::texture::MipmapsOption;
+use glium::texture::RawImage2d;
+use glium::texture::Texture2d;
+use glium::uniform;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
+use glium::uniforms::Sampler;
+use glium::uniforms::SamplerBehavior;
+use glium::uniforms::SamplerWrapFunction;
+use glium::winit::event::WindowEvent;
+use glium::winit::window::Window;
+use glium::BackfaceCullingMode;
+use glium::Blend;
+use glium::BlendingFunction;
+use glium::Display;
+use glium::DrawParameters;
+use glium::Frame;
+use glium::IndexBuffer;
+use glium::LinearBlendingFactor;
+use glium::Program;
+use glium::Rect;
+use glium::Surface;
+use glium::VertexBuffer;
+
+use crate::error::EngineError;
+use crate::graphic_component::load_shaders;
+use crate::shader::ShaderVariant;
+
+pub const EGUI_VERTEX_SHADER: &str = "builtin/egui_vertex.glsl";
+pub const EGUI_FRAGMENT_SHADER: &str = "builtin/egui_fragment.glsl";
+
+// the colours of egui are blended in srgb, so the framebuffer mustn't convert them again
+pub fn egui_variant() -> ShaderVariant {
+    return ShaderVariant::new(EGUI_VERTEX_SHADER, EGUI_FRAGMENT_SHADER).with_srgb_output();
+}
+
+// the link between egui, the window and the systems, Game::run feeds it the events of the window,
+// runs an egui pass around the systems of every event and paints the last one over the scene
+// the systems get the context of the scene as a resource, see Scene::egui_context
+pub struct EguiIntegration {
+    context: Context,
+    state: egui_winit::State,
+    painter: EguiPainter,
+    // what the last pass drew, painted with the next frame
+    shapes: Vec<egui::epaint::ClippedShape>,
+    pixels_per_point: f32,
+    // the changes of the textures of every pass since the last frame
+    textures_delta: TexturesDelta,
+}
+
+impl EguiIntegration {
+    pub fn new(context: Context, window: &Window) -> Self {
+        let state = egui_winit::State::new(
+            context.clone(),
+            ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+            None,
+        );
+        return EguiIntegration {
+            context,
+            state,
+            painter: EguiPainter::new(),
+            shapes: Vec::new(),
+            pixels_per_point: window.scale_factor() as f32,
+            textures_delta: TexturesDelta::default(),
+        };
+    }
+
+    pub fn context(&self) -> &Context {
+        return &self.context;
+    }
+
+    // returns true when the interface uses the event, a click on a panel or a key typed in a text
+    // field, in which case the game shouldn't act on it
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        return self.state.on_window_event(window, event).consumed;
+    }
+
+    // the systems build the interface between begin_pass and end_pass
+    pub fn begin_pass(&mut self, window: &Window) {
+        let input = self.state.take_egui_input(window);
+        self.context.begin_pass(input);
+    }
+
+    pub fn end_pass(&mut self, window: &Window) {
+        let output = self.context.end_pass();
+        self.state.handle_platform_output(window, output.platform_output);
+        self.textures_delta.append(output.textures_delta);
+        self.shapes = output.shapes;
+        self.pixels_per_point = output.pixels_per_point;
+    }
+
+    // draws the interface of the last pass over the frame
+    pub fn paint(&mut self, display: &Display<WindowSurface>, frame: &mut Frame) -> Result<(), EngineError> {
+        let primitives = self.context.tessellate(std::mem::take(&mut self.shapes), self.pixels_per_point);
+        let textures_delta = std::mem::take(&mut self.textures_delta);
+        return self.painter.paint(display, frame, self.pixels_per_point, &primitives, &textures_delta);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct EguiVertex {
+    position: [f32; 2],
+    tex_coord: [f32; 2],
+    color: [f32; 4],
+}
+
+implement_vertex!(EguiVertex, position, tex_coord, color);
+
+impl From<&Vertex> for EguiVertex {
+    fn from(vertex: &Vertex) -> Self {
+        return EguiVertex {
+            position: [vertex.pos.x, vertex.pos.y],
+            tex_coord: [vertex.uv.x, vertex.uv.y],
+            color: vertex.color.to_array().map(|channel| channel as f32 / 255.0),
+        };
+    }
+}
+
+// the textures of egui and the program drawing its meshes
+struct EguiPainter {
+    program: Option<Program>,
+    // the program is only tried once
+    failed: bool,
+    textures: HashMap<TextureId, (Texture2d, SamplerBehavior)>,
+}
+
+impl EguiPainter {
+    fn new() -> Self {
+        return EguiPainter {
+            program: None,
+            failed: false,
+            textures: HashMap::new(),
+        };
+    }
+
+    // the program isn't one of the scene's since the integration outlives the scenes
+    fn program(&mut self, display: &Display<WindowSurface>) -> Result<Option<&Program>, EngineError> {
+        if self.program.is_none() && !self.failed {
+            match load_shaders(&egui_variant(), display) {
+                Ok(program) => self.program = Some(program),
+                Err(err) => {
+                    self.failed = true;
+                    return Err(err);
+                }
+            }
+        }
+        return Ok(self.program.as_ref());
+    }
+
+    fn update_textures(&mut self, display: &Display<WindowSurface>, delta: &TexturesDelta) -> Result<(), EngineError> {
+        for (id, image_delta) in &delta.set {
+            let ImageData::Color(image) = &image_delta.image;
+            let pixels: Vec<u8> = image.pixels.iter().flat_map(|color| color.to_array()).collect();
+            let (width, height) = (image.size[0] as u32, image.size[1] as u32);
+            let raw = RawImage2d::from_raw_rgba(pixels, (width, height));
+            let sampler = sampler(&image_delta.options);
+            match (image_delta.pos, self.textures.get_mut(id)) {
+                // a patch of the font atlas most of the time
+                (Some([x, y]), Some((texture, _))) => {
+                    let rect = Rect {
+                        left: x as u32,
+                        bottom: y as u32,
+                        width,
+                        height,
+                    };
+                    texture.write(rect, raw);
+                }
+                (Some(_), None) => (),
+                (None, _) => {
+                    let texture = Texture2d::with_mipmaps(display, raw, MipmapsOption::NoMipmap)
+                        .map_err(|err| EngineError::upload("egui texture", err))?;
+                    self.textures.insert(*id, (texture, sampler));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn paint(
+        &mut self,
+        display: &Display<WindowSurface>,
+        frame: &mut Frame,
+        pixels_per_point: f32,
+        primitives: &[ClippedPrimitive],
+        delta: &TexturesDelta,
+    ) -> Result<(), EngineError> {
+        self.update_textures(display, delta)?;
+        let (width, height) = frame.get_dimensions();
+        let screen_size = [width as f32 / pixels_per_point, height as f32 / pixels_per_point];
+        let result = self.draw_primitives(display, frame, pixels_per_point, screen_size, primitives);
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+        return result;
+    }
+
+    fn draw_primitives(
+        &mut self,
+        display: &Display<WindowSurface>,
+        frame: &mut Frame,
+        pixels_per_point: f32,
+        screen_size: [f32; 2],
+        primitives: &[ClippedPrimitive],
+    ) -> Result<(), EngineError> {
+        let (width, height) = frame.get_dimensions();
+        self.program(display)?;
+        let Some(program) = &self.program else {
+            return Ok(());
+        };
+        for ClippedPrimitive { clip_rect, primitive } in primitives {
+            // the paint callbacks need a backend of their own
+            let Primitive::Mesh(mesh) = primitive else {
+                continue;
+            };
+            let (Some((texture, sampler)), Some(scissor)) = (
+                self.textures.get(&mesh.texture_id),
+                scissor_rect(clip_rect, pixels_per_point, width, height),
+            ) else {
+                continue;
+            };
+            if mesh.indices.is_empty() {
+                continue;
+            }
+            let vertices: Vec<EguiVertex> = mesh.vertices.iter().map(EguiVertex::from).collect();
+            let vertex_buffer =
+                VertexBuffer::new(display, &vertices).map_err(|err| EngineError::upload("egui mesh", err))?;
+            let index_buffer = IndexBuffer::new(display, PrimitiveType::TrianglesList, &mesh.indices)
+                .map_err(|err| EngineError::upload("egui mesh", err))?;
+            let params = DrawParameters {
+                blend: premultiplied_blending(),
+                scissor: Some(scissor),
+                backface_culling: BackfaceCullingMode::CullingDisabled,
+                ..Default::default()
+            };
+            let uniforms = uniform! {
+                u_screen_size: screen_size,
+                u_texture: Sampler(texture, *sampler),
+            };
+            frame.draw(&vertex_buffer, &index_buffer, program, &uniforms, &params)?;
+        }
+        return Ok(());
+    }
+}
+
+// the clip rectangle of a mesh, in points from the top left corner, as a scissor in pixels from the
+// bottom left corner, none when nothing of it is on the screen
+fn scissor_rect(clip_rect: &egui::Rect, pixels_per_point: f32, width: u32, height: u32) -> Option<Rect> {
+    let left = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, width as f32) as u32;
+    let right = (clip_rect.max.x * pixels_per_point).round().clamp(0.0, width as f32) as u32;
+    let top = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, height as f32) as u32;
+    let bottom = (clip_rect.max.y * pixels_per_point).round().clamp(0.0, height as f32) as u32;
+    if right <= left || bottom <= top {
+        return None;
+    }
+    return Some(Rect {
+        left,
+        bottom: height - bottom,
+        width: right - left,
+        height: bottom - top,
+    });
+}
+
+fn sampler(options: &TextureOptions) -> SamplerBehavior {
+    let wrap = match options.wrap_mode {
+        TextureWrapMode::ClampToEdge => SamplerWrapFunction::Clamp,
+        TextureWrapMode::Repeat => SamplerWrapFunction::Repeat,
+        TextureWrapMode::MirroredRepeat => SamplerWrapFunction::Mirror,
+    };
+    return SamplerBehavior {
+        wrap_function: (wrap, wrap, wrap),
+        minify_filter: match options.minification {
+            TextureFilter::Nearest => MinifySamplerFilter::Nearest,
+            TextureFilter::Linear => MinifySamplerFilter::Linear,
+        },
+        magnify_filter: match options.magnification {
+            TextureFilter::Nearest => MagnifySamplerFilter::Nearest,
+            TextureFilter::Linear => MagnifySamplerFilter::Linear,
+        },
+        ..Default::default()
+    };
+}
+
+// the colours of egui are premultiplied by their alpha
+fn premultiplied_blending() -> Blend {
+    return Blend {
+        color: BlendingFunction::Addition {
+            source: LinearBlendingFactor::One,
+            destination: LinearBlendingFactor::OneMinusSourceAlpha,
+        },
+        alpha: BlendingFunction::Addition {
+            source: LinearBlendingFactor::OneMinusDestinationAlpha,
+            destination: LinearBlendingFactor::One,
+        },
+        constant_value: (0.0, 0.0, 0.0, 0.0),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use egui::pos2;
+
+    use crate::shader::preprocess;
+
+    #[test]
+    fn clip_rects_become_scissors() {
+        let clip = egui::Rect::from_min_max(pos2(10.0, 20.0), pos2(50.0, 40.0));
+        let scissor = scissor_rect(&clip, 2.0, 200, 100).unwrap();
+        assert_eq!(scissor.left, 20);
+        assert_eq!(scissor.width, 80);
+        assert_eq!(scissor.height, 40);
+        // from the bottom of the window
+        assert_eq!(scissor.bottom, 20);
+        // outside of the window
+        let clip = egui::Rect::from_min_max(pos2(300.0, 0.0), pos2(400.0, 10.0));
+        assert!(scissor_rect(&clip, 1.0, 200, 100).is_none());
+    }
+
+    #[test]
+    fn shaders_are_builtin() {
+        let variant = egui_variant();
+        assert!(variant.outputs_srgb);
+        for path in [&variant.vertex_path, &variant.fragment_path] {
+            let source = preprocess(path, &variant).unwrap();
+            assert!(source.source.starts_with("#version"));
+        }
+    }
+}
@@ -0,0 +1,416 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use glium::backend::Facade;
+use glium::texture::MipmapsOption;
+use glium::texture::RawImage2d;
+use glium::texture::Texture2d;
+use glium::texture::UncompressedFloatFormat;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
+use glium::uniforms::SamplerBehavior;
+use glium::uniforms::SamplerWrapFunction;
+use glium::uniforms::UniformValue;
+use glium::Rect;
+
+use crate::error::EngineError;
+use crate::material::WHITE_TEXTURE;
+use crate::texture::Texture;
+use crate::uniforms::UniformBag;
+
+// the environment is downscaled to this width before anything is computed, it is only used
+// for blurry reflections and ambient lighting so a higher resolution would be wasted
+const MAX_ENVIRONMENT_WIDTH: u32 = 1024;
+const IRRADIANCE_WIDTH: u32 = 128;
+const BRDF_LUT_SIZE: u32 = 32;
+const BRDF_LUT_SAMPLES: u32 = 128;
+// mip levels of the specular map, the last ones are blurry enough for the roughest materials
+const MAX_SPECULAR_LEVELS: usize = 7;
+
+// an rgb float image stored row by row, the first row being the top of the image
+#[derive(Clone)]
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 3]>,
+}
+
+impl HdrImage {
+    pub fn load(path: &str) -> Result<HdrImage, image::ImageError> {
+        let image = image::open(path)?.to_rgb32f();
+        let (width, height) = image.dimensions();
+        let pixels = image.pixels().map(|p| p.0).collect();
+        Ok(HdrImage {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn get(&self, x: u32, y: u32) -> [f32; 3] {
+        return self.pixels[(y * self.width + x) as usize];
+    }
+
+    // halves both dimensions by averaging blocks of 2x2 pixels
+    pub fn downsampled(&self) -> HdrImage {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 3];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let p = self.get((2 * x + dx).min(self.width - 1), (2 * y + dy).min(self.height - 1));
+                    for c in 0..3 {
+                        sum[c] += p[c] / 4.0;
+                    }
+                }
+                pixels.push(sum);
+            }
+        }
+        HdrImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // [1, 2, 1] blur, wrapping horizontally since the image goes around the whole sphere
+    pub fn blurred(&self) -> HdrImage {
+        let (w, h) = (self.width as i64, self.height as i64);
+        let blur = |image: &HdrImage, dx: i64, dy: i64| -> Vec<[f32; 3]> {
+            (0..h)
+                .flat_map(|y| (0..w).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let mut sum = [0.0; 3];
+                    for (offset, weight) in [(-1, 0.25), (0, 0.5), (1, 0.25)] {
+                        let sx = (x + dx * offset).rem_euclid(w) as u32;
+                        let sy = (y + dy * offset).clamp(0, h - 1) as u32;
+                        let p = image.get(sx, sy);
+                        for c in 0..3 {
+                            sum[c] += p[c] * weight;
+                        }
+                    }
+                    sum
+                })
+                .collect()
+        };
+        let horizontal = HdrImage {
+            width: self.width,
+            height: self.height,
+            pixels: blur(self, 1, 0),
+        };
+        let pixels = blur(&horizontal, 0, 1);
+        HdrImage {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+
+    // the image flipped vertically as glium expects it, the first row being the bottom
+    fn to_raw(&self) -> RawImage2d<'static, f32> {
+        let data: Vec<f32> = self.pixels.iter().flatten().copied().collect();
+        return RawImage2d::from_raw_rgb_reversed(&data, (self.width, self.height));
+    }
+}
+
+// direction at the center of a pixel of an equirectangular image, y is up
+fn equirectangular_direction(x: u32, y: u32, width: u32, height: u32) -> ([f32; 3], f32) {
+    let phi = 2.0 * PI * (x as f32 + 0.5) / width as f32 - PI;
+    let theta = PI * (y as f32 + 0.5) / height as f32;
+    let direction = [theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()];
+    // solid angle covered by the pixel
+    let solid_angle = (2.0 * PI / width as f32) * (PI / height as f32) * theta.sin();
+    return (direction, solid_angle);
+}
+
+// projects the environment on the first 9 spherical harmonics, which is enough to reconstruct
+// the diffuse irradiance (Ramamoorthi and Hanrahan)
+pub fn irradiance_sh(image: &HdrImage) -> [[f32; 3]; 9] {
+    let mut coefficients = [[0.0f32; 3]; 9];
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let ([dx, dy, dz], solid_angle) = equirectangular_direction(x, y, image.width, image.height);
+            let basis = [
+                0.282095,
+                0.488603 * dy,
+                0.488603 * dz,
+                0.488603 * dx,
+                1.092548 * dx * dy,
+                1.092548 * dy * dz,
+                0.315392 * (3.0 * dz * dz - 1.0),
+                1.092548 * dx * dz,
+                0.546274 * (dx * dx - dy * dy),
+            ];
+            let color = image.get(x, y);
+            for (coefficient, b) in coefficients.iter_mut().zip(basis) {
+                for c in 0..3 {
+                    coefficient[c] += color[c] * b * solid_angle;
+                }
+            }
+        }
+    }
+    return coefficients;
+}
+
+fn radical_inverse(mut bits: u32) -> f32 {
+    bits = bits.reverse_bits();
+    return bits as f32 * 2.328_306_4e-10;
+}
+
+// scale and bias of the fresnel term for the split sum approximation (Karis 2013)
+fn integrate_brdf(n_dot_v: f32, roughness: f32) -> (f32, f32) {
+    let v = [(1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v];
+    let a = roughness * roughness;
+    // the remapping of the roughness for image based lighting, alpha / 2
+    let k = a / 2.0;
+    let geometry = |n_dot: f32| n_dot / (n_dot * (1.0 - k) + k);
+    let (mut scale, mut bias) = (0.0, 0.0);
+    for i in 0..BRDF_LUT_SAMPLES {
+        // importance sampling of the ggx distribution around the normal (0, 0, 1)
+        let (u, w) = (i as f32 / BRDF_LUT_SAMPLES as f32, radical_inverse(i));
+        let phi = 2.0 * PI * u;
+        let cos_theta = ((1.0 - w) / (1.0 + (a * a - 1.0) * w)).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let h = [sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta];
+        let v_dot_h = v[0] * h[0] + v[1] * h[1] + v[2] * h[2];
+        let l_z = 2.0 * v_dot_h * h[2] - v[2];
+        let n_dot_l = l_z.max(0.0);
+        let n_dot_h = h[2].max(0.0);
+        if n_dot_l > 0.0 {
+            let g = geometry(n_dot_v) * geometry(n_dot_l);
+            let g_vis = g * v_dot_h.max(0.0) / (n_dot_h * n_dot_v).max(1e-4);
+            let fc = (1.0 - v_dot_h.max(0.0)).powi(5);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+    return (scale / BRDF_LUT_SAMPLES as f32, bias / BRDF_LUT_SAMPLES as f32);
+}
+
+pub fn brdf_lut<F: Facade>(facade: &F) -> Result<Texture2d, EngineError> {
+    let mut data = Vec::with_capacity((BRDF_LUT_SIZE * BRDF_LUT_SIZE * 3) as usize);
+    // the rows are indexed by roughness and the columns by n.v, starting from the bottom
+    for y in 0..BRDF_LUT_SIZE {
+        for x in 0..BRDF_LUT_SIZE {
+            let n_dot_v = ((x as f32 + 0.5) / BRDF_LUT_SIZE as f32).max(1e-3);
+            let roughness = (y as f32 + 0.5) / BRDF_LUT_SIZE as f32;
+            let (scale, bias) = integrate_brdf(n_dot_v, roughness);
+            data.extend_from_slice(&[scale, bias, 0.0]);
+        }
+    }
+    let image = RawImage2d::from_raw_rgb(data, (BRDF_LUT_SIZE, BRDF_LUT_SIZE));
+    return Texture2d::with_format(
+        facade,
+        image,
+        UncompressedFloatFormat::F16F16F16,
+        MipmapsOption::NoMipmap,
+    )
+    .map_err(|err| EngineError::upload("brdf lut", err));
+}
+
+// a poor man's prefiltering, each level is downsampled then blurred, which roughly matches the
+// widening of the specular lobe as the roughness increases
+fn specular_levels(image: HdrImage) -> Vec<HdrImage> {
+    let mut levels = vec![image];
+    while levels.len() < MAX_SPECULAR_LEVELS && levels.last().unwrap().height > 4 {
+        let next = levels.last().unwrap().downsampled().blurred().blurred();
+        levels.push(next);
+    }
+    return levels;
+}
+
+// everything needed to light objects with an equirectangular environment, computed at load
+pub struct Environment {
+    // every mip level is blurrier than the previous one, the roughness selects the level
+    pub specular: Texture2d,
+    pub max_lod: f32,
+    pub irradiance_sh: [[f32; 3]; 9],
+    pub brdf_lut: Texture2d,
+    pub intensity: f32,
+}
+
+impl Environment {
+    pub fn load<F: Facade>(path: &str, facade: &F) -> Result<Environment, EngineError> {
+        let image = HdrImage::load(path).map_err(|err| EngineError::from_image(path, err))?;
+        return Environment::from_image(image, facade);
+    }
+
+    pub fn from_image<F: Facade>(mut image: HdrImage, facade: &F) -> Result<Environment, EngineError> {
+        while image.width > MAX_ENVIRONMENT_WIDTH {
+            image = image.downsampled();
+        }
+
+        let mut irradiance_source = image.clone();
+        while irradiance_source.width > IRRADIANCE_WIDTH {
+            irradiance_source = irradiance_source.downsampled();
+        }
+        let irradiance_sh = irradiance_sh(&irradiance_source);
+
+        let levels = specular_levels(image);
+        let (width, height) = (levels[0].width, levels[0].height);
+        let specular = Texture2d::empty_with_format(
+            facade,
+            UncompressedFloatFormat::F16F16F16,
+            MipmapsOption::EmptyMipmapsMax(levels.len() as u32 - 1),
+            width,
+            height,
+        )
+        .map_err(|err| EngineError::upload("environment", err))?;
+        for (i, level) in levels.iter().enumerate() {
+            if let Some(mipmap) = specular.mipmap(i as u32) {
+                let rect = Rect {
+                    left: 0,
+                    bottom: 0,
+                    width: mipmap.width(),
+                    height: mipmap.height(),
+                };
+                if rect.width == level.width && rect.height == level.height {
+                    mipmap.write(rect, level.to_raw());
+                }
+            }
+        }
+
+        return Ok(Environment {
+            specular,
+            max_lod: (levels.len() - 1) as f32,
+            irradiance_sh,
+            brdf_lut: brdf_lut(facade)?,
+            intensity: 1.0,
+        });
+    }
+}
+
+fn environment_sampler() -> SamplerBehavior {
+    SamplerBehavior {
+        wrap_function: (
+            SamplerWrapFunction::Repeat,
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+        ),
+        minify_filter: MinifySamplerFilter::LinearMipmapLinear,
+        magnify_filter: MagnifySamplerFilter::Linear,
+        ..Default::default()
+    }
+}
+
+fn lut_sampler() -> SamplerBehavior {
+    SamplerBehavior {
+        wrap_function: (
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+        ),
+        minify_filter: MinifySamplerFilter::Linear,
+        magnify_filter: MagnifySamplerFilter::Linear,
+        ..Default::default()
+    }
+}
+
+// without environment the samplers are still bound, to a white texture, so that they never
+// end up sharing a texture unit with a sampler of another type
+pub fn bind_environment<'a>(
+    environment: Option<&'a Environment>,
+    textures: &'a HashMap<String, Texture>,
+    uniforms: &mut UniformBag<'a>,
+) {
+    uniforms.set("u_use_environment", UniformValue::Bool(environment.is_some()));
+    match environment {
+        Some(environment) => {
+            uniforms.set(
+                "u_environment_map",
+                UniformValue::Texture2d(&environment.specular, Some(environment_sampler())),
+            );
+            uniforms.set("u_environment_max_lod", UniformValue::Float(environment.max_lod));
+            uniforms.set("u_environment_intensity", UniformValue::Float(environment.intensity));
+            uniforms.set(
+                "u_brdf_lut",
+                UniformValue::Texture2d(&environment.brdf_lut, Some(lut_sampler())),
+            );
+            for (i, coefficient) in environment.irradiance_sh.iter().enumerate() {
+                uniforms.set(&format!("u_irradiance_sh[{}]", i), UniformValue::Vec3(*coefficient));
+            }
+        }
+        None => {
+            if let Some(white) = textures.get(WHITE_TEXTURE) {
+                uniforms.set("u_environment_map", white.uniform_value(None));
+                uniforms.set("u_brdf_lut", white.uniform_value(None));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant_image(width: u32, height: u32, color: [f32; 3]) -> HdrImage {
+        return HdrImage {
+            width,
+            height,
+            pixels: vec![color; (width * height) as usize],
+        };
+    }
+
+    #[test]
+    fn pixels_cover_the_whole_sphere() {
+        let (width, height) = (64, 32);
+        let mut total = 0.0;
+        for y in 0..height {
+            for x in 0..width {
+                let (direction, solid_angle) = equirectangular_direction(x, y, width, height);
+                let length: f32 = direction.iter().map(|d| d * d).sum::<f32>().sqrt();
+                assert!((length - 1.0).abs() < 1e-5);
+                total += solid_angle;
+            }
+        }
+        assert!((total - 4.0 * PI).abs() < 1e-2);
+        // the first row is the top of the sky
+        assert!(equirectangular_direction(0, 0, width, height).0[1] > 0.99);
+    }
+
+    #[test]
+    fn constant_environments_only_have_ambient_irradiance() {
+        let coefficients = irradiance_sh(&constant_image(64, 32, [1.0, 0.5, 0.0]));
+        // the first band times the area of the sphere
+        let ambient = 0.282095 * 4.0 * PI;
+        assert!((coefficients[0][0] - ambient).abs() < 1e-2);
+        assert!((coefficients[0][1] - ambient / 2.0).abs() < 1e-2);
+        assert_eq!(coefficients[0][2], 0.0);
+        for coefficient in &coefficients[1..] {
+            assert!(coefficient.iter().all(|c| c.abs() < 1e-2));
+        }
+    }
+
+    #[test]
+    fn specular_levels_halve_until_they_are_tiny() {
+        let levels = specular_levels(constant_image(64, 32, [2.0, 2.0, 2.0]));
+        let sizes: Vec<(u32, u32)> = levels.iter().map(|level| (level.width, level.height)).collect();
+        assert_eq!(sizes, vec![(64, 32), (32, 16), (16, 8), (8, 4)]);
+        // the blur doesn't change the energy of the environment
+        for level in &levels {
+            assert!(level.pixels.iter().flatten().all(|c| (c - 2.0).abs() < 1e-5));
+        }
+        let levels = specular_levels(constant_image(MAX_ENVIRONMENT_WIDTH, MAX_ENVIRONMENT_WIDTH / 2, [1.0; 3]));
+        assert_eq!(levels.len(), MAX_SPECULAR_LEVELS);
+    }
+
+    #[test]
+    fn brdf_terms_stay_in_range() {
+        assert_eq!(radical_inverse(1), 0.5);
+        assert_eq!(radical_inverse(2), 0.25);
+        for roughness in [0.05, 0.5, 1.0] {
+            for n_dot_v in [0.05, 0.5, 1.0] {
+                let (scale, bias) = integrate_brdf(n_dot_v, roughness);
+                assert!(scale >= 0.0 && bias >= 0.0 && scale + bias <= 1.0 + 1e-3);
+            }
+        }
+        // smooth surfaces seen head on reflect everything with the base reflectance
+        let (scale, bias) = integrate_brdf(1.0, 0.05);
+        assert!((scale + bias - 1.0).abs() < 0.05);
+        assert!(bias < 0.01);
+    }
+}
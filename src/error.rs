@@ -0,0 +1,303 @@
+#![allow(dead_code)]
+
+use std::fmt;
+
+use tracing::error;
+use tracing::warn;
+
+use crate::logging::ASSETS;
+use crate::logging::RENDER;
+use crate::mesh::MeshError;
+use crate::shader::ShaderError;
+
+// everything which can go wrong when loading the assets of a scene or drawing it
+#[derive(Debug)]
+pub enum EngineError {
+    // the file couldn't be read
+    Io { path: String, message: String },
+    // the file was read but its content isn't valid
+    Parse { path: String, message: String },
+    // the log is the one of the driver, with the lines mapped back to the original files
+    ShaderCompile { path: String, log: String },
+    // the gpu refused the buffer or texture
+    GpuUpload { asset: String, message: String },
+    // something refers to an asset the scene doesn't know
+    MissingAsset { kind: AssetKind, name: String },
+    InvalidMesh { path: String, error: MeshError },
+    Draw { message: String },
+    // the passes of the render graph can't be ordered
+    RenderGraph { message: String },
+    // the nodes of the interface can't be laid out
+    UiLayout { message: String },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Model,
+    Texture,
+    Material,
+    Shader,
+    Environment,
+}
+
+impl fmt::Display for AssetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AssetKind::Model => "model",
+            AssetKind::Texture => "texture",
+            AssetKind::Material => "material",
+            AssetKind::Shader => "shader",
+            AssetKind::Environment => "environment",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Io { path, message } => write!(f, "could not read {}: {}", path, message),
+            EngineError::Parse { path, message } => {
+                write!(f, "could not parse {}: {}", path, message)
+            }
+            EngineError::ShaderCompile { path, log } => {
+                write!(f, "{} failed to compile:\n{}", path, log)
+            }
+            EngineError::GpuUpload { asset, message } => {
+                write!(f, "could not upload {} to the gpu: {}", asset, message)
+            }
+            EngineError::MissingAsset { kind, name } => write!(f, "{} {} does not exist", kind, name),
+            EngineError::InvalidMesh { path, error } => write!(f, "invalid mesh {}: {}", path, error),
+            EngineError::Draw { message } => write!(f, "could not draw: {}", message),
+            EngineError::RenderGraph { message } => write!(f, "invalid render graph: {}", message),
+            EngineError::UiLayout { message } => write!(f, "could not lay out the interface: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl EngineError {
+    // for the errors which are only known by their description
+    pub fn io(path: &str, err: impl fmt::Display) -> Self {
+        EngineError::Io {
+            path: path.to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn parse(path: &str, err: impl fmt::Display) -> Self {
+        EngineError::Parse {
+            path: path.to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn upload(asset: &str, err: impl fmt::Debug) -> Self {
+        EngineError::GpuUpload {
+            asset: asset.to_string(),
+            message: format!("{:?}", err),
+        }
+    }
+
+    pub fn missing(kind: AssetKind, name: &str) -> Self {
+        EngineError::MissingAsset {
+            kind,
+            name: name.to_string(),
+        }
+    }
+
+    // the shader errors keep the path of the variant which failed
+    pub fn from_shader(path: &str, err: ShaderError) -> Self {
+        match err {
+            ShaderError::Io { path, message } => EngineError::Io { path, message },
+            ShaderError::IncludeCycle { .. } | ShaderError::MalformedInclude { .. } => {
+                EngineError::parse(path, err)
+            }
+            ShaderError::Compile { stage, log } => EngineError::ShaderCompile {
+                path: format!("{} ({} stage)", path, stage),
+                log,
+            },
+            ShaderError::Link { log } => EngineError::ShaderCompile {
+                path: path.to_string(),
+                log,
+            },
+            ShaderError::Unsupported(message) => EngineError::ShaderCompile {
+                path: path.to_string(),
+                log: message,
+            },
+        }
+    }
+
+    pub fn from_image(path: &str, err: image::ImageError) -> Self {
+        match err {
+            image::ImageError::IoError(err) => EngineError::io(path, err),
+            err => EngineError::parse(path, err),
+        }
+    }
+}
+
+impl From<glium::DrawError> for EngineError {
+    fn from(err: glium::DrawError) -> Self {
+        EngineError::Draw {
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<glium::SwapBuffersError> for EngineError {
+    fn from(err: glium::SwapBuffersError) -> Self {
+        EngineError::Draw {
+            message: err.to_string(),
+        }
+    }
+}
+
+// what the scene does when something fails
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorAction {
+    Panic,
+    // the error is reported and whatever needed the asset is not drawn
+    SkipAndLog,
+    // the error is reported and a placeholder takes the place of the asset when there is one
+    // (magenta texture, cube, default material or shader), otherwise it is skipped
+    Fallback,
+}
+
+// decides what to do for every error of a scene, see Scene::set_error_policy
+pub type ErrorPolicy = Box<dyn Fn(&EngineError) -> ErrorAction>;
+
+pub fn default_error_policy() -> ErrorPolicy {
+    return Box::new(|_| ErrorAction::Fallback);
+}
+
+impl EngineError {
+    // the logging target the error is reported under
+    pub fn target(&self) -> &'static str {
+        match self {
+            EngineError::Draw { .. } | EngineError::RenderGraph { .. } | EngineError::UiLayout { .. } => RENDER,
+            _ => ASSETS,
+        }
+    }
+}
+
+// applies the action chosen by the policy, returns whether the fallback should be used
+pub fn handle_error(policy: &ErrorPolicy, err: &EngineError) -> bool {
+    let action = policy(err);
+    // the target of an event has to be known at compile time
+    match (err.target(), action) {
+        (RENDER, ErrorAction::Panic) => error!(target: RENDER, "{}", err),
+        (_, ErrorAction::Panic) => error!(target: ASSETS, "{}", err),
+        (RENDER, _) => warn!(target: RENDER, ?action, "{}", err),
+        (_, _) => warn!(target: ASSETS, ?action, "{}", err),
+    }
+    if action == ErrorAction::Panic {
+        panic!("{}", err);
+    }
+    return action == ErrorAction::Fallback;
+}
+
+// the asset when it could be loaded, otherwise its placeholder when the policy asks for one
+// a placeholder which can't be created either goes through the policy too and is skipped
+pub fn or_fallback<T>(
+    policy: &ErrorPolicy,
+    loaded: Result<T, EngineError>,
+    fallback: impl FnOnce() -> Result<T, EngineError>,
+) -> Option<T> {
+    let err = match loaded {
+        Ok(asset) => return Some(asset),
+        Err(err) => err,
+    };
+    if !handle_error(policy, &err) {
+        return None;
+    }
+    match fallback() {
+        Ok(asset) => return Some(asset),
+        Err(err) => {
+            handle_error(policy, &err);
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // a policy which records the errors it was asked about
+    fn recording_policy(action: ErrorAction) -> (ErrorPolicy, Rc<RefCell<Vec<String>>>) {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let recorded = seen.clone();
+        let policy: ErrorPolicy = Box::new(move |err| {
+            recorded.borrow_mut().push(err.to_string());
+            return action;
+        });
+        return (policy, seen);
+    }
+
+    fn missing_texture_error() -> EngineError {
+        return EngineError::missing(AssetKind::Texture, "bricks.png");
+    }
+
+    #[test]
+    fn loaded_assets_dont_reach_the_policy() {
+        let (policy, seen) = recording_policy(ErrorAction::Panic);
+        let asset = or_fallback(&policy, Ok("bricks"), || Ok("magenta"));
+        assert_eq!(asset, Some("bricks"));
+        assert!(seen.borrow().is_empty());
+    }
+
+    #[test]
+    fn skipped_assets_have_no_fallback() {
+        let (policy, seen) = recording_policy(ErrorAction::SkipAndLog);
+        assert!(!handle_error(&policy, &missing_texture_error()));
+        let asset = or_fallback(&policy, Err(missing_texture_error()), || -> Result<&str, EngineError> {
+            panic!("the fallback shouldn't be created");
+        });
+        assert_eq!(asset, None);
+        assert_eq!(seen.borrow().len(), 2);
+    }
+
+    #[test]
+    fn fallbacks_replace_the_missing_assets() {
+        let (policy, seen) = recording_policy(ErrorAction::Fallback);
+        assert!(handle_error(&policy, &missing_texture_error()));
+        let asset = or_fallback(&policy, Err(missing_texture_error()), || Ok("magenta"));
+        assert_eq!(asset, Some("magenta"));
+        assert_eq!(seen.borrow().len(), 2);
+    }
+
+    #[test]
+    fn failed_fallbacks_are_reported_and_skipped() {
+        let (policy, seen) = recording_policy(ErrorAction::Fallback);
+        let asset: Option<&str> = or_fallback(&policy, Err(missing_texture_error()), || {
+            Err(EngineError::upload("cube", "out of memory"))
+        });
+        assert_eq!(asset, None);
+        let seen = seen.borrow();
+        assert_eq!(seen.len(), 2);
+        assert!(seen[1].contains("cube"));
+    }
+
+    #[test]
+    fn policies_can_depend_on_the_error() {
+        let policy: ErrorPolicy = Box::new(|err| match err {
+            EngineError::MissingAsset { kind: AssetKind::Texture, .. } => ErrorAction::SkipAndLog,
+            _ => ErrorAction::Fallback,
+        });
+        let texture = or_fallback(&policy, Err(missing_texture_error()), || Ok("magenta"));
+        assert_eq!(texture, None);
+        let model = or_fallback(&policy, Err(EngineError::missing(AssetKind::Model, "ship.obj")), || Ok("cube"));
+        assert_eq!(model, Some("cube"));
+    }
+
+    #[test]
+    #[should_panic(expected = "bricks.png")]
+    fn panicking_policies_panic() {
+        let (policy, _) = recording_policy(ErrorAction::Panic);
+        handle_error(&policy, &missing_texture_error());
+    }
+}
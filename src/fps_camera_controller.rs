@@ -4,10 +4,12 @@
 
 use crate::camera::Camera;
 use crate::input::KeyboardState;
+use crate::logging::INPUT;
 use crate::transform::rotation_to_direction;
 use cgmath::Vector3;
 use glium::winit::keyboard::KeyCode::*;
 use glium::winit::keyboard::SmolStr;
+use tracing::debug;
 
 // Once again could use more generic types, can't be bothered for now, might never be
 enum CamInstr {
@@ -48,12 +50,13 @@ fn execute_camera_instr(instructions: CamInstr, camera: &mut Camera) -> () {
 
             CamInstr::PrintTransform() => {
                 let cam_fwd = camera.get_fwd();
-                print!(
-                    "cam fwd: x={}, y={}, z={}\n",
+                debug!(
+                    target: INPUT,
+                    "cam fwd: x={}, y={}, z={}",
                     cam_fwd.x, cam_fwd.y, cam_fwd.z
                 );
                 let cam_up = camera.get_up();
-                print!("cam up: x={}, y={}, z={}\n", cam_up.x, cam_up.y, cam_up.z);
+                debug!(target: INPUT, "cam up: x={}, y={}, z={}", cam_up.x, cam_up.y, cam_up.z);
                 camera.transform.print_transform();
             }
         }
@@ -62,7 +65,7 @@ fn execute_camera_instr(instructions: CamInstr, camera: &mut Camera) -> () {
 
 fn get_camera_instr(keyboard_state: &KeyboardState, camera: &Camera) -> Vec<CamInstr> {
     let mspeed = 0.5;
-    let rspeed = (3.1415 / 180.0) * 2.0;
+    let rspeed = (std::f32::consts::PI / 180.0) * 2.0;
     let pos = camera.transform.get_position();
     let fwd = Vector3::new(0.0, 0.0, 1.0);
     let mut camera_instructions: Vec<CamInstr> = Vec::new();
@@ -70,7 +73,6 @@ fn get_camera_instr(keyboard_state: &KeyboardState, camera: &Camera) -> Vec<CamI
     // this implementation is satisfactory for tests, but not for actual use
     // TODO need to use mouse movement to rotate camera
     // TODO move relative to the camera fwd for (same for strafing left/right)
-    //println!("event: {:?}", event);
     if keyboard_state.is_pressed(ArrowUp) {
         let delta_rot = Vector3::new(rspeed, 0.0, 0.0);
         camera_instructions.push(CamInstr::Rotate(delta_rot));
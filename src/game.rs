@@ -1,8 +1,18 @@
 use crate::camera::Camera;
+#[cfg(feature = "egui")]
+use crate::egui_integration::EguiIntegration;
 use crate::fps_camera_controller::update_camera;
+use crate::input::key_reaches_game;
 use crate::input::KeyboardState;
 use crate::input::MouseState;
+use crate::logging::INPUT;
+use crate::logging::RENDER;
 use crate::scene::Scene;
+use crate::ui::route_key;
+
+use tracing::info;
+use tracing::info_span;
+use tracing::warn;
 
 use std::thread::sleep;
 
@@ -33,7 +43,7 @@ impl Game {
         }
     }
 
-    pub fn add_scene(&mut self, mut scene: Scene) {
+    pub fn add_scene(&mut self, scene: Scene) {
         self.scenes.push(scene);
     }
 
@@ -49,20 +59,36 @@ impl Game {
         let active_scene = &mut self.scenes[0];
         active_scene.load_all_gc(&display);
 
+        #[cfg(feature = "egui")]
+        let mut egui_integration = EguiIntegration::new(active_scene.egui_context().clone(), &window);
+
+        let mut frame: u64 = 0;
+
+        // TODO move to run_app, the closure based loop is deprecated since winit 0.30
+        #[allow(deprecated)]
         let _game_loop = event_loop.run(move |ev, window_target| {
-            //println!("beginning of game loop");
             let begin_frame_time = std::time::Instant::now();
             let next_frame_time = begin_frame_time + std::time::Duration::from_nanos(16_666_667);
             
             match ev {
                 glium::winit::event::Event::WindowEvent { event, .. } => {
 
-                    mouse_state.process_event(&event);
+                    // the clicks and the keys used by the interface don't reach the game
+                    #[cfg(feature = "egui")]
+                    let ui_consumed = egui_integration.on_window_event(&window, &event);
+                    #[cfg(not(feature = "egui"))]
+                    let ui_consumed = false;
+                    mouse_state.process_event_behind_ui(&event, ui_consumed);
+                    // hovers and clicks of the retained interface, read by the systems below
+                    active_scene.update_ui_input(&mouse_state);
                     // call user created systems
                     
                     // internal event handling
                     match event {
-                    glium::winit::event::WindowEvent::CloseRequested => window_target.exit(),
+                    glium::winit::event::WindowEvent::CloseRequested => {
+                        info!(target: INPUT, "close requested");
+                        window_target.exit()
+                    }
                     KeyboardInput {
                         event:
                             KeyEvent {
@@ -70,8 +96,8 @@ impl Game {
                                 ..
                             },
                         ..
-                    } => {
-                        println!("exiting");
+                    } if !ui_consumed => {
+                        info!(target: INPUT, "exit key pressed");
                         window_target.exit();
                         return;
                     }
@@ -80,33 +106,56 @@ impl Game {
                             KeyEvent {
                                 physical_key: Code(key_code),
                                 state,
+                                repeat,
                                 ..
                             },
                         ..
-                    } => {
-                        keyboard_state.process_event(state, key_code);
+                    } if key_reaches_game(state, ui_consumed) => {
+                        // tab, the arrows, enter and escape move through the focusable nodes
+                        let focused = active_scene.ui_state().focused().is_some();
+                        if let Some(navigation) = route_key(&mut keyboard_state, state, key_code, focused) {
+                            active_scene.navigate_ui(navigation);
+                        }
+                        // cycles through the debug views of the camera
+                        if key_code == KeyCode::F3 && state.is_pressed() && !repeat {
+                            main_camera.debug_view = main_camera.debug_view.next();
+                            info!(target: INPUT, view = ?main_camera.debug_view, "debug view");
+                        }
                     }
                     RedrawRequested => {
+                        frame += 1;
+                        let _span = info_span!(target: RENDER, "frame", number = frame).entered();
                         update_camera(&keyboard_state, &mut main_camera);
 
-                        let target = display.draw();
+                        let mut target = display.draw();
 
-                        active_scene.draw_scene(target, &main_camera);
+                        active_scene.render_scene(&mut target, &main_camera);
+                        // the interface of the last systems is drawn over the scene
+                        #[cfg(feature = "egui")]
+                        if let Err(err) = egui_integration.paint(&display, &mut target) {
+                            active_scene.report_error(&err);
+                        }
+                        active_scene.finish_frame(target);
 
                         if std::time::Instant::now() > next_frame_time {
-                            println!("Warning: needed more time for this frame");
+                            let duration = begin_frame_time.elapsed();
+                            warn!(target: RENDER, ?duration, "needed more time for this frame");
                         }
                     },
                     _ => (),
                 };
+                #[cfg(feature = "egui")]
+                egui_integration.begin_pass(&window);
                 active_scene.execute_frame_steps(&keyboard_state, &mouse_state, &event); 
                 active_scene.execute_triggered_steps(&keyboard_state, &mouse_state, &event); 
+                #[cfg(feature = "egui")]
+                egui_integration.end_pass(&window);
                 },
                 AboutToWait => {
                     window.request_redraw();
                     sleep(next_frame_time - std::time::Instant::now());
                 }
-                _ => (),//println!("event: {:?}", ev),
+                _ => (),
             };
         });
     }
@@ -1,202 +1,318 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
-use std::fs;
-use std::fs::File;
-use std::io::BufReader;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use glium::backend::Facade;
 use glium::glutin::surface::WindowSurface;
-use glium::implement_vertex;
+use glium::index::PrimitiveType;
 use glium::Display;
 use glium::IndexBuffer;
+use glium::vertex::VertexBufferAny;
 use glium::Program;
-use glium::VertexBuffer;
 
 use tobj::load_obj;
 
+use tracing::debug;
+use tracing::error;
+
+use crate::bounds::Aabb;
+use crate::bounds::BoundingSphere;
+use crate::error::EngineError;
+use crate::material::MaterialProperty;
+use crate::lod::LodGroup;
+use crate::logging::ASSETS;
+use crate::material::DEFAULT_MATERIAL;
+use crate::mesh::Mesh;
+use crate::mesh::NormalMode;
+use crate::mesh::VertexAttribute;
+use crate::primitives::cube;
+use crate::primitives::CUBE_MODEL;
+use crate::shader::compile_variant;
+use crate::shader::ShaderVariant;
+use crate::render_state::RenderQueue;
+use crate::texture::TextureSettings;
+
 extern crate glium;
 extern crate tobj;
 
-#[derive(Copy, Clone)]
-pub struct Vertex {
-    position: (f32, f32, f32),
-    normal: (f32, f32, f32),
-    tex_coord: (f32, f32),
+// a mesh and its buffers on the gpu, the vertex buffer only holds the attributes needed by the
+// shaders it has been drawn with so far
+pub struct ObjectModel {
+    pub mesh: Mesh,
+    pub vertices: VertexBufferAny,
+    pub indices: IndexBuffer<u32>,
+    pub attributes: Vec<VertexAttribute>,
+    // in model space, moved along with the transform of the objects for the culling
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
 }
 
-implement_vertex!(Vertex, position, normal, tex_coord);
+impl ObjectModel {
+    // the name is only used to describe the errors
+    pub fn new<F: Facade>(name: &str, mesh: Mesh, facade: &F) -> Result<Self, EngineError> {
+        mesh.validate().map_err(|error| EngineError::InvalidMesh {
+            path: name.to_string(),
+            error,
+        })?;
+        let (vertices, attributes) = mesh
+            .vertex_buffer(facade, &[])
+            .map_err(|err| EngineError::upload(name, err))?;
+        let indices = IndexBuffer::new(facade, PrimitiveType::TrianglesList, &mesh.indices)
+            .map_err(|err| EngineError::upload(name, err))?;
+        return Ok(ObjectModel {
+            aabb: Aabb::from_points(&mesh.positions),
+            bounding_sphere: BoundingSphere::from_points(&mesh.positions),
+            mesh,
+            vertices,
+            indices,
+            attributes,
+        });
+    }
 
-pub struct ObjectModel {
-    pub vertices: glium::VertexBuffer<Vertex>,
-    pub indices: glium::IndexBuffer<u32>,
+    // uploads the vertices again with the attributes the program reads but the buffer lacks
+    pub fn require_attributes<F: Facade>(&mut self, facade: &F, program: &Program) {
+        let missing: Vec<VertexAttribute> = program
+            .attributes()
+            .filter_map(|(name, _)| VertexAttribute::from_name(name))
+            .filter(|attribute| !self.attributes.contains(attribute))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        debug!(target: ASSETS, ?missing, "uploading the vertices again");
+        let mut required = self.attributes.clone();
+        required.extend(missing);
+        match self.mesh.vertex_buffer(facade, &required) {
+            Ok((vertices, attributes)) => {
+                self.vertices = vertices;
+                self.attributes = attributes;
+            }
+            Err(err) => error!(target: ASSETS, "could not create the vertex buffers of a model: {:?}", err),
+        }
+    }
 }
 
 //#[derive(Default)]
 pub struct GraphicComponent {
     pub is_active: bool,
     pub model_path: Option<String>,
-    pub texture_path: Option<String>,
 
-    // TODO replace the src by the local file path
-    pub vertex_shader_src: Option<String>,
-    pub fragment_shader_src: Option<String>,
+    // name of the material in the scene, or path to a .ron/.toml material file
+    pub material: String,
+
+    // values which replace those of the material for this component only
+    pub material_overrides: BTreeMap<String, MaterialProperty>,
+
+    // sampling of the textures, by uniform name, on top of those of the material
+    pub texture_settings: BTreeMap<String, TextureSettings>,
+
+    pub casts_shadows: bool,
+    pub receives_shadows: bool,
 
-    pub brightness: f32,
+    // draws the component in another queue than the one of its material
+    pub render_queue: Option<RenderQueue>,
+
+    // multiplied with the albedo of the material, unlike the overrides it doesn't prevent the
+    // component from being instanced with the others sharing its model and material
+    pub color: [f32; 4],
+
+    // models drawn instead of model_path depending on how large the object looks, model_path
+    // is still used for the bounds, the shadows and to generate the simplified levels
+    pub lod_group: Option<LodGroup>,
+
+    // how the normals of the model files without normals are generated, models are shared so
+    // the first component which loads a model decides
+    pub normal_mode: NormalMode,
 }
 
-impl<'a> GraphicComponent {
+impl GraphicComponent {
     pub fn new(
         model_path: Option<String>,
     ) -> Self {
-        let vertex_shader_src = Some(include_str!("../assets/shaders/vertex_shader.glsl").to_string());
-        let fragment_shader_src = Some(include_str!("../assets/shaders/fragment_shader.glsl").to_string());
         GraphicComponent {
             is_active: true,
             model_path,
-            texture_path: None,
-            vertex_shader_src,
-            fragment_shader_src,
-            brightness: 1.0f32,
+            material: DEFAULT_MATERIAL.to_string(),
+            material_overrides: BTreeMap::new(),
+            texture_settings: BTreeMap::new(),
+            casts_shadows: true,
+            receives_shadows: true,
+            render_queue: None,
+            color: [1.0, 1.0, 1.0, 1.0],
+            lod_group: None,
+            normal_mode: NormalMode::default(),
         }
     }
 
     pub fn can_be_drawn(&self) -> bool {
-        let res = self.model_path.is_some()
-            && self.vertex_shader_src.is_some()
-            && self.fragment_shader_src.is_some();
-        return res;
+        return self.model_path.is_some();
     }
 
+    // binds the texture to the `tex` uniform used by the default shaders
     pub fn add_texture(&mut self, texture_path: String) {
-        self.texture_path = Some(texture_path);
+        self.set_property("tex", MaterialProperty::Texture(texture_path));
     }
 
     pub fn add_model(&mut self, model_path: String) {
         self.model_path = Some(model_path);
     }
+
+    pub fn set_material(&mut self, material: String) {
+        self.material = material;
+    }
+
+    pub fn set_property(&mut self, name: &str, value: MaterialProperty) {
+        self.material_overrides.insert(name.to_string(), value);
+    }
+
+    pub fn clear_property(&mut self, name: &str) {
+        self.material_overrides.remove(name);
+    }
+
+    pub fn set_render_queue(&mut self, queue: Option<RenderQueue>) {
+        self.render_queue = queue;
+    }
+
+    pub fn set_color(&mut self, color: [f32; 4]) {
+        self.color = color;
+    }
+
+    pub fn set_lod_group(&mut self, lod_group: Option<LodGroup>) {
+        self.lod_group = lod_group;
+    }
+
+    pub fn set_normal_mode(&mut self, normal_mode: NormalMode) {
+        self.normal_mode = normal_mode;
+    }
+
+    pub fn set_texture_settings(&mut self, name: &str, settings: TextureSettings) {
+        self.texture_settings.insert(name.to_string(), settings);
+    }
+
+    // textures used by this component on top of those of its material
+    pub fn texture_paths(&self) -> impl Iterator<Item = &str> {
+        self.material_overrides.values().filter_map(|p| p.texture_path())
+    }
+
+    // uniform names and paths of the textures of this component on top of those of its material
+    pub fn texture_bindings(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.material_overrides
+            .iter()
+            .filter_map(|(name, p)| Some((name.as_str(), p.texture_path()?)))
+    }
+
     pub fn is_active(&self) -> bool {
         return self.is_active;
     }
-}
 
-
-pub fn load_model(model_file_path: &Path, display: &Display<WindowSurface>) -> Option<ObjectModel> {
-    let file_result = File::open(model_file_path);
-    match file_result {
-        Err(err) => {
-            println!("Warning, failed to open file: {}", err);
-            return None;
-        }
-        Ok(file) => {
-            let input = BufReader::new(file);
-            let models_result = load_obj(model_file_path, &tobj::GPU_LOAD_OPTIONS);
-            match models_result {
-                Err(err) => {
-                    println!("Warning, failed to load object: {}", err);
-                    return None;
-                }
-                Ok((models, materials)) => {
-                    // TODO this implies that if a single .obj file contains more than one model,
-                    // only the first one will loaded, the rest will be ignored
-                    let mesh = &models[0].mesh;
-                    
-                    let positions = mesh.positions.chunks_exact(3);
-                    let normals = mesh.normals.chunks_exact(3);
-                    // TODO this is not very pretty please don't be mad 🥺👉👈
-                    // TODO investigate because i cannot fathom why this is necessary
-                    let tmp = vec![0.0f32; 2 * mesh.positions.len() / 3];
-                    let tex_coords = 
-                        if mesh.texcoords.len() > 0 {
-                            mesh.texcoords.chunks_exact(2)
-                        } else {
-                            tmp.chunks_exact(2)
-                        };
-
-                    let vertex_info = positions.zip(normals).zip(tex_coords);
-
-                    // need to factorise this, possibly with the previous line to avoid doing
-                    // two loops
-                    let vertices_vec: Vec<Vertex>;
-                    vertices_vec = vertex_info
-                        .map(|((position, normal), tex_coord)| Vertex {
-                            position: (position[0], position[1], position[2]),
-                            normal: (normal[0], normal[1], normal[2]),
-                            tex_coord: (tex_coord[0], tex_coord[1]),
-                        })
-                        .collect();
-
-                    let vertices_vertex_buffer = VertexBuffer::new(display, &vertices_vec);
-                    let indices_vertex_buffer = IndexBuffer::new(
-                        display,
-                        glium::index::PrimitiveType::TrianglesList,
-                        &mesh.indices,
-                    );
-
-                    if vertices_vertex_buffer.is_err()
-                        || indices_vertex_buffer.is_err()
-                    {
-                        println!("Error, could not create index buffers for this object");
-                        return None;
-                    } else {
-                        // can use the unwraps because the if guarantees that they will not be
-                        // errors
-                        let new_geometry = ObjectModel {
-                            vertices: vertices_vertex_buffer.unwrap(),
-                            indices: indices_vertex_buffer.unwrap(),
-                        };
-                        return Some(new_geometry);
-                    }
-                }
-            }
+    // the key of the model drawn for a level of the lod group, the model_path without level
+    pub fn model_key(&self, level: Option<usize>) -> Cow<'_, str> {
+        let source = self.model_path.as_deref().unwrap_or_default();
+        match (&self.lod_group, level) {
+            (Some(group), Some(level)) => group.model_key(level, source),
+            _ => Cow::Borrowed(source),
         }
     }
+
+    // the components which change the uniforms of their material are drawn on their own
+    pub fn can_be_instanced(&self) -> bool {
+        return self.material_overrides.is_empty() && self.texture_settings.is_empty();
+    }
 }
 
-// we have two options, we can chose to have the engine crash if anything unexpected happens or
-// wait until we have no choice, will go with the middle ground of waiting as long as possible
-// whilst loudly complaining
-// check if shaders already loaded?
-// TODO return an error, print warning and continue the best we can if function fails
-pub fn load_shaders<'a, F: Facade>(
-    vertex_shader_src: &str,
-    fragment_shader_src: &str,
-    facade: &'a F,
-) -> Option<Program> {
-    /*
-     * let vertex_file_res = fs::read_to_string(vertex_shader_path);
-    let fragment_file_res = fs::read_to_string(fragment_shader_path);
-
-    match vertex_file_res {
-        Err(err) => {
-            println!("Warning, failed to open vertex shader file: {}", err);
-            return None;
-        }
-        Ok(_) => {}
-    };
 
-    match fragment_file_res {
-        Err(err) => {
-            println!("Warning, failed to open fragment shader file: {}", err);
-            return None;
-        }
-        Ok(_) => {}
+pub fn load_model(
+    model_file_path: &Path,
+    normal_mode: NormalMode,
+    display: &Display<WindowSurface>,
+) -> Result<ObjectModel, EngineError> {
+    debug!(target: ASSETS, path = %model_file_path.display(), "loading model");
+    let mesh = load_mesh(model_file_path, normal_mode)?;
+    return ObjectModel::new(&model_file_path.display().to_string(), mesh, display);
+}
+
+// the normals are computed with the given mode when the file has none
+pub fn load_mesh(model_file_path: &Path, normal_mode: NormalMode) -> Result<Mesh, EngineError> {
+    let path = model_file_path.display().to_string();
+    let (models, materials) = load_obj(model_file_path, &tobj::GPU_LOAD_OPTIONS).map_err(|err| match err {
+        tobj::LoadError::OpenFileFailed => EngineError::io(&path, err),
+        err => EngineError::parse(&path, err),
+    })?;
+    // TODO this implies that if a single .obj file contains more than one model,
+    // only the first one will loaded, the rest will be ignored
+    let Some(model) = models.first() else {
+        return Err(EngineError::parse(&path, "the file contains no mesh"));
     };
+    let mesh = &model.mesh;
 
-    let vertex_shader_src = vertex_file_res.unwrap();
-    let fragment_shader_src = fragment_file_res.unwrap();
-    */
+    let positions = mesh.positions.chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect();
+    let mut new_mesh = Mesh::from_positions_indices(positions, mesh.indices.clone());
+    // the attributes absent from the file are left out, they are generated if a shader
+    // needs them
+    if !mesh.normals.is_empty() {
+        let normals = mesh.normals.chunks_exact(3).map(|n| [n[0], n[1], n[2]]);
+        new_mesh = new_mesh.with_normals(normals.collect());
+    }
+    if !mesh.texcoords.is_empty() {
+        let tex_coords = mesh.texcoords.chunks_exact(2).map(|t| [t[0], t[1]]);
+        new_mesh = new_mesh.with_tex_coords(tex_coords.collect());
+    }
+    if !mesh.vertex_color.is_empty() {
+        let colors = mesh.vertex_color.chunks_exact(3).map(|c| [c[0], c[1], c[2], 1.0]);
+        new_mesh = new_mesh.with_colors(colors.collect());
+    }
+    // the indices are checked before anything uses them
+    new_mesh
+        .validate()
+        .map_err(|error| EngineError::InvalidMesh { path: path.clone(), error })?;
+    if new_mesh.normals.is_none() {
+        debug!(target: ASSETS, path, ?normal_mode, "the file has no normals, generating them");
+        new_mesh.generate_normals_with(normal_mode);
+    }
+    return Ok(new_mesh);
+}
 
-    let res = glium::Program::from_source(facade, &vertex_shader_src, &fragment_shader_src, None);
-    match res {
-        Err(prog_err) => {
-            println!("WARNING: shaders have failed to compile");
-            println!("{}", prog_err.to_string());
-            return None;
-        }
-        Ok(prog_res) => {
-            return Some(prog_res);
-        }
+pub fn load_shaders<F: Facade>(variant: &ShaderVariant, facade: &F) -> Result<Program, EngineError> {
+    debug!(target: ASSETS, vertex = variant.vertex_path, fragment = variant.fragment_path, "compiling shaders");
+    return compile_variant(variant, facade).map_err(|err| {
+        let path = format!("{} + {}", variant.vertex_path, variant.fragment_path);
+        EngineError::from_shader(&path, err)
+    });
+}
+
+// what the models which couldn't be loaded are replaced with
+pub fn missing_model<F: Facade>(facade: &F) -> Result<ObjectModel, EngineError> {
+    return ObjectModel::new(CUBE_MODEL, cube(1.0), facade);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two triangles folded at a right angle along their shared edge, without normals
+    fn folded_quad() -> String {
+        let path = std::env::temp_dir().join(format!("sparkle_folded_quad_{}.obj", std::process::id()));
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 1 1 1\nf 1 2 3\nf 2 4 3\n";
+        std::fs::write(&path, obj).unwrap();
+        return path.to_string_lossy().to_string();
+    }
+
+    #[test]
+    fn components_choose_how_normals_are_generated() {
+        let mut gc = GraphicComponent::new(Some(folded_quad()));
+        assert_eq!(gc.normal_mode, NormalMode::default());
+        gc.set_normal_mode(NormalMode::Flat);
+        let path = gc.model_path.clone().unwrap();
+
+        let flat = load_mesh(Path::new(&path), gc.normal_mode).unwrap();
+        assert_eq!(flat.vertex_count(), 6);
+        let smooth = load_mesh(Path::new(&path), NormalMode::Smooth { crease_angle: 100.0f32.to_radians() }).unwrap();
+        assert_eq!(smooth.vertex_count(), 4);
+        // the fold is sharper than the default crease angle
+        let creased = load_mesh(Path::new(&path), NormalMode::default()).unwrap();
+        assert_eq!(creased.vertex_count(), 6);
     }
 }
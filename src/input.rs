@@ -21,6 +21,9 @@ pub struct MouseState {
     pub pos : (f64, f64),
     pub left_button_pressed : bool,
     pub right_button_pressed : bool,
+    // whether the buttons are held, unlike the pressed ones which are only true for the event
+    pub left_button_down : bool,
+    pub right_button_down : bool,
 }
 
 impl MouseState {
@@ -29,6 +32,8 @@ impl MouseState {
             pos : (0f64, 0f64),
             left_button_pressed : false,
             right_button_pressed : false,
+            left_button_down : false,
+            right_button_down : false,
         }
     }
     
@@ -39,16 +44,44 @@ impl MouseState {
             CursorMoved {position : pos, ..} => {
                 self.pos = (pos.x, pos.y);
             },
-            MouseInput {button, ..} => {
+            MouseInput {button, state, ..} => {
                 match button {
-                    Left => self.left_button_pressed = true,
-                    Right => self.right_button_pressed = true,
+                    Left => {
+                        self.left_button_pressed = true;
+                        self.left_button_down = state.is_pressed();
+                    }
+                    Right => {
+                        self.right_button_pressed = true;
+                        self.right_button_down = state.is_pressed();
+                    }
                     _ => (),
                 }
             }
             _ => (),
         }
     }
+
+    // for the clicks which were used by something else than the game, like the interface
+    pub fn release_buttons(&mut self) {
+        self.left_button_pressed = false;
+        self.right_button_pressed = false;
+        self.left_button_down = false;
+        self.right_button_down = false;
+    }
+
+    // the cursor still moves over the interface, but the clicks it consumed don't reach the game
+    pub fn process_event_behind_ui(&mut self, window_event: &WindowEvent, ui_consumed: bool) {
+        self.process_event(window_event);
+        if ui_consumed {
+            self.release_buttons();
+        }
+    }
+}
+
+// the keys typed in the interface don't reach the game, their releases still do so that none
+// stays pressed
+pub fn key_reaches_game(key_state: ElementState, ui_consumed: bool) -> bool {
+    return !(ui_consumed && key_state.is_pressed());
 }
 
 impl KeyboardState {
@@ -85,3 +118,66 @@ impl KeyboardState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glium::winit::dpi::PhysicalPosition;
+    use glium::winit::event::DeviceId;
+    use glium::winit::event::MouseButton;
+
+    fn click(state: ElementState) -> WindowEvent {
+        return MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button: MouseButton::Left,
+        };
+    }
+
+    #[test]
+    fn clicks_reach_the_game_unless_the_ui_consumed_them() {
+        let mut mouse = MouseState::new();
+        mouse.process_event_behind_ui(&click(ElementState::Pressed), false);
+        assert!(mouse.left_button_pressed && mouse.left_button_down);
+
+        let mut mouse = MouseState::new();
+        mouse.process_event_behind_ui(&click(ElementState::Pressed), true);
+        assert!(!mouse.left_button_pressed && !mouse.left_button_down);
+    }
+
+    #[test]
+    fn a_consumed_click_releases_the_held_buttons() {
+        let mut mouse = MouseState::new();
+        mouse.process_event_behind_ui(&click(ElementState::Pressed), false);
+        // the button is released over a panel
+        mouse.process_event_behind_ui(&click(ElementState::Released), true);
+        assert!(!mouse.left_button_down);
+    }
+
+    #[test]
+    fn the_cursor_moves_over_the_ui() {
+        let mut mouse = MouseState::new();
+        let moved = CursorMoved {
+            device_id: DeviceId::dummy(),
+            position: PhysicalPosition::new(12.0, 34.0),
+        };
+        mouse.process_event_behind_ui(&moved, true);
+        assert_eq!(mouse.pos, (12.0, 34.0));
+    }
+
+    #[test]
+    fn only_the_key_presses_are_kept_from_the_game() {
+        assert!(key_reaches_game(ElementState::Pressed, false));
+        assert!(!key_reaches_game(ElementState::Pressed, true));
+        assert!(key_reaches_game(ElementState::Released, true));
+
+        // a key pressed before the interface took the focus is still released
+        let mut keyboard = KeyboardState::new();
+        keyboard.process_event(ElementState::Pressed, KeyCode::KeyW);
+        if key_reaches_game(ElementState::Released, true) {
+            keyboard.process_event(ElementState::Released, KeyCode::KeyW);
+        }
+        assert!(keyboard.is_released(KeyCode::KeyW));
+    }
+}
@@ -0,0 +1,84 @@
+#![allow(dead_code)]
+
+use std::ops::Range;
+
+use glium::backend::Facade;
+use glium::implement_vertex;
+use glium::Program;
+use glium::VertexBuffer;
+
+use crate::error::EngineError;
+use crate::shader::ShaderVariant;
+
+// enables the per instance attributes of the vertex shaders, see assets/shaders/vertex_shader.glsl
+pub const INSTANCING_KEYWORD: &str = "INSTANCING";
+
+// what changes from one instance of a batch to the next, everything else is shared
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InstanceData {
+    // the model matrix, column major
+    pub i_matrix: [[f32; 4]; 4],
+    // the colour of the graphic component, multiplied with the albedo of the material
+    pub i_color: [f32; 4],
+}
+
+implement_vertex!(InstanceData, i_matrix, i_color);
+
+// the variant of a material which reads its model matrix from the instance buffer
+pub fn instanced_variant(variant: &ShaderVariant) -> ShaderVariant {
+    return variant.clone().with_keyword(INSTANCING_KEYWORD);
+}
+
+// the custom vertex shaders which ignore the keyword still use the matrix uniform, their
+// objects are drawn one by one
+pub fn supports_instancing(program: &Program) -> bool {
+    return program.get_attribute("i_matrix").is_some();
+}
+
+// fills the buffer shared by every batch of the frame, it only grows, by powers of two so that it
+// isn't reallocated every time an object is added
+pub fn upload_instances<F: Facade>(
+    buffer: &mut Option<VertexBuffer<InstanceData>>,
+    facade: &F,
+    instances: &[InstanceData],
+) -> Result<(), EngineError> {
+    if instances.is_empty() {
+        return Ok(());
+    }
+    let capacity = buffer.as_ref().map(|buffer| buffer.len()).unwrap_or(0);
+    if capacity < instances.len() {
+        let size = instances.len().next_power_of_two();
+        let new_buffer = VertexBuffer::empty_dynamic(facade, size)
+            .map_err(|err| EngineError::upload("instance buffer", err))?;
+        *buffer = Some(new_buffer);
+    }
+    if let Some(slice) = buffer.as_ref().and_then(|buffer| buffer.slice(0..instances.len())) {
+        slice.write(instances);
+    }
+    return Ok(());
+}
+
+// splits a sorted draw list into runs of objects which can be drawn with a single call, the
+// objects without key are always drawn alone and so are the keys which appear only once
+pub fn batch_ranges(keys: &[Option<u64>]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (i, key) in keys.iter().enumerate() {
+        match ranges.last_mut() {
+            Some(range) if key.is_some() && keys[range.start] == *key => range.end = i + 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+    return ranges;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_keys_are_batched() {
+        let keys = [Some(1), Some(1), Some(1), None, None, Some(2), Some(1), Some(1)];
+        assert_eq!(batch_ranges(&keys), vec![0..3, 3..4, 4..5, 5..6, 6..8]);
+        assert!(batch_ranges(&[]).is_empty());
+    }
+}
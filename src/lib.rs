@@ -1,8 +1,40 @@
+// the codebase deliberately uses explicit returns and `-> ()`, and most of the structs which
+// have a `new` don't have a meaningful default
+#![allow(clippy::needless_return)]
+#![allow(clippy::unused_unit)]
+#![allow(clippy::new_without_default)]
+
+pub mod background;
+pub mod bounds;
 pub mod camera;
+pub mod debug_draw;
+pub mod debug_view;
+#[cfg(feature = "egui")]
+pub mod egui_integration;
+pub mod environment;
+pub mod error;
 pub mod fps_camera_controller;
 pub mod game;
 pub mod game_object;
 pub mod graphic_component;
 pub mod input;
+pub mod instancing;
+pub mod light;
+pub mod lod;
+pub mod logging;
+pub mod material;
+pub mod mesh;
+pub mod post_processing;
+pub mod primitives;
+pub mod render_graph;
+pub mod render_state;
 pub mod scene;
+pub mod shader;
+pub mod shadow;
+pub mod simplify;
+pub mod text;
+pub mod texture;
+pub mod tonemapping;
 pub mod transform;
+pub mod ui;
+pub mod uniforms;
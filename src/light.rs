@@ -0,0 +1,264 @@
+#![allow(dead_code)]
+
+use cgmath::Vector3;
+
+use glium::uniforms::UniformValue;
+
+use legion::world::World;
+use legion::IntoQuery;
+
+use crate::transform::rotation_to_direction;
+use crate::transform::Transform;
+use crate::uniforms::UniformBag;
+
+// must be kept in sync with the sizes of the arrays in assets/shaders/lights.glsl
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 8;
+pub const MAX_SPOT_LIGHTS: usize = 4;
+
+// lights are components, they take their position and their orientation from the transform of
+// their object and shine along its local z axis
+
+// a light infinitely far away, like the sun, only the orientation of the transform matters
+#[derive(Copy, Clone, Debug)]
+pub struct DirectionalLight {
+    pub color: [f32; 3],
+    pub intensity: f32,
+    // only the first directional light casting shadows gets a (cascaded) shadow map
+    pub casts_shadows: bool,
+}
+
+// shines in every direction from the position of the transform, fades to nothing at range
+#[derive(Copy, Clone, Debug)]
+pub struct PointLight {
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+}
+
+// a cone of light, fully lit within the inner angle and fading until the outer angle
+// the angles are half angles of the cone in radians
+#[derive(Copy, Clone, Debug)]
+pub struct SpotLight {
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    pub casts_shadows: bool,
+}
+
+impl DirectionalLight {
+    pub fn new(color: [f32; 3], intensity: f32) -> Self {
+        DirectionalLight {
+            color,
+            intensity,
+            casts_shadows: false,
+        }
+    }
+}
+
+impl PointLight {
+    pub fn new(color: [f32; 3], intensity: f32, range: f32) -> Self {
+        PointLight {
+            color,
+            intensity,
+            range,
+        }
+    }
+}
+
+impl SpotLight {
+    pub fn new(color: [f32; 3], intensity: f32, range: f32, inner_angle: f32, outer_angle: f32) -> Self {
+        SpotLight {
+            color,
+            intensity,
+            range,
+            inner_angle,
+            outer_angle,
+            casts_shadows: false,
+        }
+    }
+}
+
+fn light_direction(transform: &Transform) -> [f32; 3] {
+    return rotation_to_direction(transform.get_qrot(), Vector3::new(0.0, 0.0, 1.0)).into();
+}
+
+// the lights of a scene as they will be sent to the shaders
+// if there are more lights than the shaders can handle, the extra ones are ignored
+pub struct SceneLights {
+    pub ambient: [f32; 3],
+    pub directional: Vec<([f32; 3], DirectionalLight)>,
+    pub point: Vec<([f32; 3], PointLight)>,
+    pub spot: Vec<([f32; 3], [f32; 3], SpotLight)>,
+}
+
+impl SceneLights {
+    pub fn gather(world: &World, ambient: [f32; 3]) -> Self {
+        let directional = <(&DirectionalLight, &Transform)>::query()
+            .iter(world)
+            .take(MAX_DIRECTIONAL_LIGHTS)
+            .map(|(light, transform)| (light_direction(transform), *light))
+            .collect();
+        let point = <(&PointLight, &Transform)>::query()
+            .iter(world)
+            .take(MAX_POINT_LIGHTS)
+            .map(|(light, transform)| (transform.get_position().into(), *light))
+            .collect();
+        let spot = <(&SpotLight, &Transform)>::query()
+            .iter(world)
+            .take(MAX_SPOT_LIGHTS)
+            .map(|(light, transform)| {
+                (transform.get_position().into(), light_direction(transform), *light)
+            })
+            .collect();
+        SceneLights {
+            ambient,
+            directional,
+            point,
+            spot,
+        }
+    }
+
+    pub fn bind(&self, uniforms: &mut UniformBag) {
+        uniforms.set("u_ambient_light", UniformValue::Vec3(self.ambient));
+
+        uniforms.set(
+            "u_directional_light_count",
+            UniformValue::SignedInt(self.directional.len() as i32),
+        );
+        for (i, (direction, light)) in self.directional.iter().enumerate() {
+            let name = format!("u_directional_lights[{}]", i);
+            uniforms.set(&format!("{}.direction", name), UniformValue::Vec3(*direction));
+            uniforms.set(&format!("{}.color", name), UniformValue::Vec3(light.color));
+            uniforms.set(&format!("{}.intensity", name), UniformValue::Float(light.intensity));
+        }
+
+        uniforms.set(
+            "u_point_light_count",
+            UniformValue::SignedInt(self.point.len() as i32),
+        );
+        for (i, (position, light)) in self.point.iter().enumerate() {
+            let name = format!("u_point_lights[{}]", i);
+            uniforms.set(&format!("{}.position", name), UniformValue::Vec3(*position));
+            uniforms.set(&format!("{}.color", name), UniformValue::Vec3(light.color));
+            uniforms.set(&format!("{}.intensity", name), UniformValue::Float(light.intensity));
+            uniforms.set(&format!("{}.range", name), UniformValue::Float(light.range));
+        }
+
+        uniforms.set(
+            "u_spot_light_count",
+            UniformValue::SignedInt(self.spot.len() as i32),
+        );
+        for (i, (position, direction, light)) in self.spot.iter().enumerate() {
+            let name = format!("u_spot_lights[{}]", i);
+            uniforms.set(&format!("{}.position", name), UniformValue::Vec3(*position));
+            uniforms.set(&format!("{}.direction", name), UniformValue::Vec3(*direction));
+            uniforms.set(&format!("{}.color", name), UniformValue::Vec3(light.color));
+            uniforms.set(&format!("{}.intensity", name), UniformValue::Float(light.intensity));
+            uniforms.set(&format!("{}.range", name), UniformValue::Float(light.range));
+            uniforms.set(
+                &format!("{}.cos_inner", name),
+                UniformValue::Float(light.inner_angle.cos()),
+            );
+            uniforms.set(
+                &format!("{}.cos_outer", name),
+                UniformValue::Float(light.outer_angle.cos()),
+            );
+            // overwritten by the shadows if the light gets a shadow map
+            uniforms.set(&format!("{}.shadow_index", name), UniformValue::SignedInt(-1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glium::uniforms::Uniforms;
+
+    const LIGHTS_SHADER: &str = include_str!("../assets/shaders/lights.glsl");
+
+    fn shader_define(name: &str) -> usize {
+        let prefix = format!("#define {} ", name);
+        let line = LIGHTS_SHADER.lines().find(|line| line.starts_with(&prefix)).unwrap();
+        return line[prefix.len()..].trim().parse().unwrap();
+    }
+
+    fn crowded_world() -> World {
+        let mut world = World::default();
+        for _ in 0..MAX_DIRECTIONAL_LIGHTS + 2 {
+            world.push((DirectionalLight::new([1.0, 1.0, 1.0], 1.0), Transform::default()));
+        }
+        for _ in 0..MAX_POINT_LIGHTS + 3 {
+            world.push((PointLight::new([1.0, 0.0, 0.0], 2.0, 10.0), Transform::default()));
+        }
+        for _ in 0..MAX_SPOT_LIGHTS + 1 {
+            world.push((SpotLight::new([0.0, 0.0, 1.0], 3.0, 5.0, 0.2, 0.4), Transform::default()));
+        }
+        return world;
+    }
+
+    fn bound_uniforms(lights: &SceneLights) -> Vec<(String, Option<i32>)> {
+        let mut uniforms = UniformBag::new();
+        lights.bind(&mut uniforms);
+        let mut names = Vec::new();
+        uniforms.visit_values(|name, value| match value {
+            UniformValue::SignedInt(count) => names.push((name.to_string(), Some(count))),
+            _ => names.push((name.to_string(), None)),
+        });
+        return names;
+    }
+
+    #[test]
+    fn limits_match_the_shaders() {
+        assert_eq!(shader_define("MAX_DIRECTIONAL_LIGHTS"), MAX_DIRECTIONAL_LIGHTS);
+        assert_eq!(shader_define("MAX_POINT_LIGHTS"), MAX_POINT_LIGHTS);
+        assert_eq!(shader_define("MAX_SPOT_LIGHTS"), MAX_SPOT_LIGHTS);
+    }
+
+    #[test]
+    fn extra_lights_are_ignored() {
+        let lights = SceneLights::gather(&crowded_world(), [0.1, 0.1, 0.1]);
+        assert_eq!(lights.directional.len(), MAX_DIRECTIONAL_LIGHTS);
+        assert_eq!(lights.point.len(), MAX_POINT_LIGHTS);
+        assert_eq!(lights.spot.len(), MAX_SPOT_LIGHTS);
+
+        let uniforms = bound_uniforms(&lights);
+        let count = |name: &str| uniforms.iter().find(|(n, _)| n == name).and_then(|(_, count)| *count);
+        assert_eq!(count("u_directional_light_count"), Some(MAX_DIRECTIONAL_LIGHTS as i32));
+        assert_eq!(count("u_point_light_count"), Some(MAX_POINT_LIGHTS as i32));
+        assert_eq!(count("u_spot_light_count"), Some(MAX_SPOT_LIGHTS as i32));
+        let has = |name: String| uniforms.iter().any(|(n, _)| *n == name);
+        assert!(has(format!("u_point_lights[{}].range", MAX_POINT_LIGHTS - 1)));
+        assert!(!has(format!("u_point_lights[{}].position", MAX_POINT_LIGHTS)));
+        assert!(has(format!("u_spot_lights[{}].shadow_index", MAX_SPOT_LIGHTS - 1)));
+        assert!(!has(format!("u_spot_lights[{}].color", MAX_SPOT_LIGHTS)));
+    }
+
+    #[test]
+    fn uniform_names_match_the_shaders() {
+        let lights = SceneLights::gather(&crowded_world(), [0.0, 0.0, 0.0]);
+        for (name, _) in bound_uniforms(&lights) {
+            let Some((array, field)) = name.split_once('.') else {
+                assert!(LIGHTS_SHADER.contains(&format!(" {};", name)), "{} isn't declared", name);
+                continue;
+            };
+            let (array, _) = array.split_once('[').unwrap();
+            let light_struct = match array {
+                "u_directional_lights" => "DirectionalLight",
+                "u_point_lights" => "PointLight",
+                "u_spot_lights" => "SpotLight",
+                _ => panic!("unexpected uniform {}", name),
+            };
+            assert!(LIGHTS_SHADER.contains(&format!("uniform {} {}[", light_struct, array)));
+            let declaration = LIGHTS_SHADER
+                .split(&format!("struct {} {{", light_struct))
+                .nth(1)
+                .and_then(|rest| rest.split("};").next())
+                .unwrap();
+            assert!(declaration.contains(&format!(" {};", field)), "{} isn't a field of {}", field, light_struct);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+#![allow(dead_code)]
+
+use std::borrow::Cow;
+
+use crate::bounds::BoundingSphere;
+
+// makes the fragment shaders dither between two levels, see assets/shaders/lod_fade.glsl
+pub const LOD_FADE_KEYWORD: &str = "LOD_FADE";
+
+// what the thresholds of a group are compared with
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LodMetric {
+    // the height of the bounding sphere over the height of the screen, the levels are used while
+    // the object is larger than their threshold, so the thresholds go down
+    ScreenSize,
+    // from the camera to the center of the bounding sphere, the levels are used while the object
+    // is closer than their threshold, so the thresholds go up
+    Distance,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LodModel {
+    // the model_path of the graphic component
+    Source,
+    // a model loaded like the model_path, from a file or from the builtin primitives
+    Path(String),
+    // generated from the model_path when the scene is loaded, keeping about this fraction of its
+    // triangles, see simplify::simplify
+    Simplified(f32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodLevel {
+    pub model: LodModel,
+    pub threshold: f32,
+}
+
+// the models a graphic component switches between depending on how large it looks, the first
+// level is the most detailed
+// past the threshold of the last level the object isn't drawn at all
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodGroup {
+    pub metric: LodMetric,
+    pub levels: Vec<LodLevel>,
+    // width of the band before every threshold in which the two levels are dithered into each
+    // other, as a fraction of the threshold, zero switches at once
+    pub fade: f32,
+}
+
+// the level to draw and, within the fade band, how much of it is still visible
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LodSelection {
+    pub level: usize,
+    // 1 when the level is drawn alone, goes down to 0 as the object gets to the threshold
+    pub fade: f32,
+    // drawn along with the level while it fades, none past the last level
+    pub next: Option<usize>,
+}
+
+impl LodSelection {
+    // the value of u_lod_fade, positive for the level fading out and negative for the one fading
+    // in, so that the two keep complementary pixels
+    pub fn fade_uniform(&self, incoming: bool) -> f32 {
+        if incoming {
+            return self.fade - 1.0;
+        }
+        return self.fade;
+    }
+
+    pub fn is_fading(&self) -> bool {
+        return self.fade < 1.0;
+    }
+}
+
+impl LodGroup {
+    pub fn new(metric: LodMetric) -> Self {
+        return LodGroup {
+            metric,
+            levels: Vec::new(),
+            fade: 0.0,
+        };
+    }
+
+    pub fn with_level(mut self, model: LodModel, threshold: f32) -> Self {
+        self.levels.push(LodLevel { model, threshold });
+        self
+    }
+
+    pub fn with_fade(mut self, fade: f32) -> Self {
+        self.fade = fade.max(0.0);
+        self
+    }
+
+    // the models of the levels generated from the source, by ratio
+    pub fn simplified_ratios(&self) -> impl Iterator<Item = f32> + '_ {
+        self.levels.iter().filter_map(|level| match level.model {
+            LodModel::Simplified(ratio) => Some(ratio),
+            _ => None,
+        })
+    }
+
+    // the key of the model of the level in the models of the scene
+    pub fn model_key<'a>(&'a self, level: usize, source: &'a str) -> Cow<'a, str> {
+        match &self.levels[level].model {
+            LodModel::Source => Cow::Borrowed(source),
+            LodModel::Path(path) => Cow::Borrowed(path),
+            LodModel::Simplified(ratio) => Cow::Owned(simplified_model_key(source, *ratio)),
+        }
+    }
+
+    // none when the object is past the last threshold
+    pub fn select(&self, value: f32) -> Option<LodSelection> {
+        // how far the value is from the threshold, in fade bands, negative past it
+        let margin = |threshold: f32| {
+            let band = (threshold * self.fade).abs().max(f32::EPSILON);
+            match self.metric {
+                LodMetric::ScreenSize => (value - threshold) / band,
+                LodMetric::Distance => (threshold - value) / band,
+            }
+        };
+        let level = self.levels.iter().position(|level| margin(level.threshold) >= 0.0)?;
+        let next = Some(level + 1).filter(|next| *next < self.levels.len());
+        let fade = if self.fade > 0.0 {
+            margin(self.levels[level].threshold).min(1.0)
+        } else {
+            1.0
+        };
+        return Some(LodSelection { level, fade, next });
+    }
+
+    // the value the thresholds are compared with, for a sphere in world space
+    pub fn metric_value(&self, sphere: &BoundingSphere, camera_position: [f32; 3], fov: f32) -> f32 {
+        let offset = [0, 1, 2].map(|i| sphere.center[i] - camera_position[i]);
+        let distance = (offset[0] * offset[0] + offset[1] * offset[1] + offset[2] * offset[2]).sqrt();
+        match self.metric {
+            LodMetric::Distance => distance,
+            LodMetric::ScreenSize => screen_size(sphere.radius, distance, fov),
+        }
+    }
+}
+
+// the fraction of the height of the screen a sphere covers, fov being the vertical field of view
+pub fn screen_size(radius: f32, distance: f32, fov: f32) -> f32 {
+    if distance <= radius {
+        return f32::INFINITY;
+    }
+    return radius / (distance * (fov * 0.5).tan());
+}
+
+// the name under which a simplified level is stored in the models of the scene
+pub fn simplified_model_key(source: &str, ratio: f32) -> String {
+    return format!("{}#lod{}", source, ratio);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(metric: LodMetric, thresholds: [f32; 3]) -> LodGroup {
+        let mut group = LodGroup::new(metric);
+        for (i, threshold) in thresholds.into_iter().enumerate() {
+            group = group.with_level(LodModel::Simplified(1.0 / (i + 1) as f32), threshold);
+        }
+        return group;
+    }
+
+    #[test]
+    fn levels_follow_the_thresholds() {
+        let by_size = group(LodMetric::ScreenSize, [0.5, 0.2, 0.05]);
+        assert_eq!(by_size.select(0.8).unwrap().level, 0);
+        assert_eq!(by_size.select(0.3).unwrap().level, 1);
+        assert_eq!(by_size.select(0.1).unwrap().level, 2);
+        assert!(by_size.select(0.01).is_none());
+        let by_distance = group(LodMetric::Distance, [10.0, 50.0, 200.0]);
+        assert_eq!(by_distance.select(5.0).unwrap().level, 0);
+        assert_eq!(by_distance.select(20.0).unwrap().level, 1);
+        assert!(by_distance.select(500.0).is_none());
+    }
+
+    #[test]
+    fn levels_fade_near_the_thresholds() {
+        let group = group(LodMetric::Distance, [10.0, 50.0, 200.0]).with_fade(0.2);
+        // the band of the first level goes from 8 to 10
+        assert!(!group.select(7.0).unwrap().is_fading());
+        let selection = group.select(9.0).unwrap();
+        assert_eq!((selection.level, selection.next), (0, Some(1)));
+        assert!((selection.fade - 0.5).abs() < 1e-5);
+        assert!((selection.fade_uniform(true) + 0.5).abs() < 1e-5);
+        // the last level fades out into nothing
+        assert_eq!(group.select(190.0).unwrap().next, None);
+    }
+
+    #[test]
+    fn model_keys() {
+        let group = LodGroup::new(LodMetric::Distance)
+            .with_level(LodModel::Source, 10.0)
+            .with_level(LodModel::Path("rock_far.obj".to_string()), 50.0)
+            .with_level(LodModel::Simplified(0.1), 100.0);
+        assert_eq!(group.model_key(0, "rock.obj"), "rock.obj");
+        assert_eq!(group.model_key(1, "rock.obj"), "rock_far.obj");
+        assert_eq!(group.model_key(2, "rock.obj"), "rock.obj#lod0.1");
+        assert_eq!(group.simplified_ratios().collect::<Vec<_>>(), vec![0.1]);
+    }
+}
@@ -0,0 +1,28 @@
+#![allow(dead_code)]
+
+// the engine reports what it does through the tracing facade, nothing is printed unless the game
+// installs a subscriber, either its own or the default one below
+// the events are sorted by target so that they can be filtered, for instance with
+// RUST_LOG=sparkle::render=debug,sparkle::assets=warn
+
+// loading of models, textures, materials, shaders and environments
+pub const ASSETS: &str = "sparkle::assets";
+// drawing of the frames and the passes which compose them
+pub const RENDER: &str = "sparkle::render";
+// keyboard, mouse and window events
+pub const INPUT: &str = "sparkle::input";
+// the legion systems of the scenes
+pub const SYSTEMS: &str = "sparkle::systems";
+
+// prints the events to stderr, filtered by RUST_LOG, or at the info level when it isn't set
+// does nothing if a subscriber has already been installed
+#[cfg(feature = "default-subscriber")]
+pub fn init_default_subscriber() {
+    use tracing_subscriber::EnvFilter;
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .try_init();
+}
@@ -0,0 +1,393 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use glium::uniforms::SamplerBehavior;
+use glium::uniforms::UniformValue;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::EngineError;
+use crate::render_state::RenderQueue;
+use crate::render_state::RenderState;
+use crate::shader::ShaderVariant;
+use crate::shader::DEFAULT_FRAGMENT_SHADER;
+use crate::shader::DEFAULT_VERTEX_SHADER;
+use crate::shader::PBR_FRAGMENT_SHADER;
+use crate::texture::texture_cache_key;
+use crate::texture::Texture;
+use crate::texture::TextureSettings;
+use crate::uniforms::UniformBag;
+
+// name under which every scene knows the default material
+pub const DEFAULT_MATERIAL: &str = "builtin/default";
+
+// metallic-roughness material, see assets/shaders/pbr_fragment.glsl
+pub const PBR_MATERIAL: &str = "builtin/pbr";
+
+// 1x1 textures, so that materials without textures can still sample them
+pub const WHITE_TEXTURE: &str = "builtin/white";
+pub const BLACK_TEXTURE: &str = "builtin/black";
+// normal pointing straight out of the surface
+pub const FLAT_NORMAL_TEXTURE: &str = "builtin/flat_normal";
+// magenta, what the textures which failed to load look like
+pub const MISSING_TEXTURE: &str = "builtin/missing";
+
+// a value which will be bound to the uniform of the same name
+// textures are referenced by their path and fetched from the scene's textures when drawing
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MaterialProperty {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    // rgba, bound as a vec4
+    Color([f32; 4]),
+    // column major, like the rest of the matrices we send to the shaders
+    Mat4([[f32; 4]; 4]),
+    Texture(String),
+}
+
+impl MaterialProperty {
+    // none if the property is a texture which hasn't been loaded
+    // the settings and the sampler are only used by textures, the settings find the texture the
+    // scene loaded and the sampler is how it is read
+    pub fn as_uniform_value<'a>(
+        &'a self,
+        textures: &'a HashMap<String, Texture>,
+        settings: &TextureSettings,
+        sampler: SamplerBehavior,
+    ) -> Option<UniformValue<'a>> {
+        let value = match self {
+            MaterialProperty::Float(x) => UniformValue::Float(*x),
+            MaterialProperty::Vec2(v) => UniformValue::Vec2(*v),
+            MaterialProperty::Vec3(v) => UniformValue::Vec3(*v),
+            MaterialProperty::Vec4(v) => UniformValue::Vec4(*v),
+            MaterialProperty::Color(c) => UniformValue::Vec4(*c),
+            MaterialProperty::Mat4(m) => UniformValue::Mat4(*m),
+            MaterialProperty::Texture(path) => {
+                textures.get(texture_cache_key(path, settings).as_ref())?.uniform_value(Some(sampler))
+            }
+        };
+        return Some(value);
+    }
+
+    pub fn texture_path(&self) -> Option<&str> {
+        match self {
+            MaterialProperty::Texture(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+// a shader and the values of its parameters, materials are stored in the scene and shared between
+// all the graphic components which reference them
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Material {
+    pub vertex_shader: String,
+    pub fragment_shader: String,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub defines: BTreeMap<String, String>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, MaterialProperty>,
+    // how the textures are sampled, by uniform name, the default settings are used for the others
+    #[serde(default)]
+    pub texture_settings: BTreeMap<String, TextureSettings>,
+    // queue, blending, culling and depth test
+    #[serde(default)]
+    pub render_state: RenderState,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        let mut material = Material::new(DEFAULT_VERTEX_SHADER, DEFAULT_FRAGMENT_SHADER);
+        material.set_property("brightness", MaterialProperty::Float(1.0));
+        material.set_property("tex", MaterialProperty::Texture(WHITE_TEXTURE.to_string()));
+        material.set_property("u_diffuse_color", MaterialProperty::Color([1.0, 1.0, 1.0, 1.0]));
+        material.set_property("u_specular_color", MaterialProperty::Color([0.5, 0.5, 0.5, 1.0]));
+        material.set_property("u_shininess", MaterialProperty::Float(32.0));
+        material.set_property("u_normal_map", MaterialProperty::Texture(FLAT_NORMAL_TEXTURE.to_string()));
+        material.set_property("u_normal_scale", MaterialProperty::Float(1.0));
+        // the colour map is srgb, like the albedo map of the pbr material
+        material.set_texture_settings("tex", TextureSettings::default().with_srgb(true));
+        material
+    }
+}
+
+impl Material {
+    // the maps default to textures which leave the factors unchanged
+    pub fn pbr() -> Self {
+        let mut material = Material::new(DEFAULT_VERTEX_SHADER, PBR_FRAGMENT_SHADER);
+        let texture = |path: &str| MaterialProperty::Texture(path.to_string());
+        material.set_property("u_albedo_map", texture(WHITE_TEXTURE));
+        material.set_property("u_metallic_roughness_map", texture(WHITE_TEXTURE));
+        material.set_property("u_normal_map", texture(FLAT_NORMAL_TEXTURE));
+        material.set_property("u_occlusion_map", texture(WHITE_TEXTURE));
+        material.set_property("u_emissive_map", texture(WHITE_TEXTURE));
+        material.set_property("u_albedo_color", MaterialProperty::Color([1.0, 1.0, 1.0, 1.0]));
+        material.set_property("u_metallic", MaterialProperty::Float(0.0));
+        material.set_property("u_roughness", MaterialProperty::Float(0.5));
+        material.set_property("u_emissive_color", MaterialProperty::Vec3([0.0, 0.0, 0.0]));
+        material.set_property("u_normal_scale", MaterialProperty::Float(1.0));
+        material.set_property("u_occlusion_strength", MaterialProperty::Float(1.0));
+        // the colour maps are srgb, as in gltf
+        let srgb = TextureSettings::default().with_srgb(true);
+        material.set_texture_settings("u_albedo_map", srgb);
+        material.set_texture_settings("u_emissive_map", srgb);
+        material
+    }
+
+    pub fn new(vertex_shader: &str, fragment_shader: &str) -> Self {
+        Material {
+            vertex_shader: vertex_shader.to_string(),
+            fragment_shader: fragment_shader.to_string(),
+            keywords: Vec::new(),
+            defines: BTreeMap::new(),
+            properties: BTreeMap::new(),
+            texture_settings: BTreeMap::new(),
+            render_state: RenderState::default(),
+        }
+    }
+
+    // the format is deduced from the extension, either .ron or .toml
+    pub fn load(path: &str) -> Result<Material, EngineError> {
+        let content = fs::read_to_string(path).map_err(|err| EngineError::io(path, err))?;
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("ron") => ron::from_str(&content).map_err(|err| EngineError::parse(path, err)),
+            Some("toml") => toml::from_str(&content).map_err(|err| EngineError::parse(path, err)),
+            _ => Err(EngineError::parse(path, "materials are either .ron or .toml files")),
+        }
+    }
+
+    // whether a material name designates a file we should load
+    pub fn is_material_file(name: &str) -> bool {
+        name.ends_with(".ron") || name.ends_with(".toml")
+    }
+
+    pub fn set_property(&mut self, name: &str, value: MaterialProperty) {
+        self.properties.insert(name.to_string(), value);
+    }
+
+    pub fn enable_keyword(&mut self, keyword: &str) {
+        if !self.keywords.iter().any(|k| k == keyword) {
+            self.keywords.push(keyword.to_string());
+        }
+    }
+
+    pub fn disable_keyword(&mut self, keyword: &str) {
+        self.keywords.retain(|k| k != keyword);
+    }
+
+    pub fn add_define(&mut self, name: &str, value: &str) {
+        self.defines.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn set_render_state(&mut self, render_state: RenderState) {
+        self.render_state = render_state;
+    }
+
+    // the state of the material, or the usual one of another queue when a graphic component
+    // moves it there, the culling and the alpha cutoff of the material are kept
+    pub fn render_state_for(&self, queue: Option<RenderQueue>) -> RenderState {
+        match queue {
+            Some(queue) if queue != self.render_state.queue => RenderState {
+                cull: self.render_state.cull,
+                alpha_cutoff: self.render_state.alpha_cutoff,
+                ..RenderState::for_queue(queue)
+            },
+            _ => self.render_state,
+        }
+    }
+
+    pub fn shader_variant(&self) -> ShaderVariant {
+        return self.shader_variant_for(&self.render_state);
+    }
+
+    // the alpha test queue needs the shaders to discard the pixels below the cutoff
+    pub fn shader_variant_for(&self, render_state: &RenderState) -> ShaderVariant {
+        let mut variant = ShaderVariant::new(&self.vertex_shader, &self.fragment_shader);
+        if render_state.queue == RenderQueue::AlphaTest {
+            variant = variant.with_keyword("ALPHA_TEST");
+        }
+        for keyword in &self.keywords {
+            variant = variant.with_keyword(keyword);
+        }
+        for (name, value) in &self.defines {
+            variant = variant.with_define(name, value);
+        }
+        return variant;
+    }
+
+    // every texture the material references, so that the scene can load them beforehand
+    pub fn texture_paths(&self) -> impl Iterator<Item = &str> {
+        self.properties.values().filter_map(|p| p.texture_path())
+    }
+
+    // uniform names and paths of the textures of the material
+    pub fn texture_bindings(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.properties
+            .iter()
+            .filter_map(|(name, p)| Some((name.as_str(), p.texture_path()?)))
+    }
+
+    pub fn set_texture_settings(&mut self, name: &str, settings: TextureSettings) {
+        self.texture_settings.insert(name.to_string(), settings);
+    }
+
+    // the settings of the texture bound to the uniform, the overrides come first
+    pub fn texture_settings_for(
+        &self,
+        name: &str,
+        overrides: &BTreeMap<String, TextureSettings>,
+    ) -> TextureSettings {
+        return overrides
+            .get(name)
+            .or_else(|| self.texture_settings.get(name))
+            .copied()
+            .unwrap_or_default();
+    }
+
+    // adds the properties to the uniforms, the overrides take precedence over the properties of
+    // the material
+    pub fn bind<'a>(
+        &'a self,
+        overrides: &'a BTreeMap<String, MaterialProperty>,
+        settings_overrides: &BTreeMap<String, TextureSettings>,
+        textures: &'a HashMap<String, Texture>,
+        uniforms: &mut UniformBag<'a>,
+    ) {
+        uniforms.set("u_alpha_cutoff", UniformValue::Float(self.render_state.alpha_cutoff));
+        for (name, property) in self.properties.iter().chain(overrides.iter()) {
+            let settings = self.texture_settings_for(name, settings_overrides);
+            if let Some(value) = property.as_uniform_value(textures, &settings, settings.sampler()) {
+                uniforms.set(name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glium::uniforms::Uniforms;
+
+    fn material_file(name: &str, content: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("sparkle_material_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        return path.to_string_lossy().to_string();
+    }
+
+    fn float_uniform(uniforms: &UniformBag, name: &str) -> Option<f32> {
+        let mut found = None;
+        uniforms.visit_values(|n, value| {
+            if let (true, UniformValue::Float(x)) = (n == name, value) {
+                found = Some(x);
+            }
+        });
+        return found;
+    }
+
+    fn check_loaded(material: &Material) {
+        assert_eq!(material.fragment_shader, "shaders/toon.glsl");
+        assert_eq!(material.keywords, vec!["RIM_LIGHT".to_string()]);
+        assert_eq!(material.defines.get("BANDS").map(String::as_str), Some("3"));
+        assert_eq!(material.properties.get("u_rim"), Some(&MaterialProperty::Float(0.5)));
+        assert_eq!(
+            material.properties.get("u_tint"),
+            Some(&MaterialProperty::Color([1.0, 0.5, 0.0, 1.0]))
+        );
+        assert_eq!(
+            material.properties.get("tex"),
+            Some(&MaterialProperty::Texture("bricks.png".to_string()))
+        );
+        assert_eq!(material.texture_paths().collect::<Vec<_>>(), vec!["bricks.png"]);
+    }
+
+    #[test]
+    fn ron_materials_have_typed_properties() {
+        let path = material_file(
+            "toon.ron",
+            r#"(
+                vertex_shader: "builtin/vertex_shader.glsl",
+                fragment_shader: "shaders/toon.glsl",
+                keywords: ["RIM_LIGHT"],
+                defines: { "BANDS": "3" },
+                properties: {
+                    "u_rim": Float(0.5),
+                    "u_tint": Color((1.0, 0.5, 0.0, 1.0)),
+                    "tex": Texture("bricks.png"),
+                },
+            )"#,
+        );
+        check_loaded(&Material::load(&path).unwrap());
+    }
+
+    #[test]
+    fn toml_materials_have_typed_properties() {
+        let path = material_file(
+            "toon.toml",
+            r#"
+                vertex_shader = "builtin/vertex_shader.glsl"
+                fragment_shader = "shaders/toon.glsl"
+                keywords = ["RIM_LIGHT"]
+
+                [defines]
+                BANDS = "3"
+
+                [properties]
+                u_rim = { Float = 0.5 }
+                u_tint = { Color = [1.0, 0.5, 0.0, 1.0] }
+                tex = { Texture = "bricks.png" }
+            "#,
+        );
+        check_loaded(&Material::load(&path).unwrap());
+    }
+
+    #[test]
+    fn invalid_materials_are_parse_errors() {
+        let path = material_file("broken.toml", "vertex_shader = 3");
+        assert!(matches!(Material::load(&path), Err(EngineError::Parse { .. })));
+        let path = material_file("toon.json", "{}");
+        assert!(matches!(Material::load(&path), Err(EngineError::Parse { .. })));
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_the_material() {
+        let mut material = Material::default();
+        material.set_property("u_shininess", MaterialProperty::Float(32.0));
+        material.set_property("u_rim", MaterialProperty::Float(0.5));
+        let mut overrides = BTreeMap::new();
+        overrides.insert("u_shininess".to_string(), MaterialProperty::Float(4.0));
+        let textures = HashMap::new();
+        let mut uniforms = UniformBag::new();
+        material.bind(&overrides, &BTreeMap::new(), &textures, &mut uniforms);
+        assert_eq!(float_uniform(&uniforms, "u_shininess"), Some(4.0));
+        assert_eq!(float_uniform(&uniforms, "u_rim"), Some(0.5));
+        // the textures which aren't loaded are left out rather than bound to nothing
+        assert!(!uniforms.contains("tex"));
+    }
+
+    #[test]
+    fn colour_maps_are_srgb_and_data_maps_linear() {
+        let none = BTreeMap::new();
+        let material = Material::default();
+        assert!(material.texture_settings_for("tex", &none).srgb);
+        assert!(!material.texture_settings_for("u_normal_map", &none).srgb);
+        let pbr = Material::pbr();
+        assert!(pbr.texture_settings_for("u_albedo_map", &none).srgb);
+        assert!(!pbr.texture_settings_for("u_metallic_roughness_map", &none).srgb);
+        // unless the graphic component says otherwise
+        let mut overrides = BTreeMap::new();
+        overrides.insert("tex".to_string(), TextureSettings::default());
+        assert!(!material.texture_settings_for("tex", &overrides).srgb);
+    }
+}
@@ -0,0 +1,611 @@
+#![allow(dead_code)]
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use cgmath::InnerSpace;
+use cgmath::Vector3;
+
+use glium::backend::Facade;
+use glium::vertex::AttributeType;
+use glium::vertex::BufferCreationError;
+use glium::vertex::VertexBufferAny;
+use glium::vertex::VertexFormat;
+use glium::VertexBuffer;
+
+use tracing::warn;
+
+use crate::logging::ASSETS;
+use crate::transform::v3_normalised;
+
+fn unit_or_up(normal: Vector3<f32>) -> [f32; 3] {
+    if normal.magnitude2() > 0.0 {
+        return v3_normalised(normal).into();
+    }
+    return [0.0, 1.0, 0.0];
+}
+
+// the attributes a mesh can hold, the shaders access them through the names given by `name`
+// (`position`, `normal`, `tex_coord`, `tex_coord_1`..., `color`, `tangent`, `joints`, `weights`)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VertexAttribute {
+    Position,
+    Normal,
+    // uv set, 0 being the main one
+    TexCoord(usize),
+    Color,
+    // the w component is the sign of the bitangent, bitangent = cross(normal, tangent.xyz) * w
+    Tangent,
+    // indices of the four bones influencing the vertex, as an uvec4
+    Joints,
+    Weights,
+}
+
+impl VertexAttribute {
+    pub fn name(self) -> Cow<'static, str> {
+        match self {
+            VertexAttribute::Position => Cow::Borrowed("position"),
+            VertexAttribute::Normal => Cow::Borrowed("normal"),
+            VertexAttribute::TexCoord(0) => Cow::Borrowed("tex_coord"),
+            VertexAttribute::TexCoord(set) => Cow::Owned(format!("tex_coord_{}", set)),
+            VertexAttribute::Color => Cow::Borrowed("color"),
+            VertexAttribute::Tangent => Cow::Borrowed("tangent"),
+            VertexAttribute::Joints => Cow::Borrowed("joints"),
+            VertexAttribute::Weights => Cow::Borrowed("weights"),
+        }
+    }
+
+    // None for the attributes which are not filled by meshes, like the builtin gl_ ones
+    pub fn from_name(name: &str) -> Option<Self> {
+        let attribute = match name {
+            "position" => VertexAttribute::Position,
+            "normal" => VertexAttribute::Normal,
+            "tex_coord" => VertexAttribute::TexCoord(0),
+            "color" => VertexAttribute::Color,
+            "tangent" => VertexAttribute::Tangent,
+            "joints" => VertexAttribute::Joints,
+            "weights" => VertexAttribute::Weights,
+            _ => {
+                let set = name.strip_prefix("tex_coord_")?.parse().ok()?;
+                VertexAttribute::TexCoord(set)
+            }
+        };
+        return Some(attribute);
+    }
+
+    fn attribute_type(self) -> AttributeType {
+        match self {
+            VertexAttribute::Position | VertexAttribute::Normal => AttributeType::F32F32F32,
+            VertexAttribute::TexCoord(_) => AttributeType::F32F32,
+            VertexAttribute::Joints => AttributeType::U16U16U16U16,
+            VertexAttribute::Color | VertexAttribute::Tangent | VertexAttribute::Weights => {
+                AttributeType::F32F32F32F32
+            }
+        }
+    }
+
+    fn size(self) -> usize {
+        return self.attribute_type().get_size_bytes();
+    }
+}
+
+// how the normals are computed for the meshes which have none
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NormalMode {
+    // faceted look, every triangle gets its own vertices
+    Flat,
+    // the normals are averaged between the faces meeting at a vertex, unless the angle between
+    // them is greater than the crease angle (in radians), in which case the edge stays sharp
+    Smooth { crease_angle: f32 },
+}
+
+impl Default for NormalMode {
+    fn default() -> Self {
+        NormalMode::Smooth {
+            crease_angle: 60.0f32.to_radians(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+    IncompleteTriangle { index_count: usize },
+    IndexOutOfRange { index: u32, vertex_count: usize },
+    AttributeLength { attribute: VertexAttribute, len: usize, vertex_count: usize },
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::IncompleteTriangle { index_count } => {
+                write!(f, "{} indices do not make whole triangles", index_count)
+            }
+            MeshError::IndexOutOfRange { index, vertex_count } => {
+                write!(f, "index {} is out of range, the mesh has {} vertices", index, vertex_count)
+            }
+            MeshError::AttributeLength {
+                attribute,
+                len,
+                vertex_count,
+            } => write!(
+                f,
+                "the mesh has {} vertices but {} values of {}",
+                vertex_count,
+                len,
+                attribute.name()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+// glium wants vertex formats to live forever, so every layout is leaked once and reused for all
+// the meshes which share it
+static VERTEX_FORMATS: Mutex<Vec<(Vec<VertexAttribute>, VertexFormat)>> = Mutex::new(Vec::new());
+
+fn vertex_format(layout: &[VertexAttribute]) -> VertexFormat {
+    let mut formats = VERTEX_FORMATS.lock().unwrap();
+    if let Some((_, format)) = formats.iter().find(|(l, _)| l == layout) {
+        return format;
+    }
+    let mut offset = 0;
+    let bindings: Vec<_> = layout
+        .iter()
+        .map(|attribute| {
+            let binding = (attribute.name(), offset, -1, attribute.attribute_type(), false);
+            offset += attribute.size();
+            binding
+        })
+        .collect();
+    let format: VertexFormat = Box::leak(bindings.into_boxed_slice());
+    formats.push((layout.to_vec(), format));
+    return format;
+}
+
+// geometry on the cpu side, every attribute but the positions is optional, the ones missing are
+// generated (or given neutral values) when a shader needs them
+// meshes are triangle lists
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub tex_coords: Vec<Vec<[f32; 2]>>,
+    pub colors: Option<Vec<[f32; 4]>>,
+    pub tangents: Option<Vec<[f32; 4]>>,
+    pub joints: Option<Vec<[u16; 4]>>,
+    pub weights: Option<Vec<[f32; 4]>>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn from_positions_indices(positions: Vec<[f32; 3]>, indices: Vec<u32>) -> Self {
+        Mesh {
+            positions,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_normals(mut self, normals: Vec<[f32; 3]>) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    // adds the next uv set, the first call sets `tex_coord`, the second `tex_coord_1`...
+    pub fn with_tex_coords(mut self, tex_coords: Vec<[f32; 2]>) -> Self {
+        self.tex_coords.push(tex_coords);
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Vec<[f32; 4]>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    pub fn with_tangents(mut self, tangents: Vec<[f32; 4]>) -> Self {
+        self.tangents = Some(tangents);
+        self
+    }
+
+    pub fn with_skin(mut self, joints: Vec<[u16; 4]>, weights: Vec<[f32; 4]>) -> Self {
+        self.joints = Some(joints);
+        self.weights = Some(weights);
+        self
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        return self.positions.len();
+    }
+
+    pub fn has_attribute(&self, attribute: VertexAttribute) -> bool {
+        match attribute {
+            VertexAttribute::Position => true,
+            VertexAttribute::Normal => self.normals.is_some(),
+            VertexAttribute::TexCoord(set) => set < self.tex_coords.len(),
+            VertexAttribute::Color => self.colors.is_some(),
+            VertexAttribute::Tangent => self.tangents.is_some(),
+            VertexAttribute::Joints => self.joints.is_some(),
+            VertexAttribute::Weights => self.weights.is_some(),
+        }
+    }
+
+    // the attributes actually stored in the mesh
+    pub fn attributes(&self) -> Vec<VertexAttribute> {
+        let mut attributes = vec![VertexAttribute::Position];
+        if self.normals.is_some() {
+            attributes.push(VertexAttribute::Normal);
+        }
+        attributes.extend((0..self.tex_coords.len()).map(VertexAttribute::TexCoord));
+        if self.colors.is_some() {
+            attributes.push(VertexAttribute::Color);
+        }
+        if self.tangents.is_some() {
+            attributes.push(VertexAttribute::Tangent);
+        }
+        if self.joints.is_some() {
+            attributes.push(VertexAttribute::Joints);
+        }
+        if self.weights.is_some() {
+            attributes.push(VertexAttribute::Weights);
+        }
+        return attributes;
+    }
+
+    // smooth normals shared by the vertices, each face contributing proportionally to the angle
+    // of its corner, so that the way the faces are split into triangles doesn't matter
+    pub fn generate_normals(&mut self) {
+        let mut normals = vec![Vector3::new(0.0f32, 0.0, 0.0); self.positions.len()];
+        for (face, triangle) in self.indices.chunks_exact(3).enumerate() {
+            let (face_normal, angles) = self.face_normal_and_angles(face);
+            for (index, angle) in triangle.iter().zip(angles) {
+                normals[*index as usize] += face_normal * angle;
+            }
+        }
+        self.normals = Some(normals.into_iter().map(unit_or_up).collect());
+    }
+
+    // replaces the normals, the vertices are split where they need more than one normal
+    pub fn generate_normals_with(&mut self, mode: NormalMode) {
+        match mode {
+            NormalMode::Flat => {
+                // every corner gets its own vertex
+                let corners = self.indices.clone();
+                let mut mesh = self.remap(&corners);
+                mesh.indices = (0..corners.len() as u32).collect();
+                let normals = (0..corners.len() / 3)
+                    .flat_map(|face| [mesh.face_normal_and_angles(face).0.into(); 3])
+                    .collect();
+                mesh.normals = Some(normals);
+                *self = mesh;
+            }
+            NormalMode::Smooth { crease_angle } => self.generate_creased_normals(crease_angle.cos()),
+        }
+    }
+
+    fn generate_creased_normals(&mut self, min_cos: f32) {
+        let faces: Vec<_> = (0..self.indices.len() / 3)
+            .map(|face| self.face_normal_and_angles(face))
+            .collect();
+        // the faces are gathered by position rather than by vertex, vertices split along uv seams
+        // still get the same normal
+        let key = |p: [f32; 3]| p.map(f32::to_bits);
+        let mut faces_at: HashMap<[u32; 3], Vec<(usize, f32)>> = HashMap::new();
+        for (corner, index) in self.indices.iter().enumerate() {
+            let face = corner / 3;
+            faces_at
+                .entry(key(self.positions[*index as usize]))
+                .or_default()
+                .push((face, faces[face].1[corner % 3]));
+        }
+
+        let mut sources = Vec::new();
+        let mut normals = Vec::new();
+        let mut vertices = HashMap::new();
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for (corner, index) in self.indices.iter().enumerate() {
+            let face_normal = faces[corner / 3].0;
+            // only the faces on the same side of the crease are smoothed together
+            let normal = faces_at[&key(self.positions[*index as usize])]
+                .iter()
+                .filter(|(other, _)| faces[*other].0.dot(face_normal) >= min_cos)
+                .fold(Vector3::new(0.0, 0.0, 0.0), |sum, (other, angle)| sum + faces[*other].0 * *angle);
+            let normal: [f32; 3] = unit_or_up(normal);
+            let vertex = *vertices.entry((*index, key(normal))).or_insert_with(|| {
+                sources.push(*index);
+                normals.push(normal);
+                sources.len() as u32 - 1
+            });
+            indices.push(vertex);
+        }
+        let mut mesh = self.remap(&sources);
+        mesh.indices = indices;
+        mesh.normals = Some(normals);
+        *self = mesh;
+    }
+
+    // unit normal of the face and the angles of its three corners
+    fn face_normal_and_angles(&self, face: usize) -> (Vector3<f32>, [f32; 3]) {
+        let triangle = &self.indices[face * 3..face * 3 + 3];
+        let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(self.positions[triangle[i] as usize]));
+        let angle = |from: Vector3<f32>, u: Vector3<f32>, v: Vector3<f32>| {
+            let (u, v) = (u - from, v - from);
+            if u.magnitude2() == 0.0 || v.magnitude2() == 0.0 {
+                return 0.0;
+            }
+            return u.angle(v).0;
+        };
+        let normal = Vector3::cross(b - a, c - a);
+        let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { normal };
+        return (normal, [angle(a, b, c), angle(b, c, a), angle(c, a, b)]);
+    }
+
+    // a new mesh whose vertex i is the vertex sources[i] of this one, without indices
+    fn remap(&self, sources: &[u32]) -> Mesh {
+        fn pick<T: Copy>(values: &[T], sources: &[u32]) -> Vec<T> {
+            return sources.iter().map(|i| values[*i as usize]).collect();
+        }
+        Mesh {
+            positions: pick(&self.positions, sources),
+            normals: self.normals.as_ref().map(|n| pick(n, sources)),
+            tex_coords: self.tex_coords.iter().map(|t| pick(t, sources)).collect(),
+            colors: self.colors.as_ref().map(|c| pick(c, sources)),
+            tangents: self.tangents.as_ref().map(|t| pick(t, sources)),
+            joints: self.joints.as_ref().map(|j| pick(j, sources)),
+            weights: self.weights.as_ref().map(|w| pick(w, sources)),
+            indices: Vec::new(),
+        }
+    }
+
+    // checks that the indices form triangles of existing vertices and that every attribute has
+    // a value per vertex
+    pub fn validate(&self) -> Result<(), MeshError> {
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(MeshError::IncompleteTriangle {
+                index_count: self.indices.len(),
+            });
+        }
+        let vertex_count = self.vertex_count();
+        if let Some(index) = self.indices.iter().find(|i| **i as usize >= vertex_count) {
+            return Err(MeshError::IndexOutOfRange {
+                index: *index,
+                vertex_count,
+            });
+        }
+        let lengths = [
+            (VertexAttribute::Normal, self.normals.as_ref().map(|n| n.len())),
+            (VertexAttribute::Color, self.colors.as_ref().map(|c| c.len())),
+            (VertexAttribute::Tangent, self.tangents.as_ref().map(|t| t.len())),
+            (VertexAttribute::Joints, self.joints.as_ref().map(|j| j.len())),
+            (VertexAttribute::Weights, self.weights.as_ref().map(|w| w.len())),
+        ]
+        .into_iter()
+        .chain(self.tex_coords.iter().enumerate().map(|(set, t)| (VertexAttribute::TexCoord(set), Some(t.len()))));
+        for (attribute, len) in lengths {
+            if let Some(len) = len.filter(|len| *len != vertex_count) {
+                return Err(MeshError::AttributeLength {
+                    attribute,
+                    len,
+                    vertex_count,
+                });
+            }
+        }
+        return Ok(());
+    }
+
+    // tangents from the main uv set, the normals are generated first if needed
+    pub fn generate_tangents(&mut self) {
+        if self.normals.is_none() {
+            self.generate_normals();
+        }
+        let normals = self.normals.as_ref().unwrap();
+        let tex_coords = self.tex_coords.first().map(|t| t.as_slice());
+        self.tangents = Some(generate_tangents(&self.positions, normals, tex_coords, &self.indices));
+    }
+
+    // fills in the attributes of the layout which the mesh lacks
+    fn complete(&self, layout: &[VertexAttribute]) -> Mesh {
+        let mut mesh = self.clone();
+        let count = mesh.vertex_count();
+        for attribute in layout {
+            if mesh.has_attribute(*attribute) {
+                continue;
+            }
+            match attribute {
+                VertexAttribute::Position => (),
+                VertexAttribute::Normal => mesh.generate_normals(),
+                // uvs can't be made up, every vertex maps to the corner of the textures
+                VertexAttribute::TexCoord(set) => {
+                    mesh.tex_coords.resize(set + 1, vec![[0.0, 0.0]; count]);
+                }
+                VertexAttribute::Color => mesh.colors = Some(vec![[1.0; 4]; count]),
+                VertexAttribute::Tangent => mesh.generate_tangents(),
+                VertexAttribute::Joints => mesh.joints = Some(vec![[0; 4]; count]),
+                VertexAttribute::Weights => mesh.weights = Some(vec![[1.0, 0.0, 0.0, 0.0]; count]),
+            }
+        }
+        return mesh;
+    }
+
+    // interleaved vertices, in the order of the layout
+    fn vertex_data(&self, layout: &[VertexAttribute]) -> Vec<u8> {
+        let stride: usize = layout.iter().map(|a| a.size()).sum();
+        let mut data = Vec::with_capacity(stride * self.vertex_count());
+        let floats = |data: &mut Vec<u8>, values: &[f32]| {
+            values.iter().for_each(|v| data.extend_from_slice(&v.to_ne_bytes()));
+        };
+        for i in 0..self.vertex_count() {
+            for attribute in layout {
+                match attribute {
+                    VertexAttribute::Position => floats(&mut data, &self.positions[i]),
+                    VertexAttribute::Normal => floats(&mut data, &self.normals.as_ref().unwrap()[i]),
+                    VertexAttribute::TexCoord(set) => floats(&mut data, &self.tex_coords[*set][i]),
+                    VertexAttribute::Color => floats(&mut data, &self.colors.as_ref().unwrap()[i]),
+                    VertexAttribute::Tangent => floats(&mut data, &self.tangents.as_ref().unwrap()[i]),
+                    VertexAttribute::Joints => self.joints.as_ref().unwrap()[i]
+                        .iter()
+                        .for_each(|j| data.extend_from_slice(&j.to_ne_bytes())),
+                    VertexAttribute::Weights => floats(&mut data, &self.weights.as_ref().unwrap()[i]),
+                }
+            }
+        }
+        return data;
+    }
+
+    // uploads the attributes of the mesh along with the required ones, generating those missing
+    pub fn vertex_buffer<F: Facade>(
+        &self,
+        facade: &F,
+        required: &[VertexAttribute],
+    ) -> Result<(VertexBufferAny, Vec<VertexAttribute>), BufferCreationError> {
+        let mut layout = self.attributes();
+        layout.extend(required.iter().filter(|a| !self.has_attribute(**a)));
+        layout.sort();
+        layout.dedup();
+
+        let data = self.complete(&layout).vertex_data(&layout);
+        let stride = layout.iter().map(|a| a.size()).sum();
+        // the format describes the interleaved data exactly
+        let buffer = unsafe { VertexBuffer::new_raw(facade, &data, vertex_format(&layout), stride) }?;
+        return Ok((buffer.into(), layout));
+    }
+}
+
+// indexed triangles as seen by the mikktspace algorithm
+struct TangentGeometry<'a> {
+    positions: &'a [[f32; 3]],
+    normals: &'a [[f32; 3]],
+    tex_coords: &'a [[f32; 2]],
+    indices: &'a [u32],
+    tangents: Vec<[f32; 4]>,
+}
+
+impl TangentGeometry<'_> {
+    fn index(&self, face: usize, vert: usize) -> usize {
+        return self.indices[face * 3 + vert] as usize;
+    }
+}
+
+impl bevy_mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        return self.indices.len() / 3;
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        return 3;
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        return self.positions[self.index(face, vert)];
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        return self.normals[self.index(face, vert)];
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        return self.tex_coords[self.index(face, vert)];
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let index = self.index(face, vert);
+        self.tangents[index] = tangent;
+    }
+}
+
+// any unit vector orthogonal to the normal, for meshes which have no texture coordinates to
+// derive the tangents from
+fn arbitrary_tangent(normal: [f32; 3]) -> [f32; 4] {
+    let n = Vector3::from(normal);
+    let axis = if n.x.abs() < 0.9 {
+        Vector3::new(1.0, 0.0, 0.0)
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    };
+    let tangent = v3_normalised(Vector3::cross(axis, n));
+    return [tangent.x, tangent.y, tangent.z, 1.0];
+}
+
+// per vertex tangents, computed with mikktspace so that they match the ones normal maps are
+// usually baked with
+pub fn generate_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    tex_coords: Option<&[[f32; 2]]>,
+    indices: &[u32],
+) -> Vec<[f32; 4]> {
+    let fallback = || normals.iter().map(|n| arbitrary_tangent(*n)).collect();
+    let Some(tex_coords) = tex_coords else {
+        return fallback();
+    };
+    let mut geometry = TangentGeometry {
+        positions,
+        normals,
+        tex_coords,
+        indices,
+        tangents: fallback(),
+    };
+    if !bevy_mikktspace::generate_tangents(&mut geometry) {
+        warn!(target: ASSETS, "could not generate the tangents of a mesh");
+    }
+    return geometry.tangents;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::primitives::cube;
+
+    fn cube_without_normals() -> Mesh {
+        let mut mesh = cube(2.0);
+        mesh.normals = None;
+        return mesh;
+    }
+
+    #[test]
+    fn flat_normals_split_every_corner() {
+        let mut mesh = cube_without_normals();
+        mesh.generate_normals_with(NormalMode::Flat);
+        assert_eq!(mesh.vertex_count(), 36);
+        assert!(mesh.validate().is_ok());
+        for (position, normal) in mesh.positions.iter().zip(mesh.normals.as_ref().unwrap()) {
+            // the normal of a face of the cube is the axis along which the position is maximal
+            let normal = Vector3::from(*normal);
+            assert!((Vector3::from(*position).dot(normal) - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn crease_angle_keeps_sharp_edges() {
+        let mut mesh = cube_without_normals();
+        mesh.generate_normals_with(NormalMode::default());
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_eq!(mesh.normals, cube(2.0).normals);
+    }
+
+    #[test]
+    fn smooth_normals_are_shared_across_faces() {
+        let mut mesh = cube_without_normals();
+        mesh.generate_normals_with(NormalMode::Smooth {
+            crease_angle: 100.0f32.to_radians(),
+        });
+        for (position, normal) in mesh.positions.iter().zip(mesh.normals.as_ref().unwrap()) {
+            let diagonal = Vector3::from(*position).normalize();
+            assert!(Vector3::from(*normal).dot(diagonal) > 0.9999);
+        }
+    }
+
+    #[test]
+    fn invalid_indices_are_reported() {
+        let positions = vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let mesh = Mesh::from_positions_indices(positions.clone(), vec![0, 1, 3]);
+        assert!(matches!(mesh.validate(), Err(MeshError::IndexOutOfRange { index: 3, .. })));
+        let mesh = Mesh::from_positions_indices(positions.clone(), vec![0, 1]);
+        assert!(matches!(mesh.validate(), Err(MeshError::IncompleteTriangle { index_count: 2 })));
+        let mesh = Mesh::from_positions_indices(positions, vec![0, 1, 2]).with_tex_coords(vec![]);
+        assert!(matches!(mesh.validate(), Err(MeshError::AttributeLength { .. })));
+    }
+}
@@ -0,0 +1,491 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use cgmath::Matrix4;
+use cgmath::SquareMatrix;
+
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::NoIndices;
+use glium::index::PrimitiveType;
+use glium::texture::DepthTexture2d;
+use glium::texture::MipmapsOption;
+use glium::texture::Texture2d;
+use glium::texture::UncompressedFloatFormat;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
+use glium::uniforms::SamplerBehavior;
+use glium::uniforms::SamplerWrapFunction;
+use glium::uniforms::UniformValue;
+use glium::vertex::EmptyVertexAttributes;
+use glium::Blend;
+use glium::BlendingFunction;
+use glium::LinearBlendingFactor;
+use glium::Program;
+use glium::Surface;
+
+use crate::error::EngineError;
+use crate::material::MaterialProperty;
+use crate::shader::ShaderVariant;
+use crate::texture::Texture;
+use crate::texture::TextureSettings;
+use crate::tonemapping::tonemap;
+use crate::tonemapping::tonemap_variant;
+use crate::tonemapping::Tonemapping;
+use crate::tonemapping::FULLSCREEN_VERTEX_SHADER;
+use crate::uniforms::UniformBag;
+
+pub const BLOOM_FRAGMENT_SHADER: &str = "builtin/bloom_fragment.glsl";
+pub const SSAO_FRAGMENT_SHADER: &str = "builtin/ssao_fragment.glsl";
+pub const FXAA_FRAGMENT_SHADER: &str = "builtin/fxaa_fragment.glsl";
+pub const COLOR_GRADING_FRAGMENT_SHADER: &str = "builtin/color_grading_fragment.glsl";
+pub const VIGNETTE_FRAGMENT_SHADER: &str = "builtin/vignette_fragment.glsl";
+pub const GAMMA_FRAGMENT_SHADER: &str = "builtin/gamma_fragment.glsl";
+
+// the bloom never goes below this size, the smaller levels would only blur a few pixels more
+const BLOOM_MIN_SIZE: u32 = 4;
+
+// a full screen pass written by the game, its fragment shader goes through the same loader as
+// the materials so it can include the builtin files
+// it reads the image from u_source, the depth of the scene from u_depth and the size of a pixel
+// from u_texel_size, with v_tex_coord coming from assets/shaders/fullscreen_vertex.glsl
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomEffect {
+    pub fragment_shader: String,
+    pub properties: BTreeMap<String, MaterialProperty>,
+    // runs on the hdr colours, before the tonemapping
+    pub hdr: bool,
+}
+
+impl CustomEffect {
+    pub fn new(fragment_shader: &str) -> Self {
+        return CustomEffect {
+            fragment_shader: fragment_shader.to_string(),
+            properties: BTreeMap::new(),
+            hdr: false,
+        };
+    }
+
+    pub fn with_property(mut self, name: &str, value: MaterialProperty) -> Self {
+        self.properties.insert(name.to_string(), value);
+        self
+    }
+}
+
+// a full screen pass run on the image of a camera before it is presented
+// the occlusion, the bloom and the hdr custom effects run before the tonemapping, the others
+// after it, each group in the order of the camera's list
+#[derive(Clone, Debug, PartialEq)]
+pub enum PostEffect {
+    // darkens the creases, the radius is in world units
+    Ssao { radius: f32, intensity: f32, bias: f32 },
+    // the pixels brighter than the threshold bleed on their neighbours, over `levels` halvings
+    // of the resolution
+    Bloom { threshold: f32, knee: f32, intensity: f32, radius: f32, levels: u32 },
+    // remaps the colours with a lookup table, see assets/shaders/color_grading_fragment.glsl
+    ColorGrading { lut: String, contribution: f32 },
+    Vignette { intensity: f32, smoothness: f32 },
+    Fxaa,
+    Gamma(f32),
+    Custom(CustomEffect),
+}
+
+impl PostEffect {
+    pub fn ssao() -> Self {
+        return PostEffect::Ssao {
+            radius: 0.5,
+            intensity: 1.0,
+            bias: 0.025,
+        };
+    }
+
+    pub fn bloom() -> Self {
+        return PostEffect::Bloom {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.8,
+            radius: 1.0,
+            levels: 6,
+        };
+    }
+
+    pub fn color_grading(lut: &str) -> Self {
+        return PostEffect::ColorGrading {
+            lut: lut.to_string(),
+            contribution: 1.0,
+        };
+    }
+
+    pub fn vignette() -> Self {
+        return PostEffect::Vignette {
+            intensity: 0.4,
+            smoothness: 0.6,
+        };
+    }
+
+    // whether the effect runs before the tonemapping
+    pub fn is_hdr(&self) -> bool {
+        match self {
+            PostEffect::Ssao { .. } | PostEffect::Bloom { .. } => true,
+            PostEffect::Custom(custom) => custom.hdr,
+            _ => false,
+        }
+    }
+
+    pub fn shader_variant(&self) -> ShaderVariant {
+        let fragment_shader = match self {
+            PostEffect::Ssao { .. } => SSAO_FRAGMENT_SHADER,
+            PostEffect::Bloom { .. } => BLOOM_FRAGMENT_SHADER,
+            PostEffect::ColorGrading { .. } => COLOR_GRADING_FRAGMENT_SHADER,
+            PostEffect::Vignette { .. } => VIGNETTE_FRAGMENT_SHADER,
+            PostEffect::Fxaa => FXAA_FRAGMENT_SHADER,
+            PostEffect::Gamma(_) => GAMMA_FRAGMENT_SHADER,
+            PostEffect::Custom(custom) => &custom.fragment_shader,
+        };
+        return ShaderVariant::new(FULLSCREEN_VERTEX_SHADER, fragment_shader);
+    }
+
+    // the programs of the builtin effects, they are compiled with the scene
+    pub fn builtin_variants() -> Vec<ShaderVariant> {
+        let effects = [
+            PostEffect::ssao(),
+            PostEffect::bloom(),
+            PostEffect::color_grading(""),
+            PostEffect::vignette(),
+            PostEffect::Fxaa,
+            PostEffect::Gamma(2.2),
+        ];
+        return effects.iter().map(|effect| effect.shader_variant()).collect();
+    }
+
+    // the textures the scene must load for the effect
+    pub fn texture_paths(&self) -> Vec<&str> {
+        match self {
+            PostEffect::ColorGrading { lut, .. } => vec![lut.as_str()],
+            PostEffect::Custom(custom) => custom.properties.values().filter_map(|p| p.texture_path()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn bloom_levels(&self) -> u32 {
+        match self {
+            PostEffect::Bloom { levels, .. } => *levels,
+            _ => 0,
+        }
+    }
+}
+
+// the textures the passes are drawn into, every pass reads the output of the previous one
+pub struct PostTargets {
+    swap: [Texture2d; 2],
+    // each half the size of the previous one
+    bloom: Vec<Texture2d>,
+}
+
+fn color_texture<F: Facade>(facade: &F, width: u32, height: u32) -> Result<Texture2d, EngineError> {
+    return Texture2d::empty_with_format(
+        facade,
+        UncompressedFloatFormat::F16F16F16F16,
+        MipmapsOption::NoMipmap,
+        width,
+        height,
+    )
+    .map_err(|err| EngineError::upload("post processing target", err));
+}
+
+impl PostTargets {
+    pub fn new<F: Facade>(facade: &F, width: u32, height: u32) -> Result<Self, EngineError> {
+        return Ok(PostTargets {
+            swap: [color_texture(facade, width, height)?, color_texture(facade, width, height)?],
+            bloom: Vec::new(),
+        });
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        return self.swap[0].dimensions();
+    }
+
+    fn ensure_bloom_levels<F: Facade>(&mut self, facade: &F, count: u32) -> Result<(), EngineError> {
+        let (mut width, mut height) = self.dimensions();
+        for level in 0..count as usize {
+            width /= 2;
+            height /= 2;
+            if width.min(height) < BLOOM_MIN_SIZE {
+                break;
+            }
+            if level >= self.bloom.len() {
+                self.bloom.push(color_texture(facade, width, height)?);
+            }
+        }
+        return Ok(());
+    }
+}
+
+// what the passes may read besides the image
+pub struct PostContext<'a> {
+    pub programs: &'a HashMap<u64, Program>,
+    pub textures: &'a HashMap<String, Texture>,
+    pub depth: &'a DepthTexture2d,
+    pub projection: [[f32; 4]; 4],
+    // none when the camera isn't hdr
+    pub tonemapping: Option<(Tonemapping, f32)>,
+}
+
+enum Pass<'a> {
+    Effect(&'a PostEffect, &'a Program),
+    // also copies the image to the surface when nothing else can
+    Tonemap(&'a Program, Tonemapping, f32),
+}
+
+fn sampler() -> SamplerBehavior {
+    SamplerBehavior {
+        wrap_function: (
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+        ),
+        minify_filter: MinifySamplerFilter::Linear,
+        magnify_filter: MagnifySamplerFilter::Linear,
+        ..Default::default()
+    }
+}
+
+fn texel_size(texture: &Texture2d) -> [f32; 2] {
+    let (width, height) = texture.dimensions();
+    return [1.0 / width as f32, 1.0 / height as f32];
+}
+
+fn framebuffer<'a, F: Facade>(facade: &F, texture: &'a Texture2d) -> Result<SimpleFrameBuffer<'a>, EngineError> {
+    return SimpleFrameBuffer::new(facade, texture).map_err(|err| EngineError::upload("post processing target", err));
+}
+
+fn draw_fullscreen<S: Surface>(
+    surface: &mut S,
+    program: &Program,
+    uniforms: &UniformBag,
+    blend: Blend,
+) -> Result<(), EngineError> {
+    let params = glium::DrawParameters {
+        blend,
+        ..Default::default()
+    };
+    surface.draw(
+        EmptyVertexAttributes { len: 3 },
+        NoIndices(PrimitiveType::TrianglesList),
+        program,
+        uniforms,
+        &params,
+    )?;
+    return Ok(());
+}
+
+fn additive() -> Blend {
+    let add = BlendingFunction::Addition {
+        source: LinearBlendingFactor::One,
+        destination: LinearBlendingFactor::One,
+    };
+    return Blend {
+        color: add,
+        alpha: add,
+        constant_value: (0.0, 0.0, 0.0, 0.0),
+    };
+}
+
+// runs the effects on the image of the scene and draws the result on the surface, the effects
+// whose program couldn't be compiled are skipped
+pub fn run_post_processing<F: Facade, S: Surface>(
+    facade: &F,
+    surface: &mut S,
+    source: &Texture2d,
+    effects: &[PostEffect],
+    targets: &mut PostTargets,
+    context: &PostContext,
+) -> Result<(), EngineError> {
+    let bloom_levels = effects.iter().map(|effect| effect.bloom_levels()).max().unwrap_or(0);
+    targets.ensure_bloom_levels(facade, bloom_levels)?;
+
+    let program = |effect: &PostEffect| context.programs.get(&effect.shader_variant().cache_key());
+    let tonemap_program = context.programs.get(&tonemap_variant().cache_key());
+    let mut passes = Vec::new();
+    for effect in effects.iter().filter(|effect| effect.is_hdr()) {
+        if let Some(program) = program(effect) {
+            passes.push(Pass::Effect(effect, program));
+        }
+    }
+    if let (Some((tonemapping, exposure)), Some(program)) = (context.tonemapping, tonemap_program) {
+        passes.push(Pass::Tonemap(program, tonemapping, exposure));
+    }
+    for effect in effects.iter().filter(|effect| !effect.is_hdr()) {
+        if let Some(program) = program(effect) {
+            passes.push(Pass::Effect(effect, program));
+        }
+    }
+    if passes.is_empty() {
+        let Some(program) = tonemap_program else {
+            return Ok(());
+        };
+        passes.push(Pass::Tonemap(program, Tonemapping::None, 1.0));
+    }
+
+    // the last pass draws on the surface, the others on the target the previous one didn't use
+    let targets = &*targets;
+    let mut source = source;
+    let mut next = 0;
+    let last = passes.len() - 1;
+    for (i, pass) in passes.iter().enumerate() {
+        if i == last {
+            apply_pass(facade, surface, pass, source, targets, context)?;
+        } else {
+            let destination = &targets.swap[next];
+            let mut framebuffer = framebuffer(facade, destination)?;
+            apply_pass(facade, &mut framebuffer, pass, source, targets, context)?;
+            source = destination;
+            next = 1 - next;
+        }
+    }
+    return Ok(());
+}
+
+fn apply_pass<F: Facade, S: Surface>(
+    facade: &F,
+    surface: &mut S,
+    pass: &Pass,
+    source: &Texture2d,
+    targets: &PostTargets,
+    context: &PostContext,
+) -> Result<(), EngineError> {
+    let (effect, program) = match pass {
+        Pass::Effect(effect, program) => (*effect, *program),
+        Pass::Tonemap(program, tonemapping, exposure) => {
+            return tonemap(surface, source, program, *tonemapping, *exposure);
+        }
+    };
+    let mut uniforms = UniformBag::new();
+    uniforms.set("u_source", UniformValue::Texture2d(source, Some(sampler())));
+    uniforms.set("u_texel_size", UniformValue::Vec2(texel_size(source)));
+    match effect {
+        PostEffect::Ssao { radius, intensity, bias } => {
+            let inverse = Matrix4::from(context.projection).invert().unwrap_or(Matrix4::identity());
+            uniforms.set("u_depth", UniformValue::DepthTexture2d(context.depth, Some(sampler())));
+            uniforms.set("u_projection", UniformValue::Mat4(context.projection));
+            uniforms.set("u_inverse_projection", UniformValue::Mat4(inverse.into()));
+            uniforms.set("u_radius", UniformValue::Float(*radius));
+            uniforms.set("u_intensity", UniformValue::Float(*intensity));
+            uniforms.set("u_bias", UniformValue::Float(*bias));
+        }
+        PostEffect::Bloom { .. } => {
+            return apply_bloom(facade, surface, effect, program, source, targets);
+        }
+        PostEffect::ColorGrading { lut, contribution } => {
+            // without its table the image goes through unchanged
+            let (lut, size) = match context.textures.get(lut) {
+                Some(texture) => (texture.uniform_value(Some(sampler())), texture.dimensions().1 as f32),
+                None => (UniformValue::Texture2d(source, Some(sampler())), 1.0),
+            };
+            uniforms.set("u_lut", lut);
+            uniforms.set("u_lut_size", UniformValue::Float(size));
+            let contribution = if size > 1.0 { *contribution } else { 0.0 };
+            uniforms.set("u_contribution", UniformValue::Float(contribution));
+        }
+        PostEffect::Vignette { intensity, smoothness } => {
+            uniforms.set("u_intensity", UniformValue::Float(*intensity));
+            uniforms.set("u_smoothness", UniformValue::Float(*smoothness));
+        }
+        PostEffect::Fxaa => (),
+        PostEffect::Gamma(gamma) => {
+            uniforms.set("u_gamma", UniformValue::Float(*gamma));
+        }
+        PostEffect::Custom(custom) => {
+            uniforms.set("u_depth", UniformValue::DepthTexture2d(context.depth, Some(sampler())));
+            for (name, property) in &custom.properties {
+                // the textures of the effects are loaded with the default settings
+                let settings = TextureSettings::default();
+                if let Some(value) = property.as_uniform_value(context.textures, &settings, sampler()) {
+                    uniforms.set(name, value);
+                }
+            }
+        }
+    }
+    return draw_fullscreen(surface, program, &uniforms, Blend::default());
+}
+
+// the bright pixels are extracted at half the resolution, blurred down the levels then back up,
+// each level being added to the larger one, and the largest is added to the image (Jimenez 2014)
+fn apply_bloom<F: Facade, S: Surface>(
+    facade: &F,
+    surface: &mut S,
+    effect: &PostEffect,
+    program: &Program,
+    source: &Texture2d,
+    targets: &PostTargets,
+) -> Result<(), EngineError> {
+    let PostEffect::Bloom { threshold, knee, intensity, radius, levels } = effect else {
+        return Ok(());
+    };
+    let levels = &targets.bloom[..(*levels as usize).min(targets.bloom.len())];
+    let pass = |mode: i32, input: &Texture2d| {
+        let mut uniforms = UniformBag::new();
+        uniforms.set("u_bloom_pass", UniformValue::SignedInt(mode));
+        uniforms.set("u_texel_size", UniformValue::Vec2(texel_size(input)));
+        uniforms.set("u_threshold", UniformValue::Float(*threshold));
+        uniforms.set("u_knee", UniformValue::Float(*knee));
+        uniforms.set("u_radius", UniformValue::Float(*radius));
+        uniforms.set("u_intensity", UniformValue::Float(*intensity));
+        uniforms
+    };
+    let Some(first) = levels.first() else {
+        // the image is too small to be blurred
+        let mut uniforms = pass(3, source);
+        uniforms.set("u_source", UniformValue::Texture2d(source, Some(sampler())));
+        uniforms.set("u_bloom", UniformValue::Texture2d(source, Some(sampler())));
+        uniforms.set("u_intensity", UniformValue::Float(0.0));
+        return draw_fullscreen(surface, program, &uniforms, Blend::default());
+    };
+
+    let mut uniforms = pass(0, source);
+    uniforms.set("u_source", UniformValue::Texture2d(source, Some(sampler())));
+    draw_fullscreen(&mut framebuffer(facade, first)?, program, &uniforms, Blend::default())?;
+    for i in 1..levels.len() {
+        let mut uniforms = pass(1, &levels[i - 1]);
+        uniforms.set("u_source", UniformValue::Texture2d(&levels[i - 1], Some(sampler())));
+        draw_fullscreen(&mut framebuffer(facade, &levels[i])?, program, &uniforms, Blend::default())?;
+    }
+    for i in (0..levels.len() - 1).rev() {
+        let mut uniforms = pass(2, &levels[i + 1]);
+        uniforms.set("u_source", UniformValue::Texture2d(&levels[i + 1], Some(sampler())));
+        draw_fullscreen(&mut framebuffer(facade, &levels[i])?, program, &uniforms, additive())?;
+    }
+
+    let mut uniforms = pass(3, source);
+    uniforms.set("u_source", UniformValue::Texture2d(source, Some(sampler())));
+    uniforms.set("u_bloom", UniformValue::Texture2d(first, Some(sampler())));
+    return draw_fullscreen(surface, program, &uniforms, Blend::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effects_are_split_around_the_tonemapping() {
+        assert!(PostEffect::bloom().is_hdr());
+        assert!(PostEffect::ssao().is_hdr());
+        assert!(!PostEffect::Fxaa.is_hdr());
+        let mut custom = CustomEffect::new("shaders/outline.glsl");
+        assert!(!PostEffect::Custom(custom.clone()).is_hdr());
+        custom.hdr = true;
+        assert!(PostEffect::Custom(custom).is_hdr());
+    }
+
+    #[test]
+    fn custom_effects_use_their_own_shader() {
+        let custom = CustomEffect::new("shaders/outline.glsl")
+            .with_property("u_mask", MaterialProperty::Texture("mask.png".to_string()));
+        let effect = PostEffect::Custom(custom);
+        assert_eq!(effect.shader_variant().fragment_path, "shaders/outline.glsl");
+        assert_eq!(effect.texture_paths(), vec!["mask.png"]);
+        assert_eq!(PostEffect::builtin_variants().len(), 6);
+    }
+}
@@ -0,0 +1,510 @@
+#![allow(dead_code)]
+
+use std::f32::consts::PI;
+
+use cgmath::InnerSpace;
+use cgmath::Vector3;
+
+use crate::mesh::Mesh;
+
+// model paths of the primitives, they can be given to graphic components like the path of an obj
+// file, other sizes can be registered with Scene::add_mesh
+pub const CUBE_MODEL: &str = "builtin/cube";
+pub const SPHERE_MODEL: &str = "builtin/sphere";
+pub const ICO_SPHERE_MODEL: &str = "builtin/ico_sphere";
+pub const PLANE_MODEL: &str = "builtin/plane";
+pub const CYLINDER_MODEL: &str = "builtin/cylinder";
+pub const CONE_MODEL: &str = "builtin/cone";
+pub const CAPSULE_MODEL: &str = "builtin/capsule";
+pub const TORUS_MODEL: &str = "builtin/torus";
+
+// the primitives fit in a unit cube centred on the origin
+pub fn builtin_mesh(path: &str) -> Option<Mesh> {
+    let mesh = match path {
+        CUBE_MODEL => cube(1.0),
+        SPHERE_MODEL => uv_sphere(0.5, 32, 16),
+        ICO_SPHERE_MODEL => ico_sphere(0.5, 3),
+        PLANE_MODEL => plane(1.0, 1.0, 1, 1),
+        CYLINDER_MODEL => cylinder(0.5, 1.0, 32),
+        CONE_MODEL => cone(0.5, 1.0, 32),
+        CAPSULE_MODEL => capsule(0.25, 0.5, 32, 8),
+        TORUS_MODEL => torus(0.35, 0.15, 32, 16),
+        _ => return None,
+    };
+    return Some(mesh);
+}
+
+// vertices being accumulated, every primitive has normals and uvs
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    tex_coords: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, position: Vector3<f32>, normal: Vector3<f32>, tex_coord: [f32; 2]) -> u32 {
+        self.positions.push(position.into());
+        self.normals.push(normal.into());
+        self.tex_coords.push(tex_coord);
+        return self.positions.len() as u32 - 1;
+    }
+
+    // triangles are counter-clockwise when seen from the side their normal points to
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend([a, b, c]);
+    }
+
+    // corners in counter-clockwise order
+    fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    fn build(self) -> Mesh {
+        return Mesh::from_positions_indices(self.positions, self.indices)
+            .with_normals(self.normals)
+            .with_tex_coords(self.tex_coords);
+    }
+}
+
+// the point of the unit circle of the xz plane at the given angle
+fn around_y(angle: f32) -> Vector3<f32> {
+    return Vector3::new(angle.cos(), 0.0, angle.sin());
+}
+
+pub fn cube(size: f32) -> Mesh {
+    let half = size / 2.0;
+    let x = Vector3::unit_x();
+    let y = Vector3::unit_y();
+    let z = Vector3::unit_z();
+    // normal and the two axes of the face, with cross(u, v) = normal
+    let faces = [(x, -z, y), (-x, z, y), (y, x, -z), (-y, x, z), (z, x, y), (-z, -x, y)];
+
+    let mut builder = MeshBuilder::default();
+    for (normal, u, v) in faces {
+        let centre = normal * half;
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(i, j)| {
+            let position = centre + (u * i + v * j) * half;
+            builder.vertex(position, normal, [(i + 1.0) / 2.0, (j + 1.0) / 2.0])
+        });
+        builder.quad(corners[0], corners[1], corners[2], corners[3]);
+    }
+    return builder.build();
+}
+
+// a plane of the xz plane facing up, split into a grid of quads
+pub fn plane(width: f32, depth: f32, subdivisions_x: u32, subdivisions_z: u32) -> Mesh {
+    let (columns, rows) = (subdivisions_x.max(1), subdivisions_z.max(1));
+    let mut builder = MeshBuilder::default();
+    for j in 0..=rows {
+        for i in 0..=columns {
+            let (u, v) = (i as f32 / columns as f32, j as f32 / rows as f32);
+            let position = Vector3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth);
+            builder.vertex(position, Vector3::unit_y(), [u, v]);
+        }
+    }
+    let index = |i: u32, j: u32| j * (columns + 1) + i;
+    for j in 0..rows {
+        for i in 0..columns {
+            builder.quad(index(i, j), index(i, j + 1), index(i + 1, j + 1), index(i + 1, j));
+        }
+    }
+    return builder.build();
+}
+
+// surface of revolution around the y axis, each profile point is the polar angle from +y of the
+// normal, the position of the centre of the circle the point lies on and the v coordinate
+// points of the profile on the axis (polar angle of 0 or pi) are poles
+fn lathe(radius: f32, segments: u32, profile: &[(f32, f32, f32)]) -> Mesh {
+    let segments = segments.max(3);
+    let mut builder = MeshBuilder::default();
+    for (theta, centre_y, v) in profile {
+        for s in 0..=segments {
+            let u = s as f32 / segments as f32;
+            let around = around_y(u * 2.0 * PI);
+            let normal = around * theta.sin() + Vector3::unit_y() * theta.cos();
+            let position = normal * radius + Vector3::unit_y() * *centre_y;
+            builder.vertex(position, normal, [u, *v]);
+        }
+    }
+    let index = |row: usize, s: u32| row as u32 * (segments + 1) + s;
+    let is_pole = |row: usize| profile[row].0.sin().abs() < 1e-6;
+    for row in 0..profile.len() - 1 {
+        for s in 0..segments {
+            let (a, b) = (index(row, s), index(row, s + 1));
+            let (c, d) = (index(row + 1, s + 1), index(row + 1, s));
+            // the quads touching a pole are triangles
+            if !is_pole(row) {
+                builder.triangle(a, b, c);
+            }
+            if !is_pole(row + 1) {
+                builder.triangle(a, c, d);
+            }
+        }
+    }
+    return builder.build();
+}
+
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
+    let rings = rings.max(2);
+    let profile: Vec<_> = (0..=rings)
+        .map(|r| {
+            let t = r as f32 / rings as f32;
+            (t * PI, 0.0, 1.0 - t)
+        })
+        .collect();
+    return lathe(radius, segments, &profile);
+}
+
+// a cylinder of the given height closed by two hemispheres, the total height is height + 2 radius
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Mesh {
+    let rings = rings.max(1);
+    let half = height / 2.0;
+    // the uvs are spread along the length of the profile
+    let arc = radius * PI / 2.0;
+    let length = 2.0 * arc + height;
+    let mut profile = Vec::new();
+    for r in 0..=rings {
+        let theta = r as f32 / rings as f32 * PI / 2.0;
+        profile.push((theta, half, 1.0 - radius * theta / length));
+    }
+    for r in 0..=rings {
+        let theta = PI / 2.0 + r as f32 / rings as f32 * PI / 2.0;
+        profile.push((theta, -half, 1.0 - (radius * theta + height) / length));
+    }
+    return lathe(radius, segments, &profile);
+}
+
+// subdivided icosahedron, its triangles are all about the same size unlike those of uv spheres
+pub fn ico_sphere(radius: f32, subdivisions: u32) -> Mesh {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let mut points: Vec<Vector3<f32>> = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|(x, y, z)| Vector3::new(*x, *y, *z).normalize())
+    .collect();
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+    // makes every triangle face outwards, whatever the order of the table above
+    for triangle in triangles.iter_mut() {
+        let [a, b, c] = triangle.map(|i| points[i as usize]);
+        if Vector3::cross(b - a, c - a).dot(a + b + c) < 0.0 {
+            triangle.swap(1, 2);
+        }
+    }
+
+    for _ in 0..subdivisions {
+        let mut midpoints = std::collections::HashMap::new();
+        let mut midpoint = |a: u32, b: u32, points: &mut Vec<Vector3<f32>>| -> u32 {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                points.push((points[a as usize] + points[b as usize]).normalize());
+                points.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let ab = midpoint(a, b, &mut points);
+                let bc = midpoint(b, c, &mut points);
+                let ca = midpoint(c, a, &mut points);
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let tex_coord = |p: Vector3<f32>| {
+        let u = p.z.atan2(p.x) / (2.0 * PI);
+        [if u < 0.0 { u + 1.0 } else { u }, 1.0 - p.y.clamp(-1.0, 1.0).acos() / PI]
+    };
+    let mut builder = MeshBuilder::default();
+    for point in &points {
+        builder.vertex(*point * radius, *point, tex_coord(*point));
+    }
+    // the triangles crossing the seam of the texture need copies of their vertices on the other
+    // side of it, otherwise they would span the whole texture
+    // the poles have no meaningful u, each triangle touching one gets its own copy of the pole
+    let is_pole = |i: u32| points[i as usize].y.abs() > 1.0 - 1e-6;
+    let mut wrapped = std::collections::HashMap::new();
+    for triangle in triangles {
+        let us: Vec<f32> = triangle
+            .iter()
+            .filter(|i| !is_pole(**i))
+            .map(|i| builder.tex_coords[*i as usize][0])
+            .collect();
+        let crosses_seam = us.iter().cloned().fold(0.0, f32::max)
+            - us.iter().cloned().fold(1.0, f32::min)
+            > 0.5;
+        let wrap = |u: f32| if crosses_seam && u < 0.5 { u + 1.0 } else { u };
+        let pole_u = us.iter().map(|u| wrap(*u)).sum::<f32>() / us.len() as f32;
+        let [a, b, c] = triangle.map(|i| {
+            let [u, v] = builder.tex_coords[i as usize];
+            let point = points[i as usize];
+            if is_pole(i) {
+                return builder.vertex(point * radius, point, [pole_u, v]);
+            }
+            if wrap(u) == u {
+                return i;
+            }
+            *wrapped
+                .entry(i)
+                .or_insert_with(|| builder.vertex(point * radius, point, [u + 1.0, v]))
+        });
+        builder.triangle(a, b, c);
+    }
+    return builder.build();
+}
+
+// closes a circle of the xz plane at the given height, facing up or down
+fn cap(builder: &mut MeshBuilder, radius: f32, y: f32, segments: u32, up: bool) {
+    let normal = if up { Vector3::unit_y() } else { -Vector3::unit_y() };
+    let centre = builder.vertex(Vector3::unit_y() * y, normal, [0.5, 0.5]);
+    let first = builder.positions.len() as u32;
+    for s in 0..segments {
+        let around = around_y(s as f32 / segments as f32 * 2.0 * PI);
+        let position = around * radius + Vector3::unit_y() * y;
+        builder.vertex(position, normal, [0.5 + around.x / 2.0, 0.5 + around.z / 2.0]);
+    }
+    for s in 0..segments {
+        let (a, b) = (first + s, first + (s + 1) % segments);
+        if up {
+            builder.triangle(centre, b, a);
+        } else {
+            builder.triangle(centre, a, b);
+        }
+    }
+}
+
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> Mesh {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let mut builder = MeshBuilder::default();
+    for s in 0..=segments {
+        let u = s as f32 / segments as f32;
+        let around = around_y(u * 2.0 * PI);
+        builder.vertex(around * radius - Vector3::unit_y() * half, around, [u, 0.0]);
+        builder.vertex(around * radius + Vector3::unit_y() * half, around, [u, 1.0]);
+    }
+    for s in 0..segments {
+        let (bottom, top) = (2 * s, 2 * s + 1);
+        builder.quad(bottom, top, top + 2, bottom + 2);
+    }
+    cap(&mut builder, radius, half, segments, true);
+    cap(&mut builder, radius, -half, segments, false);
+    return builder.build();
+}
+
+// the base is at -height / 2 and the tip at height / 2
+pub fn cone(radius: f32, height: f32, segments: u32) -> Mesh {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let slope_normal = |angle: f32| (around_y(angle) * height + Vector3::unit_y() * radius).normalize();
+    let mut builder = MeshBuilder::default();
+    for s in 0..=segments {
+        let u = s as f32 / segments as f32;
+        let angle = u * 2.0 * PI;
+        let position = around_y(angle) * radius - Vector3::unit_y() * half;
+        builder.vertex(position, slope_normal(angle), [u, 0.0]);
+    }
+    // the tip has one vertex per side so that each gets the normal of the middle of its side
+    for s in 0..segments {
+        let u = (s as f32 + 0.5) / segments as f32;
+        let tip = builder.vertex(Vector3::unit_y() * half, slope_normal(u * 2.0 * PI), [u, 1.0]);
+        builder.triangle(s, tip, s + 1);
+    }
+    cap(&mut builder, radius, -half, segments, false);
+    return builder.build();
+}
+
+// a ring lying in the xz plane, the major radius goes to the centre of the tube
+pub fn torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> Mesh {
+    let (major_segments, minor_segments) = (major_segments.max(3), minor_segments.max(3));
+    let mut builder = MeshBuilder::default();
+    for i in 0..=major_segments {
+        let u = i as f32 / major_segments as f32;
+        let around = around_y(u * 2.0 * PI);
+        for j in 0..=minor_segments {
+            let v = j as f32 / minor_segments as f32;
+            let angle = v * 2.0 * PI;
+            let normal = around * angle.cos() + Vector3::unit_y() * angle.sin();
+            builder.vertex(around * major_radius + normal * minor_radius, normal, [u, v]);
+        }
+    }
+    let index = |i: u32, j: u32| i * (minor_segments + 1) + j;
+    for i in 0..major_segments {
+        for j in 0..minor_segments {
+            builder.quad(index(i, j), index(i, j + 1), index(i + 1, j + 1), index(i + 1, j));
+        }
+    }
+    return builder.build();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    // vertices are duplicated along the uv seams, they are merged by position
+    fn welded_triangles(mesh: &Mesh) -> Vec<[(i32, i32, i32); 3]> {
+        let key = |i: u32| {
+            let [x, y, z] = mesh.positions[i as usize].map(|c| (c * 1e4).round() as i32);
+            (x, y, z)
+        };
+        return mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| [key(t[0]), key(t[1]), key(t[2])])
+            .collect();
+    }
+
+    // closed and consistently oriented, every edge is shared by exactly two triangles which go
+    // through it in opposite directions
+    fn assert_watertight(mesh: &Mesh) {
+        let mut edges = HashMap::new();
+        for [a, b, c] in welded_triangles(mesh) {
+            assert!(a != b && b != c && c != a, "degenerate triangle");
+            for edge in [(a, b), (b, c), (c, a)] {
+                *edges.entry(edge).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &count) in &edges {
+            assert_eq!(count, 1, "edge {:?} {:?} used {} times in the same direction", a, b, count);
+            assert_eq!(edges.get(&(b, a)), Some(&1), "edge {:?} {:?} is on a hole", a, b);
+        }
+    }
+
+    // the winding of the triangles agrees with their normals, which are of unit length
+    fn assert_normals_consistent(mesh: &Mesh) {
+        let normals = mesh.normals.as_ref().unwrap();
+        assert_eq!(normals.len(), mesh.positions.len());
+        assert_eq!(mesh.tex_coords[0].len(), mesh.positions.len());
+        for normal in normals {
+            assert!((Vector3::from(*normal).magnitude() - 1.0).abs() < 1e-4);
+        }
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(mesh.positions[triangle[i] as usize]));
+            let face_normal = Vector3::cross(b - a, c - a);
+            for index in triangle {
+                let normal = Vector3::from(normals[*index as usize]);
+                assert!(face_normal.dot(normal) > 0.0, "triangle wound against its normals");
+            }
+        }
+    }
+
+    // for shapes which are convex and centred on the origin
+    fn assert_normals_outwards(mesh: &Mesh) {
+        let normals = mesh.normals.as_ref().unwrap();
+        for (position, normal) in mesh.positions.iter().zip(normals) {
+            assert!(Vector3::from(*position).dot(Vector3::from(*normal)) > 0.0);
+        }
+    }
+
+    fn assert_closed_convex(mesh: &Mesh) {
+        assert_watertight(mesh);
+        assert_normals_consistent(mesh);
+        assert_normals_outwards(mesh);
+    }
+
+    #[test]
+    fn cube_is_closed() {
+        let mesh = cube(2.0);
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_eq!(mesh.indices.len(), 36);
+        assert_closed_convex(&mesh);
+    }
+
+    #[test]
+    fn spheres_are_closed() {
+        assert_closed_convex(&uv_sphere(1.0, 16, 8));
+        assert_closed_convex(&ico_sphere(1.0, 0));
+        assert_closed_convex(&ico_sphere(1.0, 2));
+    }
+
+    #[test]
+    fn sphere_vertices_are_on_the_sphere() {
+        for mesh in [uv_sphere(2.0, 12, 6), ico_sphere(2.0, 2)] {
+            for position in &mesh.positions {
+                assert!((Vector3::from(*position).magnitude() - 2.0).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn ico_sphere_uvs_do_not_span_the_seam() {
+        let mesh = ico_sphere(1.0, 2);
+        for triangle in mesh.indices.chunks_exact(3) {
+            let us = [0, 1, 2].map(|i| mesh.tex_coords[0][triangle[i] as usize][0]);
+            let spread = us.iter().cloned().fold(f32::MIN, f32::max)
+                - us.iter().cloned().fold(f32::MAX, f32::min);
+            assert!(spread < 0.5);
+        }
+    }
+
+    #[test]
+    fn cylinder_and_cone_are_closed() {
+        assert_closed_convex(&cylinder(1.0, 2.0, 12));
+        assert_closed_convex(&cone(1.0, 2.0, 12));
+    }
+
+    #[test]
+    fn capsule_is_closed() {
+        assert_closed_convex(&capsule(0.5, 1.0, 12, 4));
+    }
+
+    #[test]
+    fn torus_is_closed() {
+        let (major, minor) = (1.0, 0.25);
+        let mesh = torus(major, minor, 16, 8);
+        assert_watertight(&mesh);
+        assert_normals_consistent(&mesh);
+        // the normals point away from the centre line of the tube
+        for (position, normal) in mesh.positions.iter().zip(mesh.normals.as_ref().unwrap()) {
+            let position = Vector3::from(*position);
+            let centre = Vector3::new(position.x, 0.0, position.z).normalize() * major;
+            assert!((position - centre).dot(Vector3::from(*normal)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn plane_faces_up() {
+        let mesh = plane(2.0, 3.0, 4, 2);
+        assert_eq!(mesh.vertex_count(), 15);
+        assert_eq!(mesh.indices.len(), 4 * 2 * 6);
+        assert_normals_consistent(&mesh);
+    }
+
+    #[test]
+    fn builtin_meshes_exist() {
+        for path in [
+            CUBE_MODEL,
+            SPHERE_MODEL,
+            ICO_SPHERE_MODEL,
+            PLANE_MODEL,
+            CYLINDER_MODEL,
+            CONE_MODEL,
+            CAPSULE_MODEL,
+            TORUS_MODEL,
+        ] {
+            assert!(builtin_mesh(path).is_some());
+        }
+        assert!(builtin_mesh("assets/models/cube.obj").is_none());
+    }
+}
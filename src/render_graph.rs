@@ -0,0 +1,833 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use glium::backend::Facade;
+use glium::framebuffer::MultiOutputFrameBuffer;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::glutin::surface::WindowSurface;
+use glium::index::IndicesSource;
+use glium::texture::DepthFormat;
+use glium::texture::DepthTexture2d;
+use glium::texture::MipmapsOption;
+use glium::texture::Texture2d;
+use glium::texture::UncompressedFloatFormat;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::Uniforms;
+use glium::vertex::MultiVerticesSource;
+use glium::BlitMask;
+use glium::BlitTarget;
+use glium::Display;
+use glium::DrawError;
+use glium::DrawParameters;
+use glium::Frame;
+use glium::Program;
+use glium::Rect;
+use glium::Surface;
+
+use tracing::debug_span;
+use tracing::trace;
+
+use crate::camera::Camera;
+use crate::error::EngineError;
+use crate::logging::RENDER;
+use crate::scene::Scene;
+use crate::text::ScreenTextPass;
+use crate::text::WorldTextPass;
+use crate::ui::UiPass;
+
+// the window, the graph only runs the passes which end up drawing on it
+pub const BACKBUFFER: &str = "backbuffer";
+// the image and depth of the scene when it is drawn offscreen, see FrameInfo::offscreen
+pub const SCENE_COLOR: &str = "scene_color";
+pub const SCENE_DEPTH: &str = "scene_depth";
+// owned by the scene rather than by the graph, it only orders the shadow pass before the forward one
+pub const SHADOW_MAPS: &str = "shadow_maps";
+
+// the passes of RenderGraph::forward
+pub const SHADOW_PASS: &str = "shadows";
+pub const FORWARD_PASS: &str = "forward";
+pub const POST_PASS: &str = "post";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    // 8 bits per channel
+    Color,
+    // half floats, for the colours which go past 1
+    Hdr,
+    Depth,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextureSize {
+    // the size of the window
+    Screen,
+    // a fraction of the size of the window, for the effects which don't need every pixel
+    Scaled(f32),
+    Fixed(u32, u32),
+}
+
+// what a transient texture looks like, the textures with the same description are shared by the
+// resources which aren't used at the same time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureDesc {
+    pub format: TextureFormat,
+    pub size: TextureSize,
+}
+
+impl TextureDesc {
+    pub fn screen(format: TextureFormat) -> Self {
+        return TextureDesc {
+            format,
+            size: TextureSize::Screen,
+        };
+    }
+
+    pub fn dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        let (width, height) = match self.size {
+            TextureSize::Screen => (width, height),
+            TextureSize::Scaled(scale) => ((width as f32 * scale) as u32, (height as f32 * scale) as u32),
+            TextureSize::Fixed(width, height) => (width, height),
+        };
+        return (width.max(1), height.max(1));
+    }
+}
+
+// what the passes know about the frame when they declare their resources
+pub struct FrameInfo<'a> {
+    pub camera: &'a Camera,
+    pub width: u32,
+    pub height: u32,
+    // the scene is drawn into SCENE_COLOR and SCENE_DEPTH and the post pass presents it, which
+    // happens when the camera is hdr or has post effects, otherwise it is drawn on the backbuffer
+    pub offscreen: bool,
+}
+
+impl FrameInfo<'_> {
+    // where the objects of the scene are drawn this frame
+    pub fn scene_target(&self) -> &'static str {
+        if self.offscreen {
+            return SCENE_COLOR;
+        }
+        return BACKBUFFER;
+    }
+}
+
+// the resources a pass uses, declared by RenderPass::setup
+// a resource is just a name, the graph allocates the ones which are created with a description
+// and the others, like the backbuffer or the shadow maps, only order the passes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PassBuilder {
+    reads: Vec<String>,
+    writes: Vec<String>,
+    creates: Vec<(String, TextureDesc)>,
+}
+
+impl PassBuilder {
+    // the pass runs after every pass which writes the resource, nothing happens when there is none
+    pub fn read(&mut self, name: &str) {
+        self.reads.push(name.to_string());
+    }
+
+    // the passes writing the same resource run in the order they were added to the graph, and
+    // before the passes which only read it
+    pub fn write(&mut self, name: &str) {
+        self.writes.push(name.to_string());
+    }
+
+    // a texture allocated by the graph for this frame, the pass must be the first to write it
+    pub fn create(&mut self, name: &str, desc: TextureDesc) {
+        self.creates.push((name.to_string(), desc));
+    }
+
+    // for the passes drawing over the objects, with the depth of the scene when there is one
+    pub fn write_scene(&mut self, info: &FrameInfo) {
+        self.write(info.scene_target());
+        if info.offscreen {
+            self.write(SCENE_DEPTH);
+        }
+    }
+
+    fn written(&self) -> impl Iterator<Item = &str> {
+        let created = self.creates.iter().map(|(name, _)| name.as_str());
+        return created.chain(self.writes.iter().map(|name| name.as_str()));
+    }
+
+    fn uses(&self, name: &str) -> bool {
+        return self.written().chain(self.reads.iter().map(|read| read.as_str())).any(|used| used == name);
+    }
+}
+
+pub trait RenderPass {
+    // unique in its graph, used to insert passes around it
+    fn name(&self) -> &str;
+
+    // called every frame before the passes are ordered, so the resources may depend on the camera
+    fn setup(&self, info: &FrameInfo, builder: &mut PassBuilder);
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<(), EngineError>;
+}
+
+// what a pass may use while it executes, the fields are public so that the pass can borrow the
+// scene and the frame at the same time
+pub struct PassContext<'a> {
+    pub scene: &'a mut Scene,
+    pub display: &'a Display<WindowSurface>,
+    pub frame: &'a mut Frame,
+    pub info: &'a FrameInfo<'a>,
+    pub resources: &'a GraphResources<'a>,
+}
+
+enum GraphTexture {
+    Color(Texture2d),
+    Depth(DepthTexture2d),
+}
+
+// a texture of the pool, reallocated when the description or the size of the window changes
+struct PooledTexture {
+    desc: TextureDesc,
+    dimensions: (u32, u32),
+    texture: GraphTexture,
+}
+
+impl PooledTexture {
+    fn new<F: Facade>(facade: &F, desc: TextureDesc, dimensions: (u32, u32)) -> Result<Self, EngineError> {
+        let (width, height) = dimensions;
+        let color = |format| Texture2d::empty_with_format(facade, format, MipmapsOption::NoMipmap, width, height);
+        let texture = match desc.format {
+            TextureFormat::Color => color(UncompressedFloatFormat::U8U8U8U8).map(GraphTexture::Color),
+            TextureFormat::Hdr => color(UncompressedFloatFormat::F16F16F16F16).map(GraphTexture::Color),
+            TextureFormat::Depth => {
+                DepthTexture2d::empty_with_format(facade, DepthFormat::I24, MipmapsOption::NoMipmap, width, height)
+                    .map(GraphTexture::Depth)
+            }
+        };
+        let texture = texture.map_err(|err| EngineError::upload("render graph texture", err))?;
+        return Ok(PooledTexture {
+            desc,
+            dimensions,
+            texture,
+        });
+    }
+}
+
+// the transient textures of the frame, by resource name
+pub struct GraphResources<'a> {
+    textures: HashMap<String, &'a GraphTexture>,
+}
+
+impl<'a> GraphResources<'a> {
+    pub fn texture(&self, name: &str) -> Option<&'a Texture2d> {
+        match self.textures.get(name) {
+            Some(GraphTexture::Color(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+
+    pub fn depth_texture(&self, name: &str) -> Option<&'a DepthTexture2d> {
+        match self.textures.get(name) {
+            Some(GraphTexture::Depth(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+
+    // the surface to draw a colour resource on, the depth is ignored for the backbuffer which
+    // has its own
+    pub fn target<'b, F: Facade>(
+        &self,
+        facade: &F,
+        frame: &'b mut Frame,
+        color: &str,
+        depth: Option<&str>,
+    ) -> Result<PassTarget<'b>, EngineError>
+    where
+        'a: 'b,
+    {
+        if color == BACKBUFFER {
+            return Ok(PassTarget::Frame(frame));
+        }
+        let missing = |name: &str| graph_error(format!("{} is not a texture of the graph this frame", name));
+        let color_texture = self.texture(color).ok_or_else(|| missing(color))?;
+        let framebuffer = match depth {
+            Some(depth) => {
+                let depth_texture = self.depth_texture(depth).ok_or_else(|| missing(depth))?;
+                SimpleFrameBuffer::with_depth_buffer(facade, color_texture, depth_texture)
+            }
+            None => SimpleFrameBuffer::new(facade, color_texture),
+        };
+        let framebuffer = framebuffer.map_err(|err| graph_error(format!("could not draw into {}: {:?}", color, err)))?;
+        return Ok(PassTarget::Texture(framebuffer));
+    }
+}
+
+fn graph_error(message: String) -> EngineError {
+    return EngineError::RenderGraph { message };
+}
+
+// the passes to run this frame and where their textures are allocated
+#[derive(Debug, PartialEq)]
+struct CompiledGraph {
+    // indices of the passes, in the order they run
+    order: Vec<usize>,
+    transients: Vec<Transient>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Transient {
+    name: String,
+    desc: TextureDesc,
+    // index in the pool, the resources whose lifetimes don't overlap share one
+    slot: usize,
+}
+
+// orders the passes from their declarations, culls the ones which don't contribute to the
+// backbuffer and assigns the transient textures to the slots of the pool
+fn compile(names: &[&str], declarations: &[PassBuilder]) -> Result<CompiledGraph, EngineError> {
+    let count = declarations.len();
+    let mut writers: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut creators: HashMap<&str, usize> = HashMap::new();
+    for (pass, declaration) in declarations.iter().enumerate() {
+        for (name, _) in &declaration.creates {
+            if name == BACKBUFFER {
+                return Err(graph_error(format!("{} can't create the backbuffer", names[pass])));
+            }
+            if let Some(other) = creators.insert(name, pass) {
+                return Err(graph_error(format!("{} is created by both {} and {}", name, names[other], names[pass])));
+            }
+        }
+        for name in declaration.written() {
+            let list = writers.entry(name).or_default();
+            if list.last() != Some(&pass) {
+                list.push(pass);
+            }
+        }
+    }
+    for (name, creator) in &creators {
+        let first = writers[name][0];
+        if first != *creator {
+            return Err(graph_error(format!(
+                "{} is written by {} before being created by {}",
+                name, names[first], names[*creator]
+            )));
+        }
+    }
+
+    // the passes each pass waits for
+    let mut dependencies: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); count];
+    for list in writers.values() {
+        for pair in list.windows(2) {
+            dependencies[pair[1]].insert(pair[0]);
+        }
+    }
+    for (pass, declaration) in declarations.iter().enumerate() {
+        for name in &declaration.reads {
+            // a pass which also writes the resource is already chained with the other writers
+            match writers.get(name.as_str()) {
+                Some(list) if !list.contains(&pass) => dependencies[pass].extend(list.iter().copied()),
+                _ => (),
+            }
+        }
+    }
+
+    // only the passes the backbuffer depends on are kept
+    let mut needed = vec![false; count];
+    let mut stack = writers.get(BACKBUFFER).cloned().unwrap_or_default();
+    while let Some(pass) = stack.pop() {
+        if !needed[pass] {
+            needed[pass] = true;
+            stack.extend(dependencies[pass].iter().copied());
+        }
+    }
+
+    // among the passes which are ready, the one added first runs first
+    let mut remaining: Vec<usize> = dependencies.iter().map(|list| list.len()).collect();
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); count];
+    for (pass, list) in dependencies.iter().enumerate() {
+        for dependency in list {
+            dependents[*dependency].push(pass);
+        }
+    }
+    let mut ready: BTreeSet<usize> = (0..count).filter(|pass| needed[*pass] && remaining[*pass] == 0).collect();
+    let mut order = Vec::new();
+    while let Some(pass) = ready.pop_first() {
+        order.push(pass);
+        for dependent in &dependents[pass] {
+            remaining[*dependent] -= 1;
+            if remaining[*dependent] == 0 && needed[*dependent] {
+                ready.insert(*dependent);
+            }
+        }
+    }
+    let needed_count = needed.iter().filter(|needed| **needed).count();
+    if order.len() < needed_count {
+        let cycle: Vec<&str> = (0..count)
+            .filter(|pass| needed[*pass] && !order.contains(pass))
+            .map(|pass| names[pass])
+            .collect();
+        return Err(graph_error(format!("the passes {} depend on each other", cycle.join(", "))));
+    }
+
+    // a slot is free again after the last pass which uses its resource
+    let mut slots: Vec<(TextureDesc, usize)> = Vec::new();
+    let mut transients = Vec::new();
+    for (step, pass) in order.iter().enumerate() {
+        for (name, desc) in &declarations[*pass].creates {
+            let last_use = order
+                .iter()
+                .rposition(|other| declarations[*other].uses(name))
+                .unwrap_or(step);
+            let slot = match slots.iter().position(|(other, free)| other == desc && *free < step) {
+                Some(slot) => {
+                    slots[slot].1 = last_use;
+                    slot
+                }
+                None => {
+                    slots.push((*desc, last_use));
+                    slots.len() - 1
+                }
+            };
+            transients.push(Transient {
+                name: name.clone(),
+                desc: *desc,
+                slot,
+            });
+        }
+    }
+    return Ok(CompiledGraph { order, transients });
+}
+
+// the passes which draw a frame, ordered from the resources they declare rather than from the
+// order they were added in
+pub struct RenderGraph {
+    passes: Vec<Box<dyn RenderPass>>,
+    // kept from one frame to the next
+    pool: Vec<PooledTexture>,
+    // the last error of the compilation, so that a broken graph is reported only once
+    failed: Option<String>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        return RenderGraph {
+            passes: Vec::new(),
+            pool: Vec::new(),
+            failed: None,
+        };
+    }
+
+    // the graph of the scenes which don't have their own: the shadow maps, the objects and the
+    // background, the debug drawing with the debug-draw feature, the texts in the world, the post
+    // processing when the camera draws offscreen, then the interface and the texts of the screen
+    pub fn forward() -> Self {
+        let mut graph = RenderGraph::new();
+        graph.add_pass(ShadowPass);
+        graph.add_pass(ForwardPass);
+        #[cfg(feature = "debug-draw")]
+        graph.add_pass(crate::debug_draw::DebugDrawPass);
+        graph.add_pass(WorldTextPass);
+        graph.add_pass(PostPass);
+        graph.add_pass(UiPass);
+        graph.add_pass(ScreenTextPass);
+        return graph;
+    }
+
+    pub fn add_pass<P: RenderPass + 'static>(&mut self, pass: P) {
+        self.passes.push(Box::new(pass));
+        self.failed = None;
+    }
+
+    // the position only matters among the passes which write the same resource, returns false
+    // when there is no pass with that name
+    pub fn insert_before<P: RenderPass + 'static>(&mut self, name: &str, pass: P) -> bool {
+        let Some(index) = self.position(name) else {
+            return false;
+        };
+        self.passes.insert(index, Box::new(pass));
+        self.failed = None;
+        return true;
+    }
+
+    pub fn insert_after<P: RenderPass + 'static>(&mut self, name: &str, pass: P) -> bool {
+        let Some(index) = self.position(name) else {
+            return false;
+        };
+        self.passes.insert(index + 1, Box::new(pass));
+        self.failed = None;
+        return true;
+    }
+
+    pub fn remove_pass(&mut self, name: &str) -> Option<Box<dyn RenderPass>> {
+        let index = self.position(name)?;
+        self.failed = None;
+        return Some(self.passes.remove(index));
+    }
+
+    pub fn pass_names(&self) -> Vec<&str> {
+        return self.passes.iter().map(|pass| pass.name()).collect();
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        return self.passes.iter().position(|pass| pass.name() == name);
+    }
+
+    // the errors of the passes are reported through the policy of the scene and the next passes
+    // still run, the graph itself fails when it can't be ordered
+    pub fn execute(
+        &mut self,
+        scene: &mut Scene,
+        display: &Display<WindowSurface>,
+        frame: &mut Frame,
+        info: &FrameInfo,
+    ) -> Result<(), EngineError> {
+        let declarations: Vec<PassBuilder> = self
+            .passes
+            .iter()
+            .map(|pass| {
+                let mut builder = PassBuilder::default();
+                pass.setup(info, &mut builder);
+                builder
+            })
+            .collect();
+        let names = self.pass_names();
+        let compiled = match compile(&names, &declarations) {
+            Ok(compiled) => compiled,
+            Err(err) => {
+                let message = err.to_string();
+                if self.failed.as_ref() == Some(&message) {
+                    return Ok(());
+                }
+                self.failed = Some(message);
+                return Err(err);
+            }
+        };
+        trace!(
+            target: RENDER,
+            passes = compiled.order.len(),
+            culled = names.len() - compiled.order.len(),
+            transients = compiled.transients.len(),
+            "render graph compiled"
+        );
+
+        let slot_count = compiled.transients.iter().map(|transient| transient.slot + 1).max().unwrap_or(0);
+        self.pool.truncate(slot_count);
+        for transient in &compiled.transients {
+            let dimensions = transient.desc.dimensions(info.width, info.height);
+            let up_to_date = self
+                .pool
+                .get(transient.slot)
+                .map(|pooled| pooled.desc == transient.desc && pooled.dimensions == dimensions)
+                .unwrap_or(false);
+            if up_to_date {
+                continue;
+            }
+            let pooled = PooledTexture::new(display, transient.desc, dimensions)?;
+            if transient.slot < self.pool.len() {
+                self.pool[transient.slot] = pooled;
+            } else {
+                self.pool.push(pooled);
+            }
+        }
+
+        let RenderGraph { passes, pool, .. } = self;
+        let resources = GraphResources {
+            textures: compiled
+                .transients
+                .iter()
+                .map(|transient| (transient.name.clone(), &pool[transient.slot].texture))
+                .collect(),
+        };
+        for index in compiled.order {
+            let pass = &mut passes[index];
+            let _span = debug_span!(target: RENDER, "render_pass", pass = pass.name()).entered();
+            let mut context = PassContext {
+                scene: &mut *scene,
+                display,
+                frame: &mut *frame,
+                info,
+                resources: &resources,
+            };
+            if let Err(err) = pass.execute(&mut context) {
+                scene.report_error(&err);
+            }
+        }
+        return Ok(());
+    }
+}
+
+// renders the shadow maps of the lights, see shadow::render_shadow_maps
+pub struct ShadowPass;
+
+impl RenderPass for ShadowPass {
+    fn name(&self) -> &str {
+        return SHADOW_PASS;
+    }
+
+    fn setup(&self, _info: &FrameInfo, builder: &mut PassBuilder) {
+        builder.write(SHADOW_MAPS);
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<(), EngineError> {
+        let info = context.info;
+        context.scene.render_shadows(info.camera, info.width, info.height);
+        return Ok(());
+    }
+}
+
+// clears the target with the background colour and draws the objects and the background
+pub struct ForwardPass;
+
+impl RenderPass for ForwardPass {
+    fn name(&self) -> &str {
+        return FORWARD_PASS;
+    }
+
+    fn setup(&self, info: &FrameInfo, builder: &mut PassBuilder) {
+        builder.read(SHADOW_MAPS);
+        if info.offscreen {
+            builder.create(SCENE_COLOR, TextureDesc::screen(TextureFormat::Hdr));
+            builder.create(SCENE_DEPTH, TextureDesc::screen(TextureFormat::Depth));
+        } else {
+            builder.write(BACKBUFFER);
+        }
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<(), EngineError> {
+        let PassContext {
+            scene,
+            display,
+            frame,
+            info,
+            resources,
+        } = context;
+        let background = scene.frame_background(info.camera);
+        let mut target = resources.target(*display, frame, info.scene_target(), Some(SCENE_DEPTH))?;
+        // the debug views which ignore the materials are drawn on black
+        let clear_color = if info.camera.debug_view.draws_shaded() {
+            background.clear_color()
+        } else {
+            (0.0, 0.0, 0.0, 1.0)
+        };
+        target.clear_color_and_depth(clear_color, 1.0);
+        scene.draw_geometry(&mut target, info.camera, &background, info.width, info.height);
+        return Ok(());
+    }
+}
+
+// tonemaps and post processes the offscreen image of the scene onto the backbuffer, it has
+// nothing to do when the scene was drawn on the backbuffer directly
+pub struct PostPass;
+
+impl RenderPass for PostPass {
+    fn name(&self) -> &str {
+        return POST_PASS;
+    }
+
+    fn setup(&self, info: &FrameInfo, builder: &mut PassBuilder) {
+        if info.offscreen {
+            builder.read(SCENE_COLOR);
+            builder.read(SCENE_DEPTH);
+            builder.write(BACKBUFFER);
+        }
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<(), EngineError> {
+        let PassContext {
+            scene,
+            display,
+            frame,
+            info,
+            resources,
+        } = context;
+        let missing = |name: &str| graph_error(format!("{} is not a texture of the graph this frame", name));
+        let color = resources.texture(SCENE_COLOR).ok_or_else(|| missing(SCENE_COLOR))?;
+        let depth = resources.depth_texture(SCENE_DEPTH).ok_or_else(|| missing(SCENE_DEPTH))?;
+        return scene.post_process(display, *frame, info.camera, color, depth);
+    }
+}
+
+// the surface a pass draws on, the window or a framebuffer made of transient textures
+pub enum PassTarget<'a> {
+    Frame(&'a mut Frame),
+    Texture(SimpleFrameBuffer<'a>),
+}
+
+impl Surface for PassTarget<'_> {
+    fn clear(
+        &mut self,
+        rect: Option<&Rect>,
+        color: Option<(f32, f32, f32, f32)>,
+        color_srgb: bool,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        match self {
+            PassTarget::Frame(frame) => frame.clear(rect, color, color_srgb, depth, stencil),
+            PassTarget::Texture(framebuffer) => framebuffer.clear(rect, color, color_srgb, depth, stencil),
+        }
+    }
+
+    fn get_dimensions(&self) -> (u32, u32) {
+        match self {
+            PassTarget::Frame(frame) => frame.get_dimensions(),
+            PassTarget::Texture(framebuffer) => framebuffer.get_dimensions(),
+        }
+    }
+
+    fn get_depth_buffer_bits(&self) -> Option<u16> {
+        match self {
+            PassTarget::Frame(frame) => frame.get_depth_buffer_bits(),
+            PassTarget::Texture(framebuffer) => framebuffer.get_depth_buffer_bits(),
+        }
+    }
+
+    fn get_stencil_buffer_bits(&self) -> Option<u16> {
+        match self {
+            PassTarget::Frame(frame) => frame.get_stencil_buffer_bits(),
+            PassTarget::Texture(framebuffer) => framebuffer.get_stencil_buffer_bits(),
+        }
+    }
+
+    fn draw<'a, 'b, V, I, U>(
+        &mut self,
+        vertices: V,
+        indices: I,
+        program: &Program,
+        uniforms: &U,
+        draw_parameters: &DrawParameters<'_>,
+    ) -> Result<(), DrawError>
+    where
+        V: MultiVerticesSource<'b>,
+        I: Into<IndicesSource<'a>>,
+        U: Uniforms,
+    {
+        match self {
+            PassTarget::Frame(frame) => frame.draw(vertices, indices, program, uniforms, draw_parameters),
+            PassTarget::Texture(framebuffer) => framebuffer.draw(vertices, indices, program, uniforms, draw_parameters),
+        }
+    }
+
+    fn blit_buffers_from_frame(
+        &self,
+        source_rect: &Rect,
+        target_rect: &BlitTarget,
+        filter: MagnifySamplerFilter,
+        mask: BlitMask,
+    ) {
+        match self {
+            PassTarget::Frame(frame) => frame.blit_buffers_from_frame(source_rect, target_rect, filter, mask),
+            PassTarget::Texture(framebuffer) => {
+                framebuffer.blit_buffers_from_frame(source_rect, target_rect, filter, mask)
+            }
+        }
+    }
+
+    fn blit_buffers_from_simple_framebuffer(
+        &self,
+        source: &SimpleFrameBuffer<'_>,
+        source_rect: &Rect,
+        target_rect: &BlitTarget,
+        filter: MagnifySamplerFilter,
+        mask: BlitMask,
+    ) {
+        match self {
+            PassTarget::Frame(frame) => {
+                frame.blit_buffers_from_simple_framebuffer(source, source_rect, target_rect, filter, mask)
+            }
+            PassTarget::Texture(framebuffer) => {
+                framebuffer.blit_buffers_from_simple_framebuffer(source, source_rect, target_rect, filter, mask)
+            }
+        }
+    }
+
+    fn blit_buffers_from_multioutput_framebuffer(
+        &self,
+        source: &MultiOutputFrameBuffer<'_>,
+        source_rect: &Rect,
+        target_rect: &BlitTarget,
+        filter: MagnifySamplerFilter,
+        mask: BlitMask,
+    ) {
+        match self {
+            PassTarget::Frame(frame) => {
+                frame.blit_buffers_from_multioutput_framebuffer(source, source_rect, target_rect, filter, mask)
+            }
+            PassTarget::Texture(framebuffer) => {
+                framebuffer.blit_buffers_from_multioutput_framebuffer(source, source_rect, target_rect, filter, mask)
+            }
+        }
+    }
+
+    fn blit_color<S>(&self, source_rect: &Rect, target: &S, target_rect: &BlitTarget, filter: MagnifySamplerFilter)
+    where
+        S: Surface,
+    {
+        match self {
+            PassTarget::Frame(frame) => frame.blit_color(source_rect, target, target_rect, filter),
+            PassTarget::Texture(framebuffer) => framebuffer.blit_color(source_rect, target, target_rect, filter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pass(reads: &[&str], writes: &[&str], creates: &[&str]) -> PassBuilder {
+        let mut builder = PassBuilder::default();
+        reads.iter().for_each(|name| builder.read(name));
+        writes.iter().for_each(|name| builder.write(name));
+        creates.iter().for_each(|name| builder.create(name, TextureDesc::screen(TextureFormat::Hdr)));
+        return builder;
+    }
+
+    #[test]
+    fn passes_are_ordered_by_their_resources() {
+        // added in the wrong order, with a pass which nothing reads
+        let names = ["post", "unused", "forward", "shadows", "overlay"];
+        let declarations = [
+            pass(&[SCENE_COLOR], &[BACKBUFFER], &[]),
+            pass(&[SCENE_COLOR], &[], &["history"]),
+            pass(&[SHADOW_MAPS], &[], &[SCENE_COLOR]),
+            pass(&[], &[SHADOW_MAPS], &[]),
+            pass(&[], &[BACKBUFFER], &[]),
+        ];
+        let compiled = compile(&names, &declarations).unwrap();
+        assert_eq!(compiled.order, vec![3, 2, 0, 4]);
+        // reading something nobody writes doesn't prevent the pass from running
+        let compiled = compile(&["lonely"], &[pass(&["nothing"], &[BACKBUFFER], &[])]).unwrap();
+        assert_eq!(compiled.order, vec![0]);
+    }
+
+    #[test]
+    fn invalid_graphs_are_rejected() {
+        let cycle = [pass(&["b"], &[BACKBUFFER, "a"], &[]), pass(&["a"], &["b"], &[])];
+        assert!(compile(&["first", "second"], &cycle).is_err());
+        let created_twice = [pass(&[], &[], &["a"]), pass(&["a"], &[BACKBUFFER], &["a"])];
+        assert!(compile(&["first", "second"], &created_twice).is_err());
+        let written_before = [pass(&[], &["a"], &[]), pass(&["a"], &[BACKBUFFER], &["a"])];
+        assert!(compile(&["first", "second"], &written_before).is_err());
+    }
+
+    #[test]
+    fn transient_textures_are_shared() {
+        // a is done once b is created, c is still read when d is created
+        let names = ["a", "b", "c", "d", "present"];
+        let declarations = [
+            pass(&[], &[], &["a"]),
+            pass(&["a"], &[], &["b"]),
+            pass(&["b"], &[], &["c"]),
+            pass(&["c"], &[], &["d"]),
+            pass(&["c", "d"], &[BACKBUFFER], &[]),
+        ];
+        let compiled = compile(&names, &declarations).unwrap();
+        let slots: Vec<usize> = compiled.transients.iter().map(|transient| transient.slot).collect();
+        assert_eq!(slots, vec![0, 1, 0, 1]);
+        assert_eq!(TextureDesc::screen(TextureFormat::Color).dimensions(640, 480), (640, 480));
+        let half = TextureDesc {
+            format: TextureFormat::Color,
+            size: TextureSize::Scaled(0.5),
+        };
+        assert_eq!(half.dimensions(640, 480), (320, 240));
+    }
+}
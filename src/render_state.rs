@@ -0,0 +1,268 @@
+#![allow(dead_code)]
+
+use std::cmp::Ordering;
+
+use glium::draw_parameters::BackfaceCullingMode;
+use glium::draw_parameters::DepthTest;
+use glium::Blend;
+use glium::BlendingFunction;
+use glium::LinearBlendingFactor;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+// the order in which the objects are drawn, every queue is drawn after the previous one
+// the background is drawn between the alpha test and the transparent queues
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum RenderQueue {
+    // sorted by program and textures then front to back
+    Opaque,
+    // opaque but the pixels below the alpha cutoff of the material are discarded
+    AlphaTest,
+    // sorted back to front so that the blending composes the layers in order
+    Transparent,
+    // drawn last, on top of everything, for gizmos and markers
+    Overlay,
+}
+
+impl RenderQueue {
+    // whether the objects are drawn before the background
+    pub fn is_opaque(self) -> bool {
+        return matches!(self, RenderQueue::Opaque | RenderQueue::AlphaTest);
+    }
+}
+
+// how the colour of a pixel is combined with the one already in the target
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlendMode {
+    // replaces it
+    Opaque,
+    // mixes by the alpha of the source
+    Alpha,
+    // the source colour has already been multiplied by its alpha
+    Premultiplied,
+    // adds to it, for fire, glows and other lights
+    Additive,
+    // darkens it
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn blend(self) -> Blend {
+        let add = |source, destination| BlendingFunction::Addition { source, destination };
+        use LinearBlendingFactor::*;
+        let (color, alpha) = match self {
+            BlendMode::Opaque => return Blend::default(),
+            BlendMode::Alpha => (add(SourceAlpha, OneMinusSourceAlpha), add(One, OneMinusSourceAlpha)),
+            BlendMode::Premultiplied => (add(One, OneMinusSourceAlpha), add(One, OneMinusSourceAlpha)),
+            BlendMode::Additive => (add(SourceAlpha, One), add(Zero, One)),
+            BlendMode::Multiply => (add(DestinationColor, Zero), add(Zero, One)),
+        };
+        return Blend {
+            color,
+            alpha,
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        };
+    }
+}
+
+// the faces which are not drawn
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+impl CullMode {
+    // the faces of our meshes are counter clockwise seen from the front, see the primitives, but
+    // the view matrix of the camera is left handed which makes them clockwise on screen
+    fn backface_culling(self) -> BackfaceCullingMode {
+        match self {
+            CullMode::None => BackfaceCullingMode::CullingDisabled,
+            CullMode::Back => BackfaceCullingMode::CullCounterClockwise,
+            CullMode::Front => BackfaceCullingMode::CullClockwise,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepthCompare {
+    // the depth test is disabled
+    Always,
+    Less,
+    LessOrEqual,
+    Equal,
+    Greater,
+}
+
+impl DepthCompare {
+    fn depth_test(self) -> DepthTest {
+        match self {
+            DepthCompare::Always => DepthTest::Overwrite,
+            DepthCompare::Less => DepthTest::IfLess,
+            DepthCompare::LessOrEqual => DepthTest::IfLessOrEqual,
+            DepthCompare::Equal => DepthTest::IfEqual,
+            DepthCompare::Greater => DepthTest::IfMore,
+        }
+    }
+}
+
+// the fixed function state a material is drawn with, the defaults depend on the queue
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderState {
+    pub queue: RenderQueue,
+    pub blend: BlendMode,
+    pub cull: CullMode,
+    pub depth_compare: DepthCompare,
+    pub depth_write: bool,
+    // only used by the alpha test queue, bound to u_alpha_cutoff
+    pub alpha_cutoff: f32,
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        return RenderState::for_queue(RenderQueue::Opaque);
+    }
+}
+
+impl RenderState {
+    // the usual state of the objects of a queue, the transparent objects don't write their
+    // depth so that those behind them are still drawn
+    // culling is disabled since the engine has always drawn both sides of the faces
+    pub fn for_queue(queue: RenderQueue) -> Self {
+        let (blend, depth_compare, depth_write) = match queue {
+            RenderQueue::Opaque | RenderQueue::AlphaTest => (BlendMode::Opaque, DepthCompare::Less, true),
+            RenderQueue::Transparent => (BlendMode::Alpha, DepthCompare::Less, false),
+            RenderQueue::Overlay => (BlendMode::Alpha, DepthCompare::Always, false),
+        };
+        RenderState {
+            queue,
+            blend,
+            cull: CullMode::None,
+            depth_compare,
+            depth_write,
+            alpha_cutoff: 0.5,
+        }
+    }
+
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_cull(mut self, cull: CullMode) -> Self {
+        self.cull = cull;
+        self
+    }
+
+    pub fn with_depth(mut self, depth_compare: DepthCompare, depth_write: bool) -> Self {
+        self.depth_compare = depth_compare;
+        self.depth_write = depth_write;
+        self
+    }
+
+    pub fn draw_parameters(&self) -> glium::DrawParameters<'static> {
+        glium::DrawParameters {
+            depth: glium::Depth {
+                test: self.depth_compare.depth_test(),
+                write: self.depth_write,
+                ..Default::default()
+            },
+            blend: self.blend.blend(),
+            backface_culling: self.cull.backface_culling(),
+            ..Default::default()
+        }
+    }
+}
+
+// what is known of an object when the draw order is decided
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SortKey {
+    pub queue: RenderQueue,
+    pub program: u64,
+    // identifies the material and the textures of the object
+    pub textures: u64,
+    // the model, so that the objects which can be instanced follow each other
+    pub model: u64,
+    // squared distance to the camera
+    pub distance: f32,
+}
+
+// opaque objects are grouped by program, textures then model to limit the state changes, and drawn
+// front to back within a group so that the depth test rejects the hidden pixels early
+// transparent and overlay objects are drawn back to front, whatever their program
+pub fn draw_order(a: &SortKey, b: &SortKey) -> Ordering {
+    let by_distance = a.distance.total_cmp(&b.distance);
+    return a.queue.cmp(&b.queue).then_with(|| {
+        if a.queue.is_opaque() {
+            a.program
+                .cmp(&b.program)
+                .then(a.textures.cmp(&b.textures))
+                .then(a.model.cmp(&b.model))
+                .then(by_distance)
+        } else {
+            by_distance.reverse()
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(queue: RenderQueue, program: u64, distance: f32) -> SortKey {
+        return SortKey {
+            queue,
+            program,
+            textures: 0,
+            model: 0,
+            distance,
+        };
+    }
+
+    #[test]
+    fn queues_come_in_order() {
+        let mut keys = [
+            key(RenderQueue::Overlay, 0, 1.0),
+            key(RenderQueue::Transparent, 0, 1.0),
+            key(RenderQueue::AlphaTest, 0, 1.0),
+            key(RenderQueue::Opaque, 0, 1.0),
+        ];
+        keys.sort_by(draw_order);
+        let queues: Vec<RenderQueue> = keys.iter().map(|k| k.queue).collect();
+        assert_eq!(
+            queues,
+            vec![
+                RenderQueue::Opaque,
+                RenderQueue::AlphaTest,
+                RenderQueue::Transparent,
+                RenderQueue::Overlay
+            ]
+        );
+    }
+
+    #[test]
+    fn opaque_front_to_back_transparent_back_to_front() {
+        let mut keys = [
+            key(RenderQueue::Opaque, 1, 9.0),
+            key(RenderQueue::Opaque, 1, 1.0),
+            key(RenderQueue::Opaque, 0, 4.0),
+            key(RenderQueue::Transparent, 1, 1.0),
+            key(RenderQueue::Transparent, 0, 9.0),
+        ];
+        keys.sort_by(draw_order);
+        let order: Vec<(u64, f32)> = keys.iter().map(|k| (k.program, k.distance)).collect();
+        assert_eq!(order, vec![(0, 4.0), (1, 1.0), (1, 9.0), (0, 9.0), (1, 1.0)]);
+    }
+
+    #[test]
+    fn transparent_defaults_keep_the_depth_buffer() {
+        let state = RenderState::for_queue(RenderQueue::Transparent);
+        assert!(!state.depth_write);
+        assert_eq!(state.blend, BlendMode::Alpha);
+        let overlay = RenderState::for_queue(RenderQueue::Overlay);
+        assert_eq!(overlay.depth_compare, DepthCompare::Always);
+    }
+}
@@ -6,30 +6,116 @@ use crate::input::MouseState;
 use crate::input::KeyboardState;
 
 
+use crate::background::draw_background;
+use crate::background::Background;
+use crate::bounds::RenderStats;
 use crate::camera::Camera;
+use crate::debug_draw::debug_variant;
+use crate::debug_draw::DebugDraw;
+use crate::debug_draw::DebugVertex;
+use crate::debug_view::debug_view_variant;
+use crate::debug_view::normal_lines;
+use crate::debug_view::DebugView;
+use crate::error::default_error_policy;
+use crate::error::handle_error;
+use crate::error::or_fallback;
+use crate::error::AssetKind;
+use crate::error::EngineError;
+use crate::error::ErrorAction;
+use crate::error::ErrorPolicy;
 use crate::graphic_component::load_model;
 use crate::graphic_component::load_shaders;
+use crate::graphic_component::missing_model;
 use crate::graphic_component::GraphicComponent;
 use crate::graphic_component::ObjectModel;
+use crate::instancing::batch_ranges;
+use crate::instancing::instanced_variant;
+use crate::instancing::supports_instancing;
+use crate::instancing::upload_instances;
+use crate::instancing::InstanceData;
+use crate::material::Material;
+use crate::mesh::Mesh;
+use crate::mesh::NormalMode;
+use crate::post_processing::run_post_processing;
+use crate::post_processing::PostContext;
+use crate::post_processing::PostEffect;
+use crate::post_processing::PostTargets;
+use crate::primitives::builtin_mesh;
+use crate::material::DEFAULT_MATERIAL;
+use crate::material::BLACK_TEXTURE;
+use crate::material::FLAT_NORMAL_TEXTURE;
+use crate::material::MISSING_TEXTURE;
+use crate::material::PBR_MATERIAL;
+use crate::material::WHITE_TEXTURE;
+use crate::environment::bind_environment;
+use crate::environment::Environment;
+use crate::tonemapping::tonemap_variant;
+use crate::light::SceneLights;
+use crate::lod::simplified_model_key;
+use crate::lod::LodModel;
+use crate::lod::LOD_FADE_KEYWORD;
+use crate::logging::ASSETS;
+use crate::logging::RENDER;
+use crate::logging::SYSTEMS;
+use crate::render_graph::FrameInfo;
+use crate::render_graph::RenderGraph;
+use crate::render_state::draw_order;
+use crate::render_state::SortKey;
+use crate::shadow::bind_shadows;
+use crate::shadow::plan_shadows;
+use crate::shadow::render_shadow_maps;
+use crate::shadow::shadow_variant;
+use crate::shadow::ShadowMaps;
+use crate::shadow::ShadowPlan;
+use crate::shadow::ShadowSettings;
+use crate::simplify::simplify;
+use crate::text::atlas_key;
+use crate::text::draw_texts;
+use crate::text::text_variant;
+use crate::text::FontAtlas;
+use crate::text::TextComponent;
+use crate::text::TextContext;
+use crate::texture::load_texture;
+use crate::texture::missing_texture;
+use crate::texture::texture_cache_key;
+use crate::texture::solid_texture;
+use crate::texture::Texture;
+use crate::texture::TextureSettings;
 use crate::transform::Transform;
+use crate::ui::compute_layout;
+use crate::ui::draw_ui_batches;
+use crate::ui::ui_batches;
+use crate::ui::ui_image_settings;
+use crate::ui::ui_variant;
+use crate::ui::UiNavigation;
+use crate::ui::UiNode;
+use crate::ui::UiScaling;
+use crate::ui::UiState;
+use crate::ui::UiWidget;
+use crate::uniforms::UniformBag;
 
 use glium::glutin::surface::WindowSurface;
+use glium::index::NoIndices;
+use glium::index::PrimitiveType;
 use glium::uniform;
+use glium::uniforms::UniformValue;
+use glium::texture::DepthTexture2d;
+use glium::texture::Texture2d;
 use glium::Display;
 use glium::Frame;
 use glium::Program;
 use glium::Surface;
+use glium::VertexBuffer;
 
 use glium::winit::event::WindowEvent;
 
-use glium::texture::RawImage2d;
-use glium::texture::Texture2d;
 
 use legion::storage::Component;
 use legion::world::World;
 use legion::world::WorldOptions;
 use legion::IntoQuery;
 use legion::Entity;
+use legion::EntityStore;
 use legion::systems::Resources;
 use legion::systems::Step::Systems;
 use legion::systems::Executor;
@@ -37,9 +123,22 @@ use legion::systems::Step;
 
 use legion::systems::ParallelRunnable;
 
-use image::io::Reader as ImageReader;
 
+use tracing::debug;
+use tracing::debug_span;
+use tracing::info;
+use tracing::info_span;
+use tracing::trace;
+use tracing::warn;
+
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::ops::Range;
 use std::path::Path;
 
 pub enum EventID {
@@ -74,11 +173,74 @@ pub struct Scene {
     // else, we fetch it in the files and add it to the scene models
     pub models: HashMap<String, ObjectModel>,
 
+    // meshes created from code, uploaded to models under their name when the scene is loaded
+    meshes: HashMap<String, Mesh>,
+
     // same thing as models except for shaders
-    // the first String is for the vertex shaders and the second one for fragment shaders
-    pub programs: HashMap<(String, String), Program>,
+    // programs are indexed by the cache key of their shader variant
+    pub programs: HashMap<u64, Program>,
+
+    pub textures: HashMap<String, Texture>,
+
+    // materials shared by the graphic components, indexed by name or by file path
+    pub materials: HashMap<String, Material>,
+
+    // light which reaches every surface regardless of the light components
+    pub ambient_light: [f32; 3],
+
+    pub shadow_settings: ShadowSettings,
+    shadow_maps: Option<ShadowMaps>,
+    // the matrices of the shadow maps rendered this frame, none when the shadow pass didn't run
+    shadow_plan: Option<ShadowPlan>,
+
+    // kept from the loading of the scene so that render targets can be created when drawing
+    display: Option<Display<WindowSurface>>,
+
+    environment_path: Option<String>,
+    environment: Option<Environment>,
+
+    // drawn behind the objects by the cameras which don't have their own
+    background: Background,
+    // whether the background lights the pbr materials when there is no environment
+    background_lighting: bool,
+    // textures of the backgrounds and of the post effects which couldn't be loaded, and the
+    // programs of the post effects which didn't compile, so that they are reported only once
+    failed_textures: HashSet<String>,
+    failed_programs: HashSet<u64>,
+
+    // the objects outside of the view of the camera are not submitted
+    frustum_culling: bool,
+    render_stats: RenderStats,
+
+    // the opaque objects sharing a model and a material are drawn with a single call
+    instancing: bool,
+    // per instance data of the batches of the last frame, see instancing::upload_instances
+    instance_buffer: Option<VertexBuffer<InstanceData>>,
+
+    // the intermediate targets of the post effects, the image of the scene belongs to the graph
+    post_targets: Option<PostTargets>,
+
+    // the passes which draw the scene, taken out of the scene while they run
+    render_graph: Option<RenderGraph>,
+
+    error_policy: ErrorPolicy,
 
-    pub textures: HashMap<String, Texture2d>,
+    // the shapes submitted by the systems, lent to them as a resource while they run
+    debug_draw: DebugDraw,
+    // the normals of the models for DebugView::Normals, built the first time they are shown
+    normal_lines: HashMap<String, VertexBuffer<DebugVertex>>,
+
+    // the fonts of the text components with their glyph atlas, the atlases are in the textures
+    fonts: HashMap<String, FontAtlas>,
+    // the fonts which couldn't be loaded, so that they are reported only once
+    failed_fonts: HashSet<String>,
+
+    // the layout, the pointer and the focus of the nodes of the interface
+    ui: UiState,
+
+    // the immediate mode interface built by the systems, lent to them as a resource
+    #[cfg(feature = "egui")]
+    egui_context: egui::Context,
 
     // the camera which will draw the scene next, if it is none, the scene is not rendered
     pub render_cam: Option<Camera>,
@@ -91,8 +253,38 @@ impl Scene {
             is_active: true,
             game_objects: HashMap::new(),
             models: HashMap::new(),
+            meshes: HashMap::new(),
             programs: HashMap::new(),
             textures: HashMap::new(),
+            materials: HashMap::from([
+                (DEFAULT_MATERIAL.to_string(), Material::default()),
+                (PBR_MATERIAL.to_string(), Material::pbr()),
+            ]),
+            ambient_light: [0.1, 0.1, 0.1],
+            shadow_settings: ShadowSettings::default(),
+            shadow_maps: None,
+            shadow_plan: None,
+            display: None,
+            environment_path: None,
+            environment: None,
+            background: Background::default(),
+            background_lighting: false,
+            failed_textures: HashSet::new(),
+            failed_programs: HashSet::new(),
+            frustum_culling: true,
+            render_stats: RenderStats::default(),
+            instancing: true,
+            instance_buffer: None,
+            post_targets: None,
+            render_graph: Some(RenderGraph::forward()),
+            error_policy: default_error_policy(),
+            debug_draw: DebugDraw::new(),
+            normal_lines: HashMap::new(),
+            fonts: HashMap::new(),
+            failed_fonts: HashSet::new(),
+            ui: UiState::new(),
+            #[cfg(feature = "egui")]
+            egui_context: egui::Context::default(),
             world: World::new(WorldOptions::default()),
             frame_steps : Vec::new(),
             triggered_steps : Vec::new(),
@@ -114,6 +306,7 @@ impl Scene {
 
     pub fn execute_frame_steps
         (&mut self, keyboard_state: &KeyboardState, mouse_state: &MouseState, window_event: &WindowEvent) {
+        let _span = debug_span!(target: SYSTEMS, "frame_steps", count = self.frame_steps.len()).entered();
         let mut resources = Resources::default();
         // TODO this is unsatisfactory, because the lifetime of WindowEvent will clearly
         // last until the last call of the execution and will never be used after that
@@ -128,15 +321,21 @@ impl Scene {
         resources.insert(window_event.clone());
         resources.insert(mouse_state.clone());
         resources.insert(keyboard_state.clone());
+        resources.insert(std::mem::take(&mut self.debug_draw));
+        resources.insert(self.ui.events());
+        #[cfg(feature = "egui")]
+        resources.insert(self.egui_context.clone());
         for step_key in self.frame_steps.iter_mut() {
             match self.step_dict.get_mut(step_key) {
                 Some(Systems(executor)) => executor.execute(&mut self.world, &mut resources),
-                None => println!("WARNING: {} is not a valid system", step_key),
+                None => warn!(target: SYSTEMS, "{} is not a valid system", step_key),
                 _ => (),
             };
         };
         let mut new_triggered_steps = resources.get_mut::<Vec<String>>().unwrap();
         self.triggered_steps.append(&mut new_triggered_steps);
+        drop(new_triggered_steps);
+        self.debug_draw = resources.remove::<DebugDraw>().unwrap_or_default();
     }
 
     // TODO same issues as execute_frame_steps
@@ -146,21 +345,28 @@ impl Scene {
     pub fn execute_triggered_steps 
         (&mut self, keyboard_state: &KeyboardState, mouse_state: &MouseState, window_event: &WindowEvent) {
         if Vec::len(&self.triggered_steps) > 0 {
+            let _span = debug_span!(target: SYSTEMS, "triggered_steps", steps = ?self.triggered_steps).entered();
             let mut resources = Resources::default();
             let new_triggered_steps = Vec::<String>::new();
             resources.insert(new_triggered_steps);
             resources.insert(window_event.clone());
             resources.insert(mouse_state.clone());
             resources.insert(keyboard_state.clone());
+            resources.insert(std::mem::take(&mut self.debug_draw));
+            resources.insert(self.ui.events());
+            #[cfg(feature = "egui")]
+            resources.insert(self.egui_context.clone());
             for step_key in self.triggered_steps.iter_mut() {
                 match self.step_dict.get_mut(step_key) {
                     Some(Systems(executor)) => executor.execute(&mut self.world, &mut resources),
-                    None => println!("WARNING: {} is not a valid system", step_key),
+                    None => warn!(target: SYSTEMS, "{} is not a valid system", step_key),
                     _ => (),
                 };
             };
             let new_triggered_steps = resources.get_mut::<Vec<String>>().unwrap();
             self.triggered_steps = new_triggered_steps.to_vec();
+            drop(new_triggered_steps);
+            self.debug_draw = resources.remove::<DebugDraw>().unwrap_or_default();
             self.execute_triggered_steps(keyboard_state, mouse_state, window_event);
         }
     }
@@ -180,58 +386,546 @@ impl Scene {
 
 
 
+    // registers a material under a name which graphic components can then use
+    // if a material with the same name already exists, it is overwritten
+    pub fn add_material(&mut self, name: String, material: Material) {
+        self.materials.insert(name, material);
+    }
+
     // TODO find out if it is possible to take &mut self as argument instead of getting everything
     // through by hand
+    // the errors are handed to the policy, which decides whether the missing assets are replaced
+    // by fallbacks or left out
+    #[allow(clippy::too_many_arguments)]
     pub fn load_graphic_component(
         gc: &GraphicComponent,
         display_clone: &Display<WindowSurface>,
         models: &mut HashMap<String, ObjectModel>,
-        programs: &mut HashMap<(String, String), Program>,
-        textures: &mut HashMap<String, Texture2d>,
+        programs: &mut HashMap<u64, Program>,
+        textures: &mut HashMap<String, Texture>,
+        materials: &mut HashMap<String, Material>,
+        policy: &ErrorPolicy,
     ) {
         // loads and adds the model corresponding to the gc of the go if said model hasn't already
         // been loaded, when improving performance, will need to check that
         if let Some(geometry) = &gc.model_path {
-            models
-                .entry(geometry.to_string())
-                .or_insert_with(|| load_model(Path::new(&geometry), display_clone).unwrap());
-        } else {
-            println!("Warning: object has graphic component but no model");
-        }
-
-        // same thing as models but with shaders
-        // TODO very flimsy thing going on here
-        // PERF, we are using the entire shader source as a key to the hashmap
-        // needless to explain why this is incredibly dumb
-        if let (Some(vertex_shader), Some(fragment_shader)) =
-            (&gc.vertex_shader_src, &gc.fragment_shader_src)
-        {
-            let program_key = (vertex_shader.clone(), fragment_shader.clone());
-            programs.entry(program_key).or_insert_with(|| {
-                load_shaders(vertex_shader, fragment_shader, display_clone).unwrap()
-            });
+            Self::load_model_entry(geometry, gc.normal_mode, display_clone, models, policy);
+            // the levels of detail are loaded the same way, or generated from the model
+            if let Some(group) = &gc.lod_group {
+                for level in &group.levels {
+                    if let LodModel::Path(path) = &level.model {
+                        Self::load_model_entry(path, gc.normal_mode, display_clone, models, policy);
+                    }
+                }
+                for ratio in group.simplified_ratios() {
+                    let key = simplified_model_key(geometry, ratio);
+                    if models.contains_key(&key) {
+                        continue;
+                    }
+                    let Some(source) = models.get(geometry) else {
+                        continue;
+                    };
+                    let mesh = simplify(&source.mesh, ratio);
+                    debug!(target: ASSETS, model = %key, triangles = mesh.indices.len() / 3, "level of detail generated");
+                    let model = ObjectModel::new(&key, mesh, display_clone);
+                    if let Some(model) = or_fallback(policy, model, || missing_model(display_clone)) {
+                        models.insert(key, model);
+                    }
+                }
+            }
         } else {
-            println!("Warning: object has graphic cock but no shaders")
+            warn!(target: ASSETS, "object has graphic component but no model");
         }
 
-        // same thing again but with textures
-        if let Some(texture_path) = &gc.texture_path {
-            // copied from the tutorial
-            let image = ImageReader::open(texture_path).unwrap().decode().unwrap().to_rgba8();
-            let image_dimensions = image.dimensions();
-            let image = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
-            let texture = Texture2d::new(display_clone, image).unwrap();
-            textures.insert(texture_path.to_string(), texture); 
-        } else {
-            println!("Warning: object has graphic cock but no textures")
+        // materials which aren't registered in the scene are looked for on the disk
+        if !materials.contains_key(&gc.material) {
+            let material = if Material::is_material_file(&gc.material) {
+                Material::load(&gc.material)
+            } else {
+                Err(EngineError::missing(AssetKind::Material, &gc.material))
+            };
+            match material {
+                Ok(material) => {
+                    materials.insert(gc.material.clone(), material);
+                }
+                Err(err) => {
+                    if handle_error(policy, &err) {
+                        materials.insert(gc.material.clone(), Material::default());
+                    }
+                }
+            }
+        }
+        let Some(material) = materials.get(&gc.material) else {
+            return;
+        };
+
+        // same thing as models but with shaders, every variant is only compiled once
+        let variant = material.shader_variant_for(&material.render_state_for(gc.render_queue));
+        if let Entry::Vacant(entry) = programs.entry(variant.cache_key()) {
+            match load_shaders(&variant, display_clone) {
+                Ok(program) => {
+                    entry.insert(program);
+                }
+                Err(err) => {
+                    // the default shaders take the place of the broken ones
+                    let fallback = Material::default().shader_variant();
+                    if handle_error(policy, &err) && fallback != variant {
+                        if let Ok(program) = load_shaders(&fallback, display_clone) {
+                            entry.insert(program);
+                        }
+                    }
+                }
+            }
+        }
+
+        // the opaque objects may be batched, which needs the instanced variant of the shaders
+        let render_state = material.render_state_for(gc.render_queue);
+        if render_state.queue.is_opaque() && gc.can_be_instanced() {
+            let instanced = instanced_variant(&variant);
+            if let Entry::Vacant(entry) = programs.entry(instanced.cache_key()) {
+                match load_shaders(&instanced, display_clone) {
+                    Ok(program) => {
+                        entry.insert(program);
+                    }
+                    // the objects are drawn one by one
+                    Err(err) => {
+                        handle_error(policy, &err);
+                    }
+                }
+            }
+        }
+
+        // the objects fading between two levels of detail dither them into each other
+        if gc.lod_group.as_ref().is_some_and(|group| group.fade > 0.0) {
+            let fading = variant.clone().with_keyword(LOD_FADE_KEYWORD);
+            if let Entry::Vacant(entry) = programs.entry(fading.cache_key()) {
+                match load_shaders(&fading, display_clone) {
+                    Ok(program) => {
+                        entry.insert(program);
+                    }
+                    // the levels switch at once
+                    Err(err) => {
+                        handle_error(policy, &err);
+                    }
+                }
+            }
+        }
+
+        // the models must provide every attribute the shaders of the material read, and the ones
+        // of the debug views
+        if let Some(source) = &gc.model_path {
+            let levels = gc
+                .lod_group
+                .iter()
+                .flat_map(|group| (0..group.levels.len()).map(|level| group.model_key(level, source)));
+            let required = [variant.cache_key(), debug_view_variant(false).cache_key()];
+            for key in std::iter::once(Cow::Borrowed(source.as_str())).chain(levels) {
+                for program in required.iter().filter_map(|key| programs.get(key)) {
+                    if let Some(model) = models.get_mut(key.as_ref()) {
+                        model.require_attributes(display_clone, program);
+                    }
+                }
+            }
+        }
+
+        // same thing again but with textures, which are created with the settings of the first
+        // uniform they are bound to
+        let bindings = material.texture_bindings().chain(gc.texture_bindings());
+        for (name, texture_path) in bindings {
+            let settings = material.texture_settings_for(name, &gc.texture_settings);
+            let key = texture_cache_key(texture_path, &settings);
+            if !textures.contains_key(key.as_ref()) {
+                let texture = load_texture(texture_path, &settings, display_clone);
+                if let Some(texture) = or_fallback(policy, texture, || missing_texture(display_clone)) {
+                    textures.insert(key.into_owned(), texture);
+                }
+            }
+        }
+    }
+
+    // loads a model from the builtin primitives or from a file, if it isn't there yet
+    fn load_model_entry(
+        path: &str,
+        normal_mode: NormalMode,
+        display: &Display<WindowSurface>,
+        models: &mut HashMap<String, ObjectModel>,
+        policy: &ErrorPolicy,
+    ) {
+        if models.contains_key(path) {
+            return;
+        }
+        // the primitives are generated instead of being read from a file
+        let model = match builtin_mesh(path) {
+            Some(mesh) => ObjectModel::new(path, mesh, display),
+            None => load_model(Path::new(path), normal_mode, display),
+        };
+        if let Some(model) = or_fallback(policy, model, || missing_model(display)) {
+            models.insert(path.to_string(), model);
+        }
+    }
+
+    // decides what happens when an asset fails to load or an object fails to be drawn, by default
+    // the missing assets are replaced by fallbacks (magenta texture, cube, default material)
+    pub fn set_error_policy(&mut self, policy: impl Fn(&EngineError) -> ErrorAction + 'static) {
+        self.error_policy = Box::new(policy);
+    }
+
+    // registers a mesh built in code, graphic components use it by giving its name as model path
+    pub fn add_mesh(&mut self, name: String, mesh: Mesh) {
+        self.models.remove(&name);
+        self.meshes.insert(name, mesh);
+    }
+
+    pub fn set_ambient_light(&mut self, ambient_light: [f32; 3]) {
+        self.ambient_light = ambient_light;
+    }
+
+    fn load_builtin_textures(
+        display: &Display<WindowSurface>,
+        textures: &mut HashMap<String, Texture>,
+        policy: &ErrorPolicy,
+    ) {
+        let builtin_textures = [
+            (WHITE_TEXTURE, [255u8, 255, 255, 255]),
+            (BLACK_TEXTURE, [0, 0, 0, 255]),
+            (FLAT_NORMAL_TEXTURE, [128, 128, 255, 255]),
+            (MISSING_TEXTURE, [255, 0, 255, 255]),
+        ];
+        for (name, pixel) in builtin_textures {
+            if textures.contains_key(name) {
+                continue;
+            }
+            match solid_texture(display, pixel) {
+                Ok(texture) => {
+                    textures.insert(name.to_string(), texture);
+                }
+                // there is nothing to fall back to, the materials using it won't be drawn
+                Err(err) => {
+                    handle_error(policy, &err);
+                }
+            }
         }
     }
 
+    // programs the engine itself uses for its passes
+    fn load_builtin_programs(
+        display: &Display<WindowSurface>,
+        programs: &mut HashMap<u64, Program>,
+        policy: &ErrorPolicy,
+    ) {
+        let debug_variants = [debug_variant(), debug_view_variant(false), debug_view_variant(true)];
+        let variants = [shadow_variant(), tonemap_variant(), text_variant(), ui_variant()]
+            .into_iter()
+            .chain(Background::shader_variants())
+            .chain(PostEffect::builtin_variants())
+            .chain(debug_variants);
+        for variant in variants {
+            if let Entry::Vacant(entry) = programs.entry(variant.cache_key()) {
+                match load_shaders(&variant, display) {
+                    Ok(program) => {
+                        entry.insert(program);
+                    }
+                    // the passes which need them are skipped
+                    Err(err) => {
+                        handle_error(policy, &err);
+                    }
+                }
+            }
+        }
+    }
+
+    // the equirectangular hdr image used for the reflections and the ambient lighting of the
+    // pbr materials, it is loaded along with the graphic components
+    pub fn set_environment(&mut self, path: String) {
+        self.environment_path = Some(path);
+        self.environment = None;
+    }
+
+    // the lighting which comes from the background is recomputed when the scene is next loaded
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+        if self.background_lighting && self.environment_path.is_none() {
+            self.environment = None;
+        }
+    }
+
+    // lights the pbr materials with the background of the scene, when no environment was given
+    // with set_environment, this works for every background but the cubemap skyboxes
+    pub fn set_background_lighting(&mut self, enabled: bool) {
+        self.background_lighting = enabled;
+        if self.environment_path.is_none() {
+            self.environment = None;
+        }
+    }
+
+    // the environment used for the lighting when it comes from the background
+    fn background_environment(&self, display: &Display<WindowSurface>) -> Result<Option<Environment>, EngineError> {
+        if let Some(image) = self.background.to_hdr_image() {
+            return Environment::from_image(image, display).map(Some);
+        }
+        match &self.background {
+            Background::Panorama { path, intensity } => {
+                let mut environment = Environment::load(path, display)?;
+                environment.intensity = *intensity;
+                return Ok(Some(environment));
+            }
+            Background::Skybox { .. } => {
+                warn!(target: ASSETS, "skyboxes can't light the scene, use a panorama or set_environment");
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        }
+    }
+
+    // loads the texture of a background in the texture cache if it isn't there yet
+    fn load_background_texture(&mut self, background: &Background, display: &Display<WindowSurface>) {
+        if let Some(path) = background.texture_path() {
+            self.load_texture_once(path, &background.texture_settings(), display);
+        }
+    }
+
+    // for the textures which are only known when drawing, a texture which failed to load isn't
+    // tried again
+    fn load_texture_once(&mut self, path: &str, settings: &TextureSettings, display: &Display<WindowSurface>) {
+        let key = texture_cache_key(path, settings);
+        if self.textures.contains_key(key.as_ref()) || self.failed_textures.contains(key.as_ref()) {
+            return;
+        }
+        let texture = load_texture(path, settings, display);
+        match or_fallback(&self.error_policy, texture, || missing_texture(display)) {
+            Some(texture) => {
+                self.textures.insert(key.into_owned(), texture);
+            }
+            None => {
+                self.failed_textures.insert(key.into_owned());
+            }
+        }
+    }
+
+    // loads the fonts of the text components and of the texts of the interface which don't have
+    // theirs yet, the texts of a font which failed to load are not drawn
+    fn load_fonts(&mut self) {
+        let mut paths: Vec<String> = <&TextComponent>::query().iter(&self.world).map(|text| text.font.clone()).collect();
+        for node in <&UiNode>::query().iter(&self.world) {
+            if let UiWidget::Text(text) = &node.widget {
+                paths.push(text.font.clone());
+            }
+        }
+        for path in paths {
+            if self.fonts.contains_key(&path) || self.failed_fonts.contains(&path) {
+                continue;
+            }
+            match FontAtlas::load(&path) {
+                Ok(atlas) => {
+                    self.fonts.insert(path, atlas);
+                }
+                Err(err) => {
+                    handle_error(&self.error_policy, &err);
+                    self.failed_fonts.insert(path);
+                }
+            }
+        }
+    }
+
+    // draws the text components of the screen or of the world, for the text passes of the graph
+    pub(crate) fn draw_texts<S: Surface>(
+        &mut self,
+        display: &Display<WindowSurface>,
+        target: &mut S,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+        screen: bool,
+    ) -> Result<(), EngineError> {
+        // the text components added since the scene was loaded
+        self.load_fonts();
+        let Some(program) = self.programs.get(&text_variant().cache_key()) else {
+            return Ok(());
+        };
+        let context = TextContext {
+            world: &self.world,
+            fonts: &mut self.fonts,
+            textures: &mut self.textures,
+            program,
+        };
+        self.render_stats.draw_calls += draw_texts(display, target, context, camera, width, height, screen)?;
+        return Ok(());
+    }
+
+    // lays out the visible nodes of the interface and draws them, for the ui pass of the graph
+    pub(crate) fn draw_ui<S: Surface>(
+        &mut self,
+        display: &Display<WindowSurface>,
+        target: &mut S,
+        width: u32,
+        height: u32,
+    ) -> Result<(), EngineError> {
+        let nodes: Vec<(Entity, UiNode)> = <(Entity, &UiNode)>::query()
+            .iter(&self.world)
+            .filter(|(_, node)| node.visible)
+            .map(|(entity, node)| (*entity, node.clone()))
+            .collect();
+        if nodes.is_empty() {
+            self.ui.set_layout(Vec::new());
+            return Ok(());
+        }
+        self.load_fonts();
+        for (_, node) in &nodes {
+            if let UiWidget::Image { texture, .. } = &node.widget {
+                self.load_texture_once(texture, &ui_image_settings(), display);
+            }
+        }
+
+        let by_entity: HashMap<Entity, &UiNode> = nodes.iter().map(|(entity, node)| (*entity, node)).collect();
+        let layout_nodes: Vec<_> = nodes.iter().map(|(entity, node)| node.layout_node(*entity)).collect();
+        let scale = self.ui.scaling.factor(width, height);
+        let fonts = &self.fonts;
+        let measure = |entity: Entity| match &by_entity[&entity].widget {
+            UiWidget::Text(text) => fonts.get(&text.font).map(|atlas| atlas.measure(text)).unwrap_or([0.0; 2]),
+            _ => [0.0; 2],
+        };
+        let rects = compute_layout(&layout_nodes, [width as f32, height as f32], scale, measure)?;
+        let targets = rects
+            .iter()
+            .map(|(entity, rect)| {
+                let node = by_entity[entity];
+                (*entity, *rect, node.interactive, node.focusable)
+            })
+            .collect();
+        self.ui.set_layout(targets);
+
+        let batches = ui_batches(&rects, &by_entity, &self.ui, &mut self.fonts, scale);
+        for (font, atlas) in self.fonts.iter_mut() {
+            if let Some(texture) = atlas.take_texture(display)? {
+                self.textures.insert(atlas_key(font), texture);
+            }
+        }
+        let Some(program) = self.programs.get(&ui_variant().cache_key()) else {
+            return Ok(());
+        };
+        self.render_stats.draw_calls += draw_ui_batches(display, target, &batches, &self.textures, program, width, height)?;
+        return Ok(());
+    }
+
+    // the layout of the interface in the last frame, and what the pointer and the focus are on
+    pub fn ui_state(&self) -> &UiState {
+        return &self.ui;
+    }
+
+    pub fn set_ui_scaling(&mut self, scaling: UiScaling) {
+        self.ui.scaling = scaling;
+    }
+
+    // the game calls it for every event of the window before the systems, the interface gets the
+    // events of the pointer from the state of the mouse
+    pub fn update_ui_input(&mut self, mouse_state: &MouseState) {
+        let position = [mouse_state.pos.0 as f32, mouse_state.pos.1 as f32];
+        self.ui.update_pointer(position, mouse_state.left_button_down);
+    }
+
+    // moves the focus of the interface, for the keyboard, see ui::keyboard_navigation, and the
+    // gamepads
+    pub fn navigate_ui(&mut self, navigation: UiNavigation) {
+        self.ui.navigate(navigation);
+    }
+
+    // the entity of an object of the scene, to attach the nodes of the interface to their parent
+    pub fn entity(&self, go: &GameObject) -> Option<Entity> {
+        return self.game_objects.get(&go.get_id()).copied();
+    }
+
+    // compiles the programs of the custom effects of the camera and loads the textures of its
+    // effects, the builtin effects are compiled with the scene
+    fn load_post_effects(&mut self, camera: &Camera, display: &Display<WindowSurface>) {
+        for effect in &camera.post_effects {
+            let variant = effect.shader_variant();
+            let key = variant.cache_key();
+            if !self.programs.contains_key(&key) && !self.failed_programs.contains(&key) {
+                match load_shaders(&variant, display) {
+                    Ok(program) => {
+                        self.programs.insert(key, program);
+                    }
+                    // the effect is skipped
+                    Err(err) => {
+                        handle_error(&self.error_policy, &err);
+                        self.failed_programs.insert(key);
+                    }
+                }
+            }
+            // the lookup tables and the masks hold data rather than colours
+            for path in effect.texture_paths() {
+                self.load_texture_once(path, &TextureSettings::default(), display);
+            }
+        }
+    }
+
+    pub fn set_frustum_culling(&mut self, enabled: bool) {
+        self.frustum_culling = enabled;
+    }
+
+    pub fn set_instancing(&mut self, enabled: bool) {
+        self.instancing = enabled;
+    }
+
+    // how many objects the last frame drew and culled, and with how many draw calls
+    pub fn render_stats(&self) -> RenderStats {
+        return self.render_stats;
+    }
+
+    // the shadow maps are reallocated when drawing if the resolution is changed
+    pub fn set_shadow_settings(&mut self, settings: ShadowSettings) {
+        self.shadow_settings = settings;
+    }
+
     pub fn load_all_gc(&mut self, display_ref: &Display<WindowSurface>) {
+        let _span = info_span!(target: ASSETS, "load_scene", scene = %self.name).entered();
+        self.display = Some(display_ref.clone());
+        Self::load_builtin_textures(display_ref, &mut self.textures, &self.error_policy);
+        Self::load_builtin_programs(display_ref, &mut self.programs, &self.error_policy);
+        for (name, mesh) in self.meshes.drain() {
+            let model = ObjectModel::new(&name, mesh, display_ref);
+            if let Some(model) = or_fallback(&self.error_policy, model, || missing_model(display_ref)) {
+                self.models.insert(name, model);
+            }
+        }
+        if let (Some(path), None) = (&self.environment_path, &self.environment) {
+            match Environment::load(path, display_ref) {
+                Ok(environment) => self.environment = Some(environment),
+                // without environment the pbr materials fall back to the ambient light
+                Err(err) => {
+                    handle_error(&self.error_policy, &err);
+                }
+            }
+        } else if self.environment_path.is_none() && self.environment.is_none() && self.background_lighting {
+            match self.background_environment(display_ref) {
+                Ok(environment) => self.environment = environment,
+                Err(err) => {
+                    handle_error(&self.error_policy, &err);
+                }
+            }
+        }
+        let background = self.background.clone();
+        self.load_background_texture(&background, display_ref);
         let mut gc_query = <&GraphicComponent>::query();
         gc_query.iter(&self.world).for_each(|gc| {
-            Self::load_graphic_component(gc, display_ref, &mut self.models, &mut self.programs, &mut self.textures)
+            Self::load_graphic_component(
+                gc,
+                display_ref,
+                &mut self.models,
+                &mut self.programs,
+                &mut self.textures,
+                &mut self.materials,
+                &self.error_policy,
+            )
         });
+        self.load_fonts();
+        info!(
+            target: ASSETS,
+            models = self.models.len(),
+            textures = self.textures.len(),
+            programs = self.programs.len(),
+            "scene loaded"
+        );
     }
 
     // user accessible function that will allow them to set the camera of a scene so that it may be
@@ -244,100 +938,597 @@ impl Scene {
         self.is_active
     }
 
+    // the passes which draw the scene, games add their own passes to it, see render_graph
+    pub fn render_graph_mut(&mut self) -> &mut RenderGraph {
+        return self.render_graph.get_or_insert_with(RenderGraph::forward);
+    }
+
+    pub fn set_render_graph(&mut self, graph: RenderGraph) {
+        self.render_graph = Some(graph);
+    }
+
+    // the lines, shapes and texts drawn over the scene, the systems get it as a resource
+    // nothing is drawn without the debug-draw feature
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        return &mut self.debug_draw;
+    }
+
+    // the context of the egui interface, the systems get a clone of it as a resource and build
+    // their panels with it, see egui_integration
+    #[cfg(feature = "egui")]
+    pub fn egui_context(&self) -> &egui::Context {
+        return &self.egui_context;
+    }
+
+    // reports an error through the policy of the scene, for the passes of the render graph
+    pub fn report_error(&self, err: &EngineError) {
+        handle_error(&self.error_policy, err);
+    }
+
     // will draw all active objects with active graphic components
     // we assume that all objects have at most one graphic component
     pub fn draw_scene(&mut self, mut target: Frame, camera: &Camera) {
-        //println!("drawing scene");
-        // refreshes the background colour
-        target.clear_color_and_depth((0.0, 0.0, 1.0, 1.0), 1.0);
+        self.render_scene(&mut target, camera);
+        self.finish_frame(target);
+    }
 
-        // TODO set up lights properly (can wait)
-        let light = [0.0, 0.0, 0.0f32];
+    // draws the scene without presenting the frame, so that something can be drawn over it
+    pub fn render_scene(&mut self, target: &mut Frame, camera: &Camera) {
+        let _span = debug_span!(target: RENDER, "draw_scene", scene = %self.name).entered();
+        let (width, height) = target.get_dimensions();
+        let can_tonemap = self.programs.contains_key(&tonemap_variant().cache_key());
+        let background = self.frame_background(camera);
+        self.shadow_plan = None;
 
-        // parameters, not 100% what they do
-        let params = glium::DrawParameters {
-            depth: glium::Depth {
-                test: glium::draw_parameters::DepthTest::IfLess,
-                write: true,
-                ..Default::default()
-            },
-            ..Default::default()
+        match self.display.clone() {
+            Some(display) => {
+                self.load_background_texture(&background, &display);
+                self.load_post_effects(camera, &display);
+                // the scene goes through an offscreen target when its image is processed before
+                // being presented, the tonemapping is the first of the passes
+                let info = FrameInfo {
+                    camera,
+                    width,
+                    height,
+                    offscreen: (camera.hdr || !camera.post_effects.is_empty()) && can_tonemap,
+                };
+                let mut graph = self.render_graph.take().unwrap_or_else(RenderGraph::forward);
+                if let Err(err) = graph.execute(self, &display, target, &info) {
+                    handle_error(&self.error_policy, &err);
+                }
+                self.render_graph = Some(graph);
+            }
+            // nothing was loaded
+            None => target.clear_color_and_depth(background.clear_color(), 1.0),
+        }
+    }
+
+    // presents the frame drawn by render_scene
+    pub fn finish_frame(&self, target: Frame) {
+        if let Err(err) = target.finish() {
+            handle_error(&self.error_policy, &err.into());
+        }
+    }
+
+    // the background of the camera, or the one of the scene
+    pub(crate) fn frame_background(&self, camera: &Camera) -> Background {
+        return camera.background.clone().unwrap_or_else(|| self.background.clone());
+    }
+
+    // renders the depth of the scene as seen from the lights which cast shadows, the plan is kept
+    // for the objects which receive them
+    pub(crate) fn render_shadows(&mut self, camera: &Camera, width: u32, height: u32) {
+        let lights = SceneLights::gather(&self.world, self.ambient_light);
+        let view = camera.view_matrix();
+        let perspective = camera.perspective_matrix(width, height);
+        let shadow_plan = plan_shadows(&lights, camera, view, perspective, &self.shadow_settings);
+        if let Some(display) = &self.display {
+            let outdated = self
+                .shadow_maps
+                .as_ref()
+                .map(|maps| maps.resolution != self.shadow_settings.resolution)
+                .unwrap_or(true);
+            if outdated {
+                // without maps the scene is drawn unshadowed
+                self.shadow_maps = match ShadowMaps::new(display, &self.shadow_settings) {
+                    Ok(maps) => Some(maps),
+                    Err(err) => {
+                        handle_error(&self.error_policy, &err);
+                        None
+                    }
+                };
+            }
+            if let (Some(maps), Some(program)) = (
+                &self.shadow_maps,
+                self.programs.get(&shadow_variant().cache_key()),
+            ) {
+                let result = render_shadow_maps(display, &shadow_plan, maps, &self.world, &self.models, program);
+                if let Err(err) = result {
+                    handle_error(&self.error_policy, &err);
+                }
+            }
+        }
+        self.shadow_plan = Some(shadow_plan);
+    }
+
+    // runs the post effects of the camera on the offscreen image of the scene, the tonemapping
+    // included, and draws the result on the surface
+    pub(crate) fn post_process<S: Surface>(
+        &mut self,
+        display: &Display<WindowSurface>,
+        surface: &mut S,
+        camera: &Camera,
+        color: &Texture2d,
+        depth: &DepthTexture2d,
+    ) -> Result<(), EngineError> {
+        let (width, height) = color.dimensions();
+        // the targets are taken out of the scene while they are drawn into
+        let mut post_targets = match self.post_targets.take() {
+            Some(targets) if targets.dimensions() == (width, height) => targets,
+            _ => PostTargets::new(display, width, height)?,
+        };
+        let context = PostContext {
+            programs: &self.programs,
+            textures: &self.textures,
+            depth,
+            projection: camera.perspective_matrix(width, height),
+            tonemapping: camera.hdr.then_some((camera.tonemapping, camera.exposure)),
         };
+        let result = run_post_processing(display, surface, color, &camera.post_effects, &mut post_targets, &context);
+        self.post_targets = Some(post_targets);
+        return result;
+    }
+
+    // fills the pixels the objects left empty, after them so that the covered pixels are skipped
+    fn draw_background<S: Surface>(
+        &self,
+        target: &mut S,
+        background: &Background,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+    ) {
+        let settings = background.texture_settings();
+        let texture = background
+            .texture_path()
+            .and_then(|path| self.textures.get(texture_cache_key(path, &settings).as_ref()));
+        // the skyboxes which couldn't be loaded are replaced by the 2d missing texture, which
+        // can't be sampled as a cubemap, so they are drawn as a magenta sky instead
+        let missing_skybox = Background::Gradient {
+            top: [1.0, 0.0, 1.0],
+            bottom: [1.0, 0.0, 1.0],
+        };
+        let background = match (background, texture) {
+            (Background::Skybox { .. }, Some(texture)) if !texture.is_cubemap() => &missing_skybox,
+            (Background::Skybox { .. } | Background::Panorama { .. }, None) => return,
+            _ => background,
+        };
+        // the solid colours were drawn when the target was cleared
+        let Some(variant) = background.shader_variant() else {
+            return;
+        };
+        let Some(program) = self.programs.get(&variant.cache_key()) else {
+            return;
+        };
+        let view = camera.view_matrix();
+        let perspective = camera.perspective_matrix(width, height);
+        let environment = self.environment.as_ref();
+        if let Err(err) = draw_background(target, background, program, texture, environment, view, perspective) {
+            handle_error(&self.error_policy, &err);
+        }
+    }
+
+    // draws the objects, lit and shadowed, and the background, on a surface which has already
+    // been cleared, the shadows are the ones of the shadow pass of this frame
+    pub(crate) fn draw_geometry<S: Surface>(
+        &mut self,
+        target: &mut S,
+        camera: &Camera,
+        background: &Background,
+        width: u32,
+        height: u32,
+    ) {
+        let lights = SceneLights::gather(&self.world, self.ambient_light);
+        let camera_position: [f32; 3] = camera.transform.get_position().into();
 
         // computes the camera's veiw matrix
         let view = camera.view_matrix();
 
         // computes the perspective matrix
-        let perspective = {
-            let (width, height) = target.get_dimensions();
-            let aspect_ratio = height as f32 / width as f32;
-
-            let fov: f32 = 1.0;
-            let zfar = 1024.0;
-            let znear = 0.1;
-
-            let f = 1.0 / (fov / 2.0).tan();
-            
-
-            [
-                [f * aspect_ratio, 0.0, 0.0, 0.0],
-                [0.0, f, 0.0, 0.0],
-                [0.0, 0.0,  (zfar + znear) / (zfar - znear), 1.0],
-                [0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0],
-            ]
+        let perspective = camera.perspective_matrix(width, height);
+
+        // the objects are drawn queue by queue, see render_state::draw_order
+        let frustum = camera.frustum(width, height);
+        let mut stats = RenderStats::default();
+        let mut draw_list = Vec::with_capacity(self.game_objects.len());
+        for entity in self.game_objects.values() {
+            let Ok(go_entry) = self.world.entry_ref(*entity) else {
+                continue;
+            };
+            let (Ok(gc), Ok(transform)) = (
+                go_entry.get_component::<GraphicComponent>(),
+                go_entry.get_component::<Transform>(),
+            ) else {
+                continue;
+            };
+            let Some(material) = self.materials.get(&gc.material) else {
+                continue;
+            };
+            let model = gc.model_path.as_ref().and_then(|path| self.models.get(path));
+            let (Some(model), true) = (model, gc.is_active()) else {
+                continue;
+            };
+            // the sphere is the cheaper test, the box is tighter for the long objects
+            if self.frustum_culling {
+                let matrix = transform.uniform_matrix();
+                let visible = frustum.intersects_sphere(&model.bounding_sphere.transformed(&matrix))
+                    && frustum.intersects_aabb(&model.aabb.transformed(&matrix));
+                if !visible {
+                    stats.culled += 1;
+                    continue;
+                }
+            }
+            // the level of detail is picked from the bounds of the model_path, while an object
+            // fades between two levels both are drawn
+            let lods = match &gc.lod_group {
+                Some(group) => {
+                    let sphere = model.bounding_sphere.transformed(&transform.uniform_matrix());
+                    let value = group.metric_value(&sphere, camera_position, camera.fov as f32);
+                    let Some(selection) = group.select(value) else {
+                        stats.culled += 1;
+                        continue;
+                    };
+                    let fading = selection.is_fading();
+                    let level = (Some(selection.level), fading.then(|| selection.fade_uniform(false)));
+                    let next = selection.next.filter(|_| fading);
+                    let next = next.map(|next| (Some(next), Some(selection.fade_uniform(true))));
+                    std::iter::once(level).chain(next)
+                }
+                None => std::iter::once((None, None)).chain(None),
+            };
+            stats.drawn += 1;
+            let render_state = material.render_state_for(gc.render_queue);
+            let position: [f32; 3] = transform.get_position().into();
+            let offset = [0, 1, 2].map(|i| position[i] - camera_position[i]);
+            for (lod_level, lod_fade) in lods {
+                let mut variant = material.shader_variant_for(&render_state);
+                if lod_fade.is_some() {
+                    variant = variant.with_keyword(LOD_FADE_KEYWORD);
+                }
+                let key = SortKey {
+                    queue: render_state.queue,
+                    program: variant.cache_key(),
+                    textures: texture_key(gc),
+                    model: hash_key(gc.model_key(lod_level).as_ref()),
+                    distance: offset.iter().map(|x| x * x).sum(),
+                };
+                // the transparent objects must keep their order and the fading ones have their
+                // own uniform, they are never batched
+                let batch = self.instancing
+                    && render_state.queue.is_opaque()
+                    && gc.can_be_instanced()
+                    && lod_fade.is_none();
+                draw_list.push(DrawItem {
+                    key,
+                    batch: batch.then(|| batch_key(&key, gc)),
+                    entity: *entity,
+                    lod_level,
+                    lod_fade,
+                });
+            }
+        }
+        draw_list.sort_by(|a, b| draw_order(&a.key, &b.key));
+        trace!(target: RENDER, drawn = stats.drawn, culled = stats.culled, "culling");
+        let view_mode = camera.debug_view;
+        if view_mode == DebugView::Normals {
+            self.load_normal_lines(&draw_list);
+        }
+
+        // the matrices and colours of every batch go into a single buffer, each batch draws a
+        // slice of it
+        let batch_keys: Vec<Option<u64>> = draw_list.iter().map(|item| item.batch).collect();
+        let batches = batch_ranges(&batch_keys);
+        let mut instances = Vec::new();
+        let mut instance_ranges = Vec::with_capacity(batches.len());
+        for batch in &batches {
+            if batch.len() < 2 {
+                instance_ranges.push(None);
+                continue;
+            }
+            let start = instances.len();
+            for item in &draw_list[batch.clone()] {
+                let Ok(go_entry) = self.world.entry_ref(item.entity) else {
+                    continue;
+                };
+                if let (Ok(gc), Ok(transform)) = (
+                    go_entry.get_component::<GraphicComponent>(),
+                    go_entry.get_component::<Transform>(),
+                ) {
+                    instances.push(InstanceData {
+                        i_matrix: transform.uniform_matrix(),
+                        i_color: gc.color,
+                    });
+                }
+            }
+            instance_ranges.push(Some(start..instances.len()));
+        }
+        let uploaded = match &self.display {
+            Some(display) => upload_instances(&mut self.instance_buffer, display, &instances)
+                .map_err(|err| handle_error(&self.error_policy, &err))
+                .is_ok(),
+            None => false,
         };
+        if !uploaded {
+            instance_ranges.fill(None);
+        }
 
         // we need the game object in order to draw the object because that is where its
         // transform is stored
-        let mut draw_component = |gc: &GraphicComponent, obj_transform: &Transform| {
-            //let go_entry = self.world.entry_ref(go.entity).unwrap();
-            //let gc = go_entry.get_component::<GraphicComponent>().unwrap();
-            if gc.is_active() && gc.can_be_drawn() {
-                // TODO this is very unsatisfactory, need to find some way to not have to use clones
-                let program_key = &(
-                    gc.vertex_shader_src.clone().unwrap(),
-                    gc.fragment_shader_src.clone().unwrap(),
-                );
-                let object_geometry = self.models.get(gc.model_path.as_ref().unwrap()).unwrap();
-                let program = self.programs.get(program_key).unwrap();
-                let texture = self.textures.get(gc.texture_path.as_ref().unwrap()).unwrap();
-
-                let vertices = &object_geometry.vertices;
-                let indices = &object_geometry.indices;
-
-                let matrix = obj_transform.uniform_matrix();
-
-                //println!("drawing object");
-                target
-                    .draw(
-                        vertices,
-                        indices,
-                        &program,
-                        &uniform! {
-                            matrix: matrix,
-                            view: view, 
-                            u_light: light, 
-                            perspective: perspective, 
-                            tex: texture,
-                            brightness: gc.brightness,
-                        },
-                        &params,
-                    )
-                    .unwrap();
+        // with a range of the instance buffer, the whole batch is drawn and the transform is
+        // ignored, NotInstanced is returned when the batch must be drawn one object at a time
+        // the layer is the debug view the object is drawn with, see DebugView::layers
+        let draw_component = |target: &mut S,
+                              gc: &GraphicComponent,
+                              obj_transform: &Transform,
+                              item: &DrawItem,
+                              instances: Option<Range<usize>>,
+                              layer: DebugView|
+         -> DrawOutcome {
+            if !gc.is_active() || !gc.can_be_drawn() {
+                return DrawOutcome::Skipped;
+            }
+            // the assets which failed to load have already been reported
+            let Some(material) = self.materials.get(&gc.material) else {
+                return DrawOutcome::Skipped;
+            };
+            let render_state = material.render_state_for(gc.render_queue);
+            let debug_index = layer.shader_index();
+            let variant = match debug_index {
+                Some(_) => debug_view_variant(instances.is_some()),
+                None => {
+                    let mut variant = material.shader_variant_for(&render_state);
+                    if instances.is_some() {
+                        variant = instanced_variant(&variant);
+                    }
+                    if item.lod_fade.is_some() {
+                        variant = variant.with_keyword(LOD_FADE_KEYWORD);
+                    }
+                    variant
+                }
+            };
+            let Some(object_geometry) = self.models.get(gc.model_key(item.lod_level).as_ref()) else {
+                return DrawOutcome::Skipped;
+            };
+            let Some(program) = self.programs.get(&variant.cache_key()) else {
+                return match instances {
+                    Some(_) => DrawOutcome::NotInstanced,
+                    None => DrawOutcome::Skipped,
+                };
+            };
+            let slice = match instances {
+                Some(range) if supports_instancing(program) => {
+                    match self.instance_buffer.as_ref().and_then(|buffer| buffer.slice(range)) {
+                        Some(slice) => Some(slice),
+                        None => return DrawOutcome::NotInstanced,
+                    }
+                }
+                Some(_) => return DrawOutcome::NotInstanced,
+                None => None,
+            };
+
+            let vertices = &object_geometry.vertices;
+            let indices = &object_geometry.indices;
+
+            // the engine's uniforms come first so that materials may override them
+            let mut uniforms = UniformBag::new();
+            if slice.is_none() {
+                uniforms.set("matrix", UniformValue::Mat4(obj_transform.uniform_matrix()));
+                uniforms.set("u_object_color", UniformValue::Vec4(gc.color));
+            }
+            uniforms.set("view", UniformValue::Mat4(view));
+            uniforms.set("perspective", UniformValue::Mat4(perspective));
+            uniforms.set("u_camera_position", UniformValue::Vec3(camera_position));
+            if let Some(fade) = item.lod_fade {
+                uniforms.set("u_lod_fade", UniformValue::Float(fade));
+            }
+            lights.bind(&mut uniforms);
+            if let (Some(maps), Some(shadow_plan)) = (&self.shadow_maps, &self.shadow_plan) {
+                bind_shadows(shadow_plan, maps, &self.shadow_settings, gc.receives_shadows, &mut uniforms);
+            }
+            bind_environment(self.environment.as_ref(), &self.textures, &mut uniforms);
+            material.bind(&gc.material_overrides, &gc.texture_settings, &self.textures, &mut uniforms);
+            uniforms.set("u_lighting_only", UniformValue::Bool(layer == DebugView::LightingOnly));
+            let mut params = render_state.draw_parameters();
+            if let Some(index) = debug_index {
+                uniforms.set("u_debug_view", UniformValue::SignedInt(index));
+                uniforms.set("u_debug_color", UniformValue::Vec4(layer.debug_color()));
+                uniforms.set("u_depth_range", UniformValue::Vec2([camera.znear, camera.zfar]));
+                params = layer.draw_parameters(params);
             }
+
+            let result = match &slice {
+                Some(slice) => match slice.per_instance() {
+                    Ok(per_instance) => target.draw((vertices, per_instance), indices, program, &uniforms, &params),
+                    // the context can't instance, which only happens with very old drivers
+                    Err(_) => return DrawOutcome::NotInstanced,
+                },
+                None => target.draw(vertices, indices, program, &uniforms, &params),
+            };
+            if let Err(err) = result {
+                handle_error(&self.error_policy, &err.into());
+                return DrawOutcome::Skipped;
+            }
+            return DrawOutcome::Drawn;
         };
 
-        let gc_query = <&GraphicComponent>::query();
+        for layer in view_mode.layers() {
+            // the debug views which ignore the materials go on a plain background
+            let mut background_drawn = !layer.draws_shaded();
+            for (batch, instances) in batches.iter().zip(&instance_ranges) {
+                // the background goes behind the opaque objects and the transparent ones blend
+                // over it
+                if !background_drawn && !draw_list[batch.start].key.queue.is_opaque() {
+                    self.draw_background(target, background, camera, width, height);
+                    background_drawn = true;
+                }
+                let mut instances = instances.clone();
+                for item in &draw_list[batch.clone()] {
+                    let Ok(go_entry) = self.world.entry_ref(item.entity) else {
+                        continue;
+                    };
+                    if let (Ok(gc), Ok(transform)) = (
+                        go_entry.get_component::<GraphicComponent>(),
+                        go_entry.get_component::<Transform>(),
+                    ) {
+                        // every object of the batch shares the material and model of the first one
+                        if let Some(range) = instances.take() {
+                            let outcome = draw_component(target, gc, transform, item, Some(range), layer);
+                            if outcome != DrawOutcome::NotInstanced {
+                                if outcome == DrawOutcome::Drawn {
+                                    stats.draw_calls += 1;
+                                }
+                                break;
+                            }
+                        }
+                        if draw_component(target, gc, transform, item, None, layer) == DrawOutcome::Drawn {
+                            stats.draw_calls += 1;
+                        }
+                    }
+                }
+            }
+            if !background_drawn {
+                self.draw_background(target, background, camera, width, height);
+            }
+        }
+        if view_mode == DebugView::Normals {
+            stats.draw_calls += self.draw_normal_lines(target, &draw_list, view, perspective);
+        }
+        trace!(target: RENDER, draw_calls = stats.draw_calls, "geometry drawn");
+        self.render_stats = stats;
+    }
 
-        for (_, entity) in &self.game_objects {
-            let go_entry = self.world.entry(*entity).unwrap();
-            let gc_res = go_entry.get_component::<GraphicComponent>();
-            let transform_res = go_entry.get_component::<Transform>();
-            if let (Ok(gc), Ok(transform)) = (gc_res, transform_res) {
-                draw_component(gc, transform);
+    // builds the lines of the normals of the models in the draw list which don't have them yet
+    fn load_normal_lines(&mut self, draw_list: &[DrawItem]) {
+        let Some(display) = &self.display else {
+            return;
+        };
+        for item in draw_list {
+            let Ok(go_entry) = self.world.entry_ref(item.entity) else {
+                continue;
+            };
+            let Ok(gc) = go_entry.get_component::<GraphicComponent>() else {
+                continue;
+            };
+            let key = gc.model_key(item.lod_level);
+            if self.normal_lines.contains_key(key.as_ref()) {
+                continue;
+            }
+            let Some(model) = self.models.get(key.as_ref()) else {
+                continue;
+            };
+            let lines = normal_lines(&model.mesh, model.bounding_sphere.radius);
+            match VertexBuffer::new(display, &lines) {
+                Ok(buffer) => {
+                    self.normal_lines.insert(key.into_owned(), buffer);
+                }
+                Err(err) => {
+                    handle_error(&self.error_policy, &EngineError::upload("normal lines", err));
+                }
             }
         }
+    }
 
-        target.finish().unwrap();
+    // draws the normals of the objects of the draw list over them, returns the number of draw calls
+    fn draw_normal_lines<S: Surface>(
+        &self,
+        target: &mut S,
+        draw_list: &[DrawItem],
+        view: [[f32; 4]; 4],
+        perspective: [[f32; 4]; 4],
+    ) -> usize {
+        let Some(program) = self.programs.get(&debug_variant().cache_key()) else {
+            return 0;
+        };
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+                write: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut draw_calls = 0;
+        for item in draw_list {
+            let Ok(go_entry) = self.world.entry_ref(item.entity) else {
+                continue;
+            };
+            let (Ok(gc), Ok(transform)) = (
+                go_entry.get_component::<GraphicComponent>(),
+                go_entry.get_component::<Transform>(),
+            ) else {
+                continue;
+            };
+            let Some(lines) = self.normal_lines.get(gc.model_key(item.lod_level).as_ref()) else {
+                continue;
+            };
+            if lines.len() == 0 {
+                continue;
+            }
+            let uniforms = uniform! {
+                matrix: transform.uniform_matrix(),
+                view: view,
+                perspective: perspective,
+            };
+            let result = target.draw(lines, NoIndices(PrimitiveType::LinesList), program, &uniforms, &params);
+            if let Err(err) = result {
+                handle_error(&self.error_policy, &err.into());
+            }
+            draw_calls += 1;
+        }
+        return draw_calls;
     }
 }
+
+// an object in the draw list, the objects fading between two levels of detail have one for each
+struct DrawItem {
+    key: SortKey,
+    batch: Option<u64>,
+    entity: Entity,
+    // the level of the lod group of the component, the model_path without group
+    lod_level: Option<usize>,
+    // the value of u_lod_fade while the object fades between two levels
+    lod_fade: Option<f32>,
+}
+
+// what became of an object given to draw_component, only the drawn ones count as draw calls
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum DrawOutcome {
+    Drawn,
+    // inactive, or an asset it needs is missing or failed to draw, which was already reported
+    Skipped,
+    // the batch must be drawn one object at a time
+    NotInstanced,
+}
+
+// groups the objects which bind the same textures, from their material and their overrides
+fn texture_key(gc: &GraphicComponent) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    gc.material.hash(&mut hasher);
+    for (name, path) in gc.texture_bindings() {
+        name.hash(&mut hasher);
+        path.hash(&mut hasher);
+    }
+    return hasher.finish();
+}
+
+fn hash_key<T: Hash>(value: T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    return hasher.finish();
+}
+
+// the objects with the same key are drawn with a single instanced call, they share their
+// program, textures, model and queue and only differ by their transform and colour
+fn batch_key(key: &SortKey, gc: &GraphicComponent) -> u64 {
+    return hash_key((key.queue, key.program, key.textures, key.model, gc.receives_shadows));
+}
@@ -0,0 +1,645 @@
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use glium::backend::Facade;
+use glium::program::ProgramCreationError;
+use glium::program::ProgramCreationInput;
+use glium::program::ShaderType;
+use glium::Program;
+
+// shaders shipped with the engine, they are referenced with paths starting with "builtin/" so
+// that games don't need to carry the engine's assets folder around
+const BUILTIN_SHADERS: &[(&str, &str)] = &[
+    (
+        "builtin/vertex_shader.glsl",
+        include_str!("../assets/shaders/vertex_shader.glsl"),
+    ),
+    (
+        "builtin/fragment_shader.glsl",
+        include_str!("../assets/shaders/fragment_shader.glsl"),
+    ),
+    (
+        "builtin/lighting.glsl",
+        include_str!("../assets/shaders/lighting.glsl"),
+    ),
+    (
+        "builtin/lights.glsl",
+        include_str!("../assets/shaders/lights.glsl"),
+    ),
+    (
+        "builtin/environment.glsl",
+        include_str!("../assets/shaders/environment.glsl"),
+    ),
+    (
+        "builtin/normal_mapping.glsl",
+        include_str!("../assets/shaders/normal_mapping.glsl"),
+    ),
+    (
+        "builtin/lod_fade.glsl",
+        include_str!("../assets/shaders/lod_fade.glsl"),
+    ),
+    (
+        "builtin/pbr_fragment.glsl",
+        include_str!("../assets/shaders/pbr_fragment.glsl"),
+    ),
+    (
+        "builtin/fullscreen_vertex.glsl",
+        include_str!("../assets/shaders/fullscreen_vertex.glsl"),
+    ),
+    (
+        "builtin/tonemap_fragment.glsl",
+        include_str!("../assets/shaders/tonemap_fragment.glsl"),
+    ),
+    (
+        "builtin/bloom_fragment.glsl",
+        include_str!("../assets/shaders/bloom_fragment.glsl"),
+    ),
+    (
+        "builtin/ssao_fragment.glsl",
+        include_str!("../assets/shaders/ssao_fragment.glsl"),
+    ),
+    (
+        "builtin/fxaa_fragment.glsl",
+        include_str!("../assets/shaders/fxaa_fragment.glsl"),
+    ),
+    (
+        "builtin/color_grading_fragment.glsl",
+        include_str!("../assets/shaders/color_grading_fragment.glsl"),
+    ),
+    (
+        "builtin/vignette_fragment.glsl",
+        include_str!("../assets/shaders/vignette_fragment.glsl"),
+    ),
+    (
+        "builtin/gamma_fragment.glsl",
+        include_str!("../assets/shaders/gamma_fragment.glsl"),
+    ),
+    (
+        "builtin/background_vertex.glsl",
+        include_str!("../assets/shaders/background_vertex.glsl"),
+    ),
+    (
+        "builtin/background_fragment.glsl",
+        include_str!("../assets/shaders/background_fragment.glsl"),
+    ),
+    (
+        "builtin/cubemap_face_fragment.glsl",
+        include_str!("../assets/shaders/cubemap_face_fragment.glsl"),
+    ),
+    (
+        "builtin/debug_vertex.glsl",
+        include_str!("../assets/shaders/debug_vertex.glsl"),
+    ),
+    (
+        "builtin/debug_fragment.glsl",
+        include_str!("../assets/shaders/debug_fragment.glsl"),
+    ),
+    (
+        "builtin/debug_view.glsl",
+        include_str!("../assets/shaders/debug_view.glsl"),
+    ),
+    (
+        "builtin/debug_view_fragment.glsl",
+        include_str!("../assets/shaders/debug_view_fragment.glsl"),
+    ),
+    (
+        "builtin/text_vertex.glsl",
+        include_str!("../assets/shaders/text_vertex.glsl"),
+    ),
+    (
+        "builtin/text_fragment.glsl",
+        include_str!("../assets/shaders/text_fragment.glsl"),
+    ),
+    (
+        "builtin/ui_fragment.glsl",
+        include_str!("../assets/shaders/ui_fragment.glsl"),
+    ),
+    (
+        "builtin/egui_vertex.glsl",
+        include_str!("../assets/shaders/egui_vertex.glsl"),
+    ),
+    (
+        "builtin/egui_fragment.glsl",
+        include_str!("../assets/shaders/egui_fragment.glsl"),
+    ),
+    (
+        "builtin/shadow_vertex.glsl",
+        include_str!("../assets/shaders/shadow_vertex.glsl"),
+    ),
+    (
+        "builtin/shadow_fragment.glsl",
+        include_str!("../assets/shaders/shadow_fragment.glsl"),
+    ),
+];
+
+pub const DEFAULT_VERTEX_SHADER: &str = "builtin/vertex_shader.glsl";
+pub const DEFAULT_FRAGMENT_SHADER: &str = "builtin/fragment_shader.glsl";
+pub const PBR_FRAGMENT_SHADER: &str = "builtin/pbr_fragment.glsl";
+
+// name used in the line map for the lines we inject ourselves (defines and keywords)
+const INJECTED_FILE: &str = "<injected>";
+
+#[derive(Debug, Clone)]
+pub enum ShaderError {
+    Io { path: String, message: String },
+    IncludeCycle { path: String },
+    MalformedInclude { path: String, line: usize },
+    Compile { stage: &'static str, log: String },
+    Link { log: String },
+    Unsupported(String),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, message } => {
+                write!(f, "could not read shader {}: {}", path, message)
+            }
+            ShaderError::IncludeCycle { path } => write!(f, "{} includes itself", path),
+            ShaderError::MalformedInclude { path, line } => {
+                write!(f, "{}:{}: malformed #include directive", path, line)
+            }
+            ShaderError::Compile { stage, log } => {
+                write!(f, "{} shader failed to compile:\n{}", stage, log)
+            }
+            ShaderError::Link { log } => write!(f, "shaders failed to link:\n{}", log),
+            ShaderError::Unsupported(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+// the full description of a compiled program, two components with the same variant share the
+// same program
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShaderVariant {
+    pub vertex_path: String,
+    pub fragment_path: String,
+    // kept sorted so that the order in which they were added doesn't create new variants
+    pub defines: Vec<(String, String)>,
+    pub keywords: Vec<String>,
+    // the fragment shader writes srgb values itself, so the framebuffer mustn't convert them
+    pub outputs_srgb: bool,
+}
+
+impl Default for ShaderVariant {
+    fn default() -> Self {
+        ShaderVariant::new(DEFAULT_VERTEX_SHADER, DEFAULT_FRAGMENT_SHADER)
+    }
+}
+
+impl ShaderVariant {
+    pub fn new(vertex_path: &str, fragment_path: &str) -> Self {
+        ShaderVariant {
+            vertex_path: vertex_path.to_string(),
+            fragment_path: fragment_path.to_string(),
+            defines: Vec::new(),
+            keywords: Vec::new(),
+            outputs_srgb: false,
+        }
+    }
+
+    pub fn with_srgb_output(mut self) -> Self {
+        self.outputs_srgb = true;
+        self
+    }
+
+    // keywords are injected as `#define KEYWORD 1` and are meant to toggle features with #ifdef
+    pub fn with_keyword(mut self, keyword: &str) -> Self {
+        if !self.keywords.iter().any(|k| k == keyword) {
+            self.keywords.push(keyword.to_string());
+            self.keywords.sort();
+        }
+        self
+    }
+
+    pub fn with_define(mut self, name: &str, value: &str) -> Self {
+        self.defines.retain(|(n, _)| n != name);
+        self.defines.push((name.to_string(), value.to_string()));
+        self.defines.sort();
+        self
+    }
+
+    // every line which will be inserted right after the #version directive
+    fn injected_lines(&self) -> Vec<String> {
+        let keywords = self.keywords.iter().map(|k| format!("#define {} 1", k));
+        let defines = self.defines.iter().map(|(n, v)| format!("#define {} {}", n, v));
+        keywords.chain(defines).collect()
+    }
+
+    // hash of the variant which doesn't depend on the compiler version or the process, unlike
+    // the std hasher, so that it may be used as a cache key between runs
+    pub fn cache_key(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        hasher.write_str(&self.vertex_path);
+        hasher.write_str(&self.fragment_path);
+        for keyword in &self.keywords {
+            hasher.write_str(keyword);
+        }
+        for (name, value) in &self.defines {
+            hasher.write_str(name);
+            hasher.write_str(value);
+        }
+        if self.outputs_srgb {
+            hasher.write_str("srgb");
+        }
+        hasher.finish()
+    }
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write_str(&mut self, s: &str) {
+        for byte in s.bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+        // separator so that ("ab", "c") and ("a", "bc") don't collide
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// a shader source where every #include has been replaced and every define has been injected
+pub struct PreprocessedSource {
+    pub source: String,
+    // for each line of the generated source, the file and the line (starting at 1) it comes from
+    pub line_map: Vec<(String, usize)>,
+}
+
+impl PreprocessedSource {
+    // the original location of a line (starting at 1) of the generated source
+    pub fn origin(&self, line: usize) -> Option<(&str, usize)> {
+        if line == 0 {
+            return None;
+        }
+        self.line_map
+            .get(line - 1)
+            .map(|(file, line)| (file.as_str(), *line))
+    }
+
+    // rewrites the line numbers of a compiler log so that they point to the original files
+    // handles the two formats we have encountered: mesa's `0:12(5): error` and nvidia's
+    // `0(12) : error`
+    pub fn map_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|log_line| match parse_log_location(log_line) {
+                Some((line, rest)) => match self.origin(line) {
+                    Some((file, original_line)) => format!("{}:{}{}", file, original_line, rest),
+                    None => log_line.to_string(),
+                },
+                None => log_line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// returns the line number of a log line and what comes after the location
+fn parse_log_location(log_line: &str) -> Option<(usize, &str)> {
+    let trimmed = log_line.trim_start();
+    let after_source = trimmed.strip_prefix(|c: char| c.is_ascii_digit())?;
+    let after_source = after_source.trim_start_matches(|c: char| c.is_ascii_digit());
+    if let Some(rest) = after_source.strip_prefix(':') {
+        // mesa
+        let digits_end = rest.find(|c: char| !c.is_ascii_digit())?;
+        let line = rest[..digits_end].parse().ok()?;
+        let rest = &rest[digits_end..];
+        let rest = match rest.strip_prefix('(') {
+            Some(column) => &column[column.find(')')? + 1..],
+            None => rest,
+        };
+        Some((line, rest))
+    } else if let Some(rest) = after_source.strip_prefix('(') {
+        // nvidia
+        let close = rest.find(')')?;
+        let line = rest[..close].parse().ok()?;
+        Some((line, &rest[close + 1..]))
+    } else {
+        None
+    }
+}
+
+// reads a shader either from the disk or from the builtin shaders
+pub fn read_shader_file(path: &str) -> Result<String, ShaderError> {
+    let err = match fs::read_to_string(path) {
+        Ok(source) => return Ok(source),
+        Err(err) => err,
+    };
+    match BUILTIN_SHADERS.iter().find(|(name, _)| *name == path) {
+        Some((_, source)) => Ok(source.to_string()),
+        None => Err(ShaderError::Io {
+            path: path.to_string(),
+            message: err.to_string(),
+        }),
+    }
+}
+
+// resolves an include relative to the directory of the file which includes it
+fn resolve_include(including_file: &str, included: &str) -> String {
+    match Path::new(including_file).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => {
+            dir.join(included).to_string_lossy().replace('\\', "/")
+        }
+        _ => included.to_string(),
+    }
+}
+
+// file name of an `#include "file"` (or `#include <file>`) directive
+fn parse_include(line: &str) -> Option<Option<&str>> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix("include")?.trim();
+    let name = rest
+        .strip_prefix('"')
+        .and_then(|r| r.strip_suffix('"'))
+        .or_else(|| rest.strip_prefix('<').and_then(|r| r.strip_suffix('>')));
+    Some(name)
+}
+
+pub fn preprocess(path: &str, variant: &ShaderVariant) -> Result<PreprocessedSource, ShaderError> {
+    let mut body = PreprocessedSource {
+        source: String::new(),
+        line_map: Vec::new(),
+    };
+    let mut included = HashSet::new();
+    let mut stack = Vec::new();
+    expand_file(path, &mut body, &mut included, &mut stack)?;
+
+    // the #version directive must stay the first line, so the defines are injected right after
+    let injected_at = body
+        .source
+        .lines()
+        .position(|line| line.trim_start().starts_with("#version"))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+
+    let mut output = PreprocessedSource {
+        source: String::new(),
+        line_map: Vec::new(),
+    };
+    let injected = variant
+        .injected_lines()
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| (line, (INJECTED_FILE.to_string(), i + 1)));
+    let body_lines = body
+        .source
+        .lines()
+        .map(str::to_string)
+        .zip(body.line_map.iter().cloned());
+    let lines = body_lines
+        .clone()
+        .take(injected_at)
+        .chain(injected)
+        .chain(body_lines.skip(injected_at));
+    for (line, origin) in lines {
+        output.source.push_str(&line);
+        output.source.push('\n');
+        output.line_map.push(origin);
+    }
+
+    Ok(output)
+}
+
+// every file is only included once, which saves us from having to write include guards
+fn expand_file(
+    path: &str,
+    output: &mut PreprocessedSource,
+    included: &mut HashSet<String>,
+    stack: &mut Vec<String>,
+) -> Result<(), ShaderError> {
+    if stack.iter().any(|p| p == path) {
+        return Err(ShaderError::IncludeCycle {
+            path: path.to_string(),
+        });
+    }
+    if !included.insert(path.to_string()) {
+        return Ok(());
+    }
+    let source = read_shader_file(path)?;
+    stack.push(path.to_string());
+    for (i, line) in source.lines().enumerate() {
+        match parse_include(line) {
+            Some(Some(name)) => {
+                let include_path = resolve_include(path, name);
+                expand_file(&include_path, output, included, stack)?;
+            }
+            Some(None) => {
+                return Err(ShaderError::MalformedInclude {
+                    path: path.to_string(),
+                    line: i + 1,
+                })
+            }
+            None => {
+                output.source.push_str(line);
+                output.source.push('\n');
+                output.line_map.push((path.to_string(), i + 1));
+            }
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+// preprocesses both stages of a variant and compiles them, compile errors point to the original
+// files rather than to the generated source
+pub fn compile_variant<F: Facade>(
+    variant: &ShaderVariant,
+    facade: &F,
+) -> Result<Program, ShaderError> {
+    let vertex = preprocess(&variant.vertex_path, variant)?;
+    let fragment = preprocess(&variant.fragment_path, variant)?;
+
+    let input = ProgramCreationInput::SourceCode {
+        vertex_shader: &vertex.source,
+        tessellation_control_shader: None,
+        tessellation_evaluation_shader: None,
+        geometry_shader: None,
+        fragment_shader: &fragment.source,
+        transform_feedback_varyings: None,
+        outputs_srgb: variant.outputs_srgb,
+        uses_point_size: false,
+    };
+    let res = Program::new(facade, input);
+    match res {
+        Ok(program) => Ok(program),
+        Err(ProgramCreationError::CompilationError(log, ShaderType::Vertex)) => {
+            Err(ShaderError::Compile {
+                stage: "vertex",
+                log: vertex.map_log(&log),
+            })
+        }
+        Err(ProgramCreationError::CompilationError(log, ShaderType::Fragment)) => {
+            Err(ShaderError::Compile {
+                stage: "fragment",
+                log: fragment.map_log(&log),
+            })
+        }
+        Err(ProgramCreationError::LinkingError(log)) => Err(ShaderError::Link { log }),
+        Err(err) => Err(ShaderError::Unsupported(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // writes the files in a folder of their own and returns the path of the first one
+    fn shader_files(test: &str, files: &[(&str, &str)]) -> String {
+        let dir = std::env::temp_dir().join(format!("sparkle_shader_{}_{}", test, std::process::id()));
+        for (name, source) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, source).unwrap();
+        }
+        return dir.join(files[0].0).to_string_lossy().replace('\\', "/");
+    }
+
+    fn lines(source: &PreprocessedSource) -> Vec<&str> {
+        return source.source.lines().collect();
+    }
+
+    #[test]
+    fn includes_are_expanded_once_relative_to_their_file() {
+        let main = shader_files(
+            "nested",
+            &[
+                ("main.glsl", "#version 330\n#include \"lib/a.glsl\"\n#include \"lib/b.glsl\"\nvoid main() {}\n"),
+                ("lib/a.glsl", "#include <b.glsl>\nfloat a;\n"),
+                ("lib/b.glsl", "float b;\n"),
+            ],
+        );
+        let source = preprocess(&main, &ShaderVariant::default()).unwrap();
+        assert_eq!(lines(&source), vec!["#version 330", "float b;", "float a;", "void main() {}"]);
+        let (file, line) = source.origin(2).unwrap();
+        assert!(file.ends_with("lib/b.glsl"));
+        assert_eq!(line, 1);
+        let (file, line) = source.origin(3).unwrap();
+        assert!(file.ends_with("lib/a.glsl"));
+        assert_eq!(line, 2);
+        assert_eq!(source.origin(4), Some((main.as_str(), 4)));
+        assert_eq!(source.origin(0), None);
+        assert_eq!(source.origin(5), None);
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let main = shader_files(
+            "cycle",
+            &[
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "#include \"a.glsl\"\n"),
+            ],
+        );
+        match preprocess(&main, &ShaderVariant::default()) {
+            Err(ShaderError::IncludeCycle { path }) => assert!(path.ends_with("a.glsl")),
+            _ => panic!("the cycle wasn't detected"),
+        }
+    }
+
+    #[test]
+    fn malformed_includes_are_errors() {
+        let main = shader_files("malformed", &[("main.glsl", "#version 330\n#include lib.glsl\n")]);
+        match preprocess(&main, &ShaderVariant::default()) {
+            Err(ShaderError::MalformedInclude { path, line }) => {
+                assert_eq!(path, main);
+                assert_eq!(line, 2);
+            }
+            _ => panic!("the include wasn't rejected"),
+        }
+        assert_eq!(parse_include("#include \"a.glsl"), Some(None));
+        assert_eq!(parse_include("  # include <a.glsl>"), Some(Some("a.glsl")));
+        assert_eq!(parse_include("#define A 1"), None);
+    }
+
+    #[test]
+    fn missing_files_keep_the_io_error() {
+        match read_shader_file("no/such/shader.glsl") {
+            Err(ShaderError::Io { path, message }) => {
+                assert_eq!(path, "no/such/shader.glsl");
+                assert!(!message.is_empty());
+            }
+            _ => panic!("the shader shouldn't exist"),
+        }
+    }
+
+    #[test]
+    fn defines_are_injected_after_the_version() {
+        let main = shader_files("defines", &[("main.glsl", "// header\n#version 330 core\nvoid main() {}\n")]);
+        let variant = ShaderVariant::default()
+            .with_define("MAX_LIGHTS", "4")
+            .with_keyword("SHADOWS");
+        let source = preprocess(&main, &variant).unwrap();
+        assert_eq!(
+            lines(&source),
+            vec![
+                "// header",
+                "#version 330 core",
+                "#define SHADOWS 1",
+                "#define MAX_LIGHTS 4",
+                "void main() {}",
+            ]
+        );
+        assert_eq!(source.origin(3), Some((INJECTED_FILE, 1)));
+        assert_eq!(source.origin(4), Some((INJECTED_FILE, 2)));
+        assert_eq!(source.origin(5), Some((main.as_str(), 3)));
+    }
+
+    #[test]
+    fn compiler_logs_point_to_the_original_files() {
+        let source = PreprocessedSource {
+            source: String::new(),
+            line_map: vec![
+                ("main.glsl".to_string(), 1),
+                (INJECTED_FILE.to_string(), 1),
+                ("lights.glsl".to_string(), 12),
+            ],
+        };
+        // mesa
+        assert_eq!(parse_log_location("0:3(5): error: oops"), Some((3, ": error: oops")));
+        assert_eq!(source.map_log("0:3(5): error: oops"), "lights.glsl:12: error: oops");
+        // nvidia
+        assert_eq!(parse_log_location("0(3) : error C0000: oops"), Some((3, " : error C0000: oops")));
+        assert_eq!(source.map_log("0(3) : error C0000: oops"), "lights.glsl:12 : error C0000: oops");
+        // anything else is left as it is
+        assert_eq!(parse_log_location("error: oops"), None);
+        assert_eq!(
+            source.map_log("0:9(1): error: past the end\nlinker error"),
+            "0:9(1): error: past the end\nlinker error"
+        );
+    }
+
+    #[test]
+    fn cache_keys_ignore_the_order_of_defines_and_keywords() {
+        let a = ShaderVariant::default()
+            .with_keyword("SHADOWS")
+            .with_keyword("FOG")
+            .with_define("A", "1")
+            .with_define("B", "2");
+        let b = ShaderVariant::default()
+            .with_define("B", "2")
+            .with_keyword("FOG")
+            .with_define("A", "1")
+            .with_keyword("SHADOWS")
+            .with_keyword("FOG");
+        assert_eq!(a, b);
+        assert_eq!(a.cache_key(), b.cache_key());
+        assert_ne!(a.cache_key(), a.clone().with_define("B", "3").cache_key());
+        assert_ne!(a.cache_key(), ShaderVariant::default().cache_key());
+        assert_ne!(a.cache_key(), a.clone().with_srgb_output().cache_key());
+    }
+}
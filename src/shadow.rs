@@ -0,0 +1,470 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use cgmath::InnerSpace;
+use cgmath::Matrix4;
+use cgmath::SquareMatrix;
+use cgmath::Vector3;
+use cgmath::Vector4;
+
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::DepthTexture2d;
+use glium::uniform;
+use glium::uniforms::DepthTextureComparison;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
+use glium::uniforms::SamplerBehavior;
+use glium::uniforms::SamplerWrapFunction;
+use glium::uniforms::UniformValue;
+use glium::Program;
+use glium::Surface;
+
+use legion::world::World;
+use legion::IntoQuery;
+
+use crate::camera::Camera;
+use crate::graphic_component::GraphicComponent;
+use crate::error::EngineError;
+use crate::graphic_component::ObjectModel;
+use crate::light::SceneLights;
+use crate::shader::ShaderVariant;
+use crate::transform::Transform;
+use crate::uniforms::UniformBag;
+
+// must be kept in sync with assets/shaders/lights.glsl
+pub const MAX_SHADOW_CASCADES: usize = 4;
+pub const MAX_SPOT_SHADOWS: usize = 2;
+
+pub const SHADOW_VERTEX_SHADER: &str = "builtin/shadow_vertex.glsl";
+pub const SHADOW_FRAGMENT_SHADER: &str = "builtin/shadow_fragment.glsl";
+
+pub fn shadow_variant() -> ShaderVariant {
+    return ShaderVariant::new(SHADOW_VERTEX_SHADER, SHADOW_FRAGMENT_SHADER);
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowSettings {
+    // width and height of every shadow map
+    pub resolution: u32,
+    // depth offset which avoids surfaces shadowing themselves (shadow acne)
+    pub bias: f32,
+    // offset along the normal, in world units, for the same purpose on slanted surfaces
+    pub normal_bias: f32,
+    // number of slices the view frustum is split in for directional lights, at most 4
+    pub cascade_count: usize,
+    // 0 splits the cascades evenly, 1 logarithmically
+    pub cascade_split_lambda: f32,
+    // distance from the camera after which directional lights don't cast shadows anymore
+    pub max_distance: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            resolution: 2048,
+            bias: 0.002,
+            normal_bias: 0.02,
+            cascade_count: 3,
+            cascade_split_lambda: 0.75,
+            max_distance: 100.0,
+        }
+    }
+}
+
+// the depth textures the lights render into, unused slots are bound to a 1x1 texture so that
+// every sampler of the shaders is always bound to a depth texture
+pub struct ShadowMaps {
+    pub resolution: u32,
+    pub cascades: Vec<DepthTexture2d>,
+    pub spots: Vec<DepthTexture2d>,
+    pub placeholder: DepthTexture2d,
+}
+
+impl ShadowMaps {
+    pub fn new<F: Facade>(facade: &F, settings: &ShadowSettings) -> Result<Self, EngineError> {
+        let size = settings.resolution;
+        let new_map = |size| {
+            DepthTexture2d::empty(facade, size, size).map_err(|err| EngineError::upload("shadow map", err))
+        };
+        let placeholder = new_map(1)?;
+        SimpleFrameBuffer::depth_only(facade, &placeholder)
+            .map_err(|err| EngineError::upload("shadow map", err))?
+            .clear_depth(1.0);
+        return Ok(ShadowMaps {
+            resolution: size,
+            cascades: (0..MAX_SHADOW_CASCADES).map(|_| new_map(size)).collect::<Result<_, _>>()?,
+            spots: (0..MAX_SPOT_SHADOWS).map(|_| new_map(size)).collect::<Result<_, _>>()?,
+            placeholder,
+        });
+    }
+}
+
+// the matrices of every shadow map which will be rendered this frame
+pub struct ShadowPlan {
+    // far distance (from the camera) of every cascade
+    pub cascade_splits: Vec<f32>,
+    pub cascade_matrices: Vec<[[f32; 4]; 4]>,
+    // index in SceneLights::directional of the light the cascades belong to
+    pub directional_index: Option<usize>,
+    // index in SceneLights::spot and matrix of every shadowed spot light
+    pub spot_matrices: Vec<(usize, [[f32; 4]; 4])>,
+}
+
+// view matrix of an eye looking towards `forward`, same conventions as the camera
+fn look_to(eye: Vector3<f32>, forward: Vector3<f32>) -> Matrix4<f32> {
+    let f = forward.normalize();
+    let up = if f.y.abs() > 0.99 {
+        Vector3::new(1.0, 0.0, 0.0)
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    };
+    let s = up.cross(f).normalize();
+    let u = f.cross(s);
+    return Matrix4::new(
+        s.x, u.x, f.x, 0.0,
+        s.y, u.y, f.y, 0.0,
+        s.z, u.z, f.z, 0.0,
+        -eye.dot(s), -eye.dot(u), -eye.dot(f), 1.0,
+    );
+}
+
+// orthographic projection where z grows away from the eye, like the camera's perspective
+fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix4<f32> {
+    return Matrix4::new(
+        2.0 / (right - left), 0.0, 0.0, 0.0,
+        0.0, 2.0 / (top - bottom), 0.0, 0.0,
+        0.0, 0.0, 2.0 / (far - near), 0.0,
+        -(right + left) / (right - left),
+        -(top + bottom) / (top - bottom),
+        -(far + near) / (far - near),
+        1.0,
+    );
+}
+
+fn perspective(fov: f32, near: f32, far: f32) -> Matrix4<f32> {
+    let f = 1.0 / (fov / 2.0).tan();
+    return Matrix4::new(
+        f, 0.0, 0.0, 0.0,
+        0.0, f, 0.0, 0.0,
+        0.0, 0.0, (far + near) / (far - near), 1.0,
+        0.0, 0.0, -(2.0 * far * near) / (far - near), 0.0,
+    );
+}
+
+// distances at which the view frustum is split, the first one is the near plane
+fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (0..=count)
+        .map(|i| {
+            let ratio = i as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(ratio);
+            let uniform = near + (far - near) * ratio;
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+// fits an orthographic projection around the part of the camera frustum between two distances
+fn cascade_matrix(
+    inverse_view_projection: Matrix4<f32>,
+    camera: &Camera,
+    (near, far): (f32, f32),
+    direction: Vector3<f32>,
+    settings: &ShadowSettings,
+) -> Matrix4<f32> {
+    // depth of a distance once projected by the camera's perspective
+    let (n, f) = (camera.znear, camera.zfar);
+    let ndc_depth = |d: f32| (f + n) / (f - n) - (2.0 * f * n) / ((f - n) * d);
+
+    let mut corners = Vec::with_capacity(8);
+    for z in [ndc_depth(near), ndc_depth(far)] {
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            let corner = inverse_view_projection * Vector4::new(x, y, z, 1.0);
+            corners.push(corner.truncate() / corner.w);
+        }
+    }
+    let center = corners.iter().fold(Vector3::new(0.0, 0.0, 0.0), |a, c| a + c) / 8.0;
+    // a sphere rather than a box so that the size doesn't change when the camera rotates
+    let radius = corners
+        .iter()
+        .map(|c| (c - center).magnitude())
+        .fold(0.0f32, f32::max)
+        .ceil();
+
+    // snapping the center to the texels of the map stops the shadows from shimmering when
+    // the camera moves
+    let rotation = look_to(Vector3::new(0.0, 0.0, 0.0), direction);
+    let texel = 2.0 * radius / settings.resolution as f32;
+    let light_center = (rotation * center.extend(1.0)).truncate();
+    let snapped_x = (light_center.x / texel).floor() * texel;
+    let snapped_y = (light_center.y / texel).floor() * texel;
+
+    // objects outside of the frustum but between it and the light still cast shadows
+    let projection = orthographic(
+        snapped_x - radius,
+        snapped_x + radius,
+        snapped_y - radius,
+        snapped_y + radius,
+        light_center.z - radius - settings.max_distance,
+        light_center.z + radius,
+    );
+    return projection * rotation;
+}
+
+pub fn plan_shadows(
+    lights: &SceneLights,
+    camera: &Camera,
+    view: [[f32; 4]; 4],
+    perspective_matrix: [[f32; 4]; 4],
+    settings: &ShadowSettings,
+) -> ShadowPlan {
+    let mut plan = ShadowPlan {
+        cascade_splits: Vec::new(),
+        cascade_matrices: Vec::new(),
+        directional_index: None,
+        spot_matrices: Vec::new(),
+    };
+
+    let directional = lights
+        .directional
+        .iter()
+        .position(|(_, light)| light.casts_shadows);
+    let inverse = (Matrix4::from(perspective_matrix) * Matrix4::from(view)).invert();
+    if let (Some(index), Some(inverse)) = (directional, inverse) {
+        let count = settings.cascade_count.clamp(1, MAX_SHADOW_CASCADES);
+        let far = settings.max_distance.min(camera.zfar);
+        let splits = cascade_splits(camera.znear, far, count, settings.cascade_split_lambda);
+        let direction = Vector3::from(lights.directional[index].0);
+        plan.directional_index = Some(index);
+        for i in 0..count {
+            let matrix = cascade_matrix(inverse, camera, (splits[i], splits[i + 1]), direction, settings);
+            plan.cascade_matrices.push(matrix.into());
+            plan.cascade_splits.push(splits[i + 1]);
+        }
+    }
+
+    let shadowed_spots = lights
+        .spot
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, _, light))| light.casts_shadows)
+        .take(MAX_SPOT_SHADOWS);
+    for (i, (position, direction, light)) in shadowed_spots {
+        let view = look_to(Vector3::from(*position), Vector3::from(*direction));
+        let projection = perspective(2.0 * light.outer_angle, 0.05, light.range.max(0.1));
+        plan.spot_matrices.push((i, (projection * view).into()));
+    }
+
+    return plan;
+}
+
+// renders the depth of every shadow casting component into the shadow maps of the plan
+pub fn render_shadow_maps<F: Facade>(
+    facade: &F,
+    plan: &ShadowPlan,
+    maps: &ShadowMaps,
+    world: &World,
+    models: &HashMap<String, ObjectModel>,
+    program: &Program,
+) -> Result<(), EngineError> {
+    let params = glium::DrawParameters {
+        depth: glium::Depth {
+            test: glium::draw_parameters::DepthTest::IfLess,
+            write: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let matrices = plan
+        .cascade_matrices
+        .iter()
+        .zip(maps.cascades.iter())
+        .chain(plan.spot_matrices.iter().map(|(_, m)| m).zip(maps.spots.iter()));
+    for (light_matrix, map) in matrices {
+        let mut framebuffer =
+            SimpleFrameBuffer::depth_only(facade, map).map_err(|err| EngineError::upload("shadow map", err))?;
+        framebuffer.clear_depth(1.0);
+        let mut casters = <(&GraphicComponent, &Transform)>::query();
+        for (gc, transform) in casters.iter(world) {
+            if !(gc.is_active() && gc.casts_shadows) {
+                continue;
+            }
+            let Some(model) = gc.model_path.as_ref().and_then(|path| models.get(path)) else {
+                continue;
+            };
+            framebuffer
+                .draw(
+                    &model.vertices,
+                    &model.indices,
+                    program,
+                    &uniform! {
+                        matrix: transform.uniform_matrix(),
+                        u_light_matrix: *light_matrix,
+                    },
+                    &params,
+                )?;
+        }
+    }
+    return Ok(());
+}
+
+fn shadow_sampler() -> SamplerBehavior {
+    SamplerBehavior {
+        wrap_function: (
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+            SamplerWrapFunction::Clamp,
+        ),
+        minify_filter: MinifySamplerFilter::Linear,
+        magnify_filter: MagnifySamplerFilter::Linear,
+        depth_texture_comparison: Some(DepthTextureComparison::LessOrEqual),
+        ..Default::default()
+    }
+}
+
+pub fn bind_shadows<'a>(
+    plan: &ShadowPlan,
+    maps: &'a ShadowMaps,
+    settings: &ShadowSettings,
+    receives_shadows: bool,
+    uniforms: &mut UniformBag<'a>,
+) {
+    let sampler = Some(shadow_sampler());
+    uniforms.set("u_receives_shadows", UniformValue::Bool(receives_shadows));
+    uniforms.set("u_shadow_bias", UniformValue::Float(settings.bias));
+    uniforms.set("u_shadow_normal_bias", UniformValue::Float(settings.normal_bias));
+
+    let directional_index = plan.directional_index.map(|i| i as i32).unwrap_or(-1);
+    uniforms.set(
+        "u_shadowed_directional_light",
+        UniformValue::SignedInt(directional_index),
+    );
+    uniforms.set(
+        "u_cascade_count",
+        UniformValue::SignedInt(plan.cascade_matrices.len() as i32),
+    );
+    for i in 0..MAX_SHADOW_CASCADES {
+        let map = match plan.cascade_matrices.get(i) {
+            Some(matrix) => {
+                uniforms.set(&format!("u_cascade_matrices[{}]", i), UniformValue::Mat4(*matrix));
+                uniforms.set(
+                    &format!("u_cascade_splits[{}]", i),
+                    UniformValue::Float(plan.cascade_splits[i]),
+                );
+                &maps.cascades[i]
+            }
+            None => &maps.placeholder,
+        };
+        uniforms.set(&format!("u_cascade_map{}", i), UniformValue::DepthTexture2d(map, sampler));
+    }
+
+    for i in 0..MAX_SPOT_SHADOWS {
+        let map = match plan.spot_matrices.get(i) {
+            Some((light_index, matrix)) => {
+                uniforms.set(
+                    &format!("u_spot_lights[{}].shadow_index", light_index),
+                    UniformValue::SignedInt(i as i32),
+                );
+                uniforms.set(&format!("u_spot_shadow_matrices[{}]", i), UniformValue::Mat4(*matrix));
+                &maps.spots[i]
+            }
+            None => &maps.placeholder,
+        };
+        uniforms.set(&format!("u_spot_shadow_map{}", i), UniformValue::DepthTexture2d(map, sampler));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn transform_point(matrix: Matrix4<f32>, point: Vector3<f32>) -> Vector3<f32> {
+        let projected = matrix * point.extend(1.0);
+        return projected.truncate() / projected.w;
+    }
+
+    #[test]
+    fn splits_go_from_near_to_far() {
+        for lambda in [0.0, 0.5, 0.75, 1.0] {
+            let splits = cascade_splits(0.1, 100.0, 4, lambda);
+            assert_eq!(splits.len(), 5);
+            assert!((splits[0] - 0.1).abs() < EPSILON);
+            assert!((splits[4] - 100.0).abs() < EPSILON);
+            assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+
+    #[test]
+    fn lambda_blends_uniform_and_logarithmic_splits() {
+        let uniform = cascade_splits(1.0, 81.0, 4, 0.0);
+        for (split, expected) in uniform.iter().zip([1.0, 21.0, 41.0, 61.0, 81.0]) {
+            assert!((split - expected).abs() < EPSILON);
+        }
+        let logarithmic = cascade_splits(1.0, 81.0, 4, 1.0);
+        for (split, expected) in logarithmic.iter().zip([1.0, 3.0, 9.0, 27.0, 81.0]) {
+            assert!((split - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn look_to_puts_the_eye_at_the_origin() {
+        let eye = Vector3::new(1.0, 2.0, 3.0);
+        for forward in [Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.3, -1.0, 0.2), Vector3::new(0.0, -1.0, 0.0)] {
+            let view = look_to(eye, forward);
+            assert!(transform_point(view, eye).magnitude() < EPSILON);
+            // what is in front of the eye is along +z
+            let ahead = transform_point(view, eye + forward.normalize() * 5.0);
+            assert!(ahead.truncate().magnitude() < EPSILON);
+            assert!((ahead.z - 5.0).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn orthographic_maps_the_box_to_ndc() {
+        let projection = orthographic(-2.0, 4.0, -1.0, 3.0, 0.5, 10.0);
+        let low = transform_point(projection, Vector3::new(-2.0, -1.0, 0.5));
+        let high = transform_point(projection, Vector3::new(4.0, 3.0, 10.0));
+        assert!((low - Vector3::new(-1.0, -1.0, -1.0)).magnitude() < EPSILON);
+        assert!((high - Vector3::new(1.0, 1.0, 1.0)).magnitude() < EPSILON);
+    }
+
+    #[test]
+    fn cascades_contain_their_slice_of_the_frustum() {
+        let mut camera = Camera::new();
+        camera.zfar = 200.0;
+        let (width, height) = (800, 600);
+        let view = Matrix4::from(camera.view_matrix());
+        let perspective = Matrix4::from(camera.perspective_matrix(width, height));
+        let inverse = (perspective * view).invert().unwrap();
+        let settings = ShadowSettings::default();
+        let splits = cascade_splits(camera.znear, settings.max_distance, 3, settings.cascade_split_lambda);
+
+        // half the size of the view at a distance of 1, see Camera::perspective_matrix
+        let half_height = (camera.fov as f32 / 2.0).tan();
+        let half_width = half_height * width as f32 / height as f32;
+        let position = camera.transform.get_position();
+        for direction in [Vector3::new(0.3, -1.0, 0.2), Vector3::new(0.0, 0.0, 1.0)] {
+            for slice in splits.windows(2) {
+                let matrix = cascade_matrix(inverse, &camera, (slice[0], slice[1]), direction, &settings);
+                for distance in slice {
+                    for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+                        let corner = position
+                            + Vector3::new(x * half_width * distance, y * half_height * distance, *distance);
+                        let ndc = transform_point(matrix, corner);
+                        assert!(
+                            ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0 && ndc.z.abs() <= 1.0,
+                            "{:?} is outside of the cascade",
+                            ndc
+                        );
+                    }
+                }
+                let aside = transform_point(matrix, position + Vector3::new(1000.0, 0.0, slice[1]));
+                assert!(aside.x.abs() > 1.0 || aside.y.abs() > 1.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,375 @@
+#![allow(dead_code)]
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+
+use crate::mesh::Mesh;
+
+// how much more the borders of open meshes cost to move than the surfaces, so that the
+// silhouette of planes and cut objects is kept
+const BOUNDARY_WEIGHT: f64 = 1000.0;
+
+// symmetric 4x4 matrix, the sum of the squared distances to a set of planes (Garland and
+// Heckbert 1997), only the upper triangle is stored
+#[derive(Copy, Clone, Debug, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    // the plane is a, b, c, d with ax + by + cz + d = 0 and (a, b, c) of unit length
+    fn from_plane(plane: [f64; 4], weight: f64) -> Self {
+        let [a, b, c, d] = plane;
+        let q = [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d];
+        return Quadric(q.map(|x| x * weight));
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for i in 0..10 {
+            self.0[i] += other.0[i];
+        }
+    }
+
+    fn error(&self, p: [f64; 3]) -> f64 {
+        let q = &self.0;
+        let [x, y, z] = p;
+        let error = q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9];
+        // rounding may make it slightly negative, which would break the ordering of the heap
+        return error.max(0.0);
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    return [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    return [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]];
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+fn length(a: [f64; 3]) -> f64 {
+    return dot(a, a).sqrt();
+}
+
+// a collapse of the point `from` onto the point `to`, valid as long as neither has changed
+// since it was computed
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Collapse {
+    // the bits of a positive f64 sort like the float
+    cost: Reverse<u64>,
+    from: usize,
+    to: usize,
+    from_version: u32,
+    to_version: u32,
+}
+
+// the vertices which share a position are welded into a point, so that the seams of the uvs and
+// of the normals don't open when the mesh is simplified
+struct Simplifier<'a> {
+    mesh: &'a Mesh,
+    positions: Vec<[f64; 3]>,
+    // the point of every vertex
+    vertex_point: Vec<usize>,
+    // the vertices of every point
+    point_vertices: Vec<Vec<u32>>,
+    quadrics: Vec<Quadric>,
+    // the triangles around every point, some of them may have been removed since
+    point_triangles: Vec<Vec<usize>>,
+    triangles: Vec<[u32; 3]>,
+    removed: Vec<bool>,
+    alive: Vec<bool>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(mesh: &'a Mesh) -> Self {
+        let mut points: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut positions = Vec::new();
+        let mut point_vertices: Vec<Vec<u32>> = Vec::new();
+        let vertex_point = mesh
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(v, p)| {
+                let point = *points.entry(p.map(f32::to_bits)).or_insert_with(|| {
+                    positions.push(p.map(|x| x as f64));
+                    point_vertices.push(Vec::new());
+                    positions.len() - 1
+                });
+                point_vertices[point].push(v as u32);
+                point
+            })
+            .collect();
+        let triangles: Vec<[u32; 3]> = mesh.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+        let count = positions.len();
+        let mut simplifier = Simplifier {
+            mesh,
+            positions,
+            vertex_point,
+            point_vertices,
+            quadrics: vec![Quadric::default(); count],
+            point_triangles: vec![Vec::new(); count],
+            removed: vec![false; triangles.len()],
+            triangles,
+            alive: vec![true; count],
+            versions: vec![0; count],
+            heap: BinaryHeap::new(),
+        };
+        simplifier.init();
+        return simplifier;
+    }
+
+    fn points(&self, triangle: usize) -> [usize; 3] {
+        return self.triangles[triangle].map(|v| self.vertex_point[v as usize]);
+    }
+
+    // unnormalised, its length is twice the area of the triangle
+    fn normal(&self, points: [usize; 3]) -> [f64; 3] {
+        let [a, b, c] = points.map(|p| self.positions[p]);
+        return cross(sub(b, a), sub(c, a));
+    }
+
+    fn init(&mut self) {
+        // the planes of the triangles, weighted by their area
+        let mut edges: HashMap<(usize, usize), (u32, usize)> = HashMap::new();
+        for triangle in 0..self.triangles.len() {
+            let points = self.points(triangle);
+            if points[0] == points[1] || points[1] == points[2] || points[0] == points[2] {
+                self.removed[triangle] = true;
+                continue;
+            }
+            let normal = self.normal(points);
+            let area = length(normal);
+            if area > 0.0 {
+                let n = normal.map(|x| x / area);
+                let quadric = Quadric::from_plane([n[0], n[1], n[2], -dot(n, self.positions[points[0]])], area * 0.5);
+                for p in points {
+                    self.quadrics[p].add(&quadric);
+                }
+            }
+            for (i, p) in points.iter().enumerate() {
+                self.point_triangles[*p].push(triangle);
+                let q = points[(i + 1) % 3];
+                let edge = edges.entry((*p.min(&q), *p.max(&q))).or_insert((0, triangle));
+                edge.0 += 1;
+            }
+        }
+        // a plane through every border edge, perpendicular to its triangle
+        for (&(a, b), &(count, triangle)) in &edges {
+            if count != 1 {
+                continue;
+            }
+            let normal = self.normal(self.points(triangle));
+            let edge = sub(self.positions[b], self.positions[a]);
+            let perpendicular = cross(edge, normal);
+            let length = length(perpendicular);
+            if length > 0.0 {
+                let n = perpendicular.map(|x| x / length);
+                let plane = [n[0], n[1], n[2], -dot(n, self.positions[a])];
+                let quadric = Quadric::from_plane(plane, BOUNDARY_WEIGHT * dot(edge, edge));
+                self.quadrics[a].add(&quadric);
+                self.quadrics[b].add(&quadric);
+            }
+        }
+        for &(a, b) in edges.keys() {
+            self.push_edge(a, b);
+        }
+    }
+
+    // the cheapest of the two directions, the points are only moved onto each other so that the
+    // attributes of the remaining vertices stay valid
+    fn push_edge(&mut self, a: usize, b: usize) {
+        let mut quadric = self.quadrics[a];
+        quadric.add(&self.quadrics[b]);
+        let (cost_a, cost_b) = (quadric.error(self.positions[a]), quadric.error(self.positions[b]));
+        let (from, to, cost) = if cost_a <= cost_b { (b, a, cost_a) } else { (a, b, cost_b) };
+        self.heap.push(Collapse {
+            cost: Reverse(cost.to_bits()),
+            from,
+            to,
+            from_version: self.versions[from],
+            to_version: self.versions[to],
+        });
+    }
+
+    fn triangle_count(&self) -> usize {
+        return self.removed.iter().filter(|removed| !**removed).count();
+    }
+
+    // whether moving the point turns one of its triangles over or makes it degenerate
+    fn flips(&self, from: usize, to: usize) -> bool {
+        for &triangle in &self.point_triangles[from] {
+            let points = self.points(triangle);
+            if self.removed[triangle] || points.contains(&to) {
+                continue;
+            }
+            let before = self.normal(points);
+            let moved = points.map(|p| if p == from { to } else { p });
+            let after = self.normal(moved);
+            if dot(before, after) <= 0.0 {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    // the vertex of `to` which replaces a vertex of `from`, the one with the closest uv so that
+    // the vertices on both sides of a seam keep their own side
+    fn replacement(&self, vertex: u32, to: usize) -> u32 {
+        let candidates = &self.point_vertices[to];
+        let Some(uvs) = self.mesh.tex_coords.first() else {
+            return candidates[0];
+        };
+        let uv = uvs[vertex as usize];
+        let distance = |v: &u32| {
+            let other = uvs[*v as usize];
+            (other[0] - uv[0]).powi(2) + (other[1] - uv[1]).powi(2)
+        };
+        return *candidates.iter().min_by(|a, b| distance(a).total_cmp(&distance(b))).unwrap();
+    }
+
+    // returns how many triangles were removed
+    fn collapse(&mut self, from: usize, to: usize) -> usize {
+        let triangles = std::mem::take(&mut self.point_triangles[from]);
+        let mut removed = 0;
+        for &triangle in &triangles {
+            if self.removed[triangle] {
+                continue;
+            }
+            if self.points(triangle).contains(&to) {
+                self.removed[triangle] = true;
+                removed += 1;
+                continue;
+            }
+            for corner in 0..3 {
+                let vertex = self.triangles[triangle][corner];
+                if self.vertex_point[vertex as usize] == from {
+                    self.triangles[triangle][corner] = self.replacement(vertex, to);
+                }
+            }
+        }
+        self.point_triangles[to].extend(triangles);
+        self.point_triangles[to].retain(|t| !self.removed[*t]);
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+        self.alive[from] = false;
+        self.versions[to] += 1;
+
+        let mut neighbours: Vec<usize> = self.point_triangles[to]
+            .iter()
+            .flat_map(|t| self.points(*t))
+            .filter(|p| *p != to)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        for neighbour in neighbours {
+            self.push_edge(to, neighbour);
+        }
+        return removed;
+    }
+
+    fn run(&mut self, target: usize) {
+        let mut count = self.triangle_count();
+        while count > target {
+            let Some(collapse) = self.heap.pop() else {
+                break;
+            };
+            let Collapse { from, to, .. } = collapse;
+            let outdated = !self.alive[from]
+                || !self.alive[to]
+                || self.versions[from] != collapse.from_version
+                || self.versions[to] != collapse.to_version;
+            if outdated || self.flips(from, to) {
+                continue;
+            }
+            count -= self.collapse(from, to);
+        }
+    }
+
+    // the remaining triangles, with only the vertices they use
+    fn finish(&self) -> Mesh {
+        let mut remap: HashMap<u32, u32> = HashMap::new();
+        let mut used: Vec<usize> = Vec::new();
+        let mut indices = Vec::new();
+        for (triangle, corners) in self.triangles.iter().enumerate() {
+            if self.removed[triangle] {
+                continue;
+            }
+            for vertex in corners {
+                let index = *remap.entry(*vertex).or_insert_with(|| {
+                    used.push(*vertex as usize);
+                    (used.len() - 1) as u32
+                });
+                indices.push(index);
+            }
+        }
+        let mesh = self.mesh;
+        return Mesh {
+            positions: pick(&mesh.positions, &used),
+            normals: mesh.normals.as_ref().map(|values| pick(values, &used)),
+            tex_coords: mesh.tex_coords.iter().map(|values| pick(values, &used)).collect(),
+            colors: mesh.colors.as_ref().map(|values| pick(values, &used)),
+            tangents: mesh.tangents.as_ref().map(|values| pick(values, &used)),
+            joints: mesh.joints.as_ref().map(|values| pick(values, &used)),
+            weights: mesh.weights.as_ref().map(|values| pick(values, &used)),
+            indices,
+        };
+    }
+}
+
+fn pick<T: Copy>(values: &[T], used: &[usize]) -> Vec<T> {
+    return used.iter().map(|v| values[*v]).collect();
+}
+
+// a lower level of detail of the mesh, with about `ratio` of its triangles, by collapsing the
+// edges which change the surface the least (quadric error metric)
+// the vertices are never moved, only merged, so the attributes are kept as they are
+pub fn simplify(mesh: &Mesh, ratio: f32) -> Mesh {
+    let triangles = mesh.indices.len() / 3;
+    let target = (triangles as f32 * ratio.clamp(0.0, 1.0)).round() as usize;
+    let mut simplifier = Simplifier::new(mesh);
+    simplifier.run(target);
+    return simplifier.finish();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::primitives::plane;
+    use crate::primitives::uv_sphere;
+
+    #[test]
+    fn simplified_sphere_has_fewer_triangles() {
+        let sphere = uv_sphere(1.0, 32, 16);
+        let simplified = simplify(&sphere, 0.25);
+        let (before, after) = (sphere.indices.len() / 3, simplified.indices.len() / 3);
+        assert!(after <= before / 4 + 2, "{} triangles left of {}", after, before);
+        assert!(after > 0);
+        assert!(simplified.validate().is_ok());
+        assert_eq!(simplified.tex_coords.len(), sphere.tex_coords.len());
+        // the vertices are not moved so they all stay on the sphere
+        for p in &simplified.positions {
+            let radius = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+            assert!((radius - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn flat_plane_keeps_its_borders() {
+        let mesh = plane(2.0, 2.0, 8, 8);
+        let simplified = simplify(&mesh, 0.02);
+        assert!(simplified.indices.len() <= 9);
+        // the flat inside goes first, the corners are what is left
+        let xs = simplified.positions.iter().map(|p| p[0]);
+        let (min, max) = xs.fold((f32::MAX, f32::MIN), |(lo, hi), x| (lo.min(x), hi.max(x)));
+        assert_eq!((min, max), (-1.0, 1.0));
+    }
+}
@@ -0,0 +1,657 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+
+use ab_glyph::point;
+use ab_glyph::Font;
+use ab_glyph::FontVec;
+use ab_glyph::GlyphId;
+use ab_glyph::PxScale;
+use ab_glyph::ScaleFont;
+
+use cgmath::Matrix4;
+use cgmath::Vector4;
+
+use glium::backend::Facade;
+use glium::implement_vertex;
+use glium::index::NoIndices;
+use glium::index::PrimitiveType;
+use glium::texture::MipmapsOption;
+use glium::texture::RawImage2d;
+use glium::texture::Texture2d;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
+use glium::uniforms::SamplerBehavior;
+use glium::uniforms::SamplerWrapFunction;
+use glium::uniforms::UniformValue;
+use glium::Program;
+use glium::Surface;
+use glium::VertexBuffer;
+
+use legion::world::World;
+use legion::IntoQuery;
+use tracing::warn;
+
+use crate::camera::Camera;
+use crate::error::EngineError;
+use crate::logging::RENDER;
+use crate::render_graph::FrameInfo;
+use crate::render_graph::PassBuilder;
+use crate::render_graph::PassContext;
+use crate::render_graph::RenderPass;
+use crate::render_graph::BACKBUFFER;
+use crate::render_graph::SCENE_DEPTH;
+use crate::shader::ShaderVariant;
+use crate::texture::Texture;
+use crate::transform::Transform;
+use crate::uniforms::UniformBag;
+
+pub const TEXT_VERTEX_SHADER: &str = "builtin/text_vertex.glsl";
+pub const TEXT_FRAGMENT_SHADER: &str = "builtin/text_fragment.glsl";
+
+// the passes of RenderGraph::forward, the texts of the world are hidden by the objects and go
+// through the post processing, the ones of the screen are drawn over everything
+pub const WORLD_TEXT_PASS: &str = "world_text";
+pub const SCREEN_TEXT_PASS: &str = "screen_text";
+
+// the size of the atlas of every font, it is cleared when it is full
+const ATLAS_SIZE: u32 = 1024;
+// the texts in the world are rasterized at this size whatever their size in world units
+const WORLD_TEXT_PIXELS: f32 = 64.0;
+
+pub fn text_variant() -> ShaderVariant {
+    return ShaderVariant::new(TEXT_VERTEX_SHADER, TEXT_FRAGMENT_SHADER);
+}
+
+// the key of the atlas of a font in the textures of the scene
+pub fn atlas_key(font_path: &str) -> String {
+    return format!("{}#atlas", font_path);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextSpace {
+    // in pixels from the top left corner of the window, the size is in pixels
+    Screen([f32; 2]),
+    // at the transform of the entity, the size is in world units
+    // a billboard faces the camera, otherwise the text lies on the xy plane of the transform
+    World { billboard: bool },
+}
+
+// a text drawn by the text passes, the lines go down from the anchor and are aligned on it
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextComponent {
+    pub text: String,
+    // a ttf or otf file
+    pub font: String,
+    // the height of a line without the spacing
+    pub size: f32,
+    pub color: [f32; 4],
+    pub align: TextAlign,
+    // the lines are wrapped between words past this width, in the unit of the size
+    pub max_width: Option<f32>,
+    // multiplies the line height of the font
+    pub line_spacing: f32,
+    pub space: TextSpace,
+}
+
+impl TextComponent {
+    pub fn new(text: &str, font: &str) -> Self {
+        return TextComponent {
+            text: text.to_string(),
+            font: font.to_string(),
+            size: 24.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.0,
+            space: TextSpace::Screen([0.0, 0.0]),
+        };
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn with_line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    pub fn at_screen_position(mut self, position: [f32; 2]) -> Self {
+        self.space = TextSpace::Screen(position);
+        self
+    }
+
+    pub fn in_world(mut self, billboard: bool) -> Self {
+        self.space = TextSpace::World { billboard };
+        self
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text.clear();
+        self.text.push_str(text);
+    }
+
+    pub fn is_screen_space(&self) -> bool {
+        return matches!(self.space, TextSpace::Screen(_));
+    }
+}
+
+// a line of a laid out text, the range is in bytes
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLine {
+    pub range: Range<usize>,
+    pub width: f32,
+}
+
+// splits the text on the line breaks, and between the words of the lines wider than the maximum
+// advance gives the width of a character, kerning with the previous one included
+// a word wider than the maximum gets a line of its own
+pub fn break_lines(text: &str, max_width: Option<f32>, advance: impl Fn(Option<char>, char) -> f32) -> Vec<TextLine> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut width = 0.0;
+    let mut previous: Option<char> = None;
+    // the end of the last word and its width, then where the next word starts and the width
+    // up to there
+    let mut last_break: Option<(usize, f32, usize, f32)> = None;
+    for (index, character) in text.char_indices() {
+        if character == '\n' {
+            lines.push(TextLine {
+                range: start..index,
+                width,
+            });
+            start = index + 1;
+            width = 0.0;
+            previous = None;
+            last_break = None;
+            continue;
+        }
+        let step = advance(previous, character);
+        if character.is_whitespace() {
+            let next = index + character.len_utf8();
+            match (&mut last_break, previous) {
+                (Some(found), Some(previous)) if previous.is_whitespace() => {
+                    found.2 = next;
+                    found.3 = width + step;
+                }
+                (_, Some(_)) => last_break = Some((index, width, next, width + step)),
+                // the spaces starting a line are kept
+                (_, None) => (),
+            }
+        } else if let (Some(max_width), Some((end, end_width, next, next_width))) = (max_width, last_break) {
+            if width + step > max_width {
+                lines.push(TextLine {
+                    range: start..end,
+                    width: end_width,
+                });
+                start = next;
+                width -= next_width;
+                last_break = None;
+            }
+        }
+        width += step;
+        previous = Some(character);
+    }
+    lines.push(TextLine {
+        range: start..text.len(),
+        width,
+    });
+    return lines;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextVertex {
+    pub position: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub color: [f32; 4],
+}
+
+implement_vertex!(TextVertex, position, tex_coord, color);
+
+// where a glyph is in the atlas
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct AtlasGlyph {
+    // the corners of the glyph relative to its origin on the baseline, in pixels, y going down
+    min: [f32; 2],
+    max: [f32; 2],
+    // min u, min v, max u, max v
+    uv: [f32; 4],
+}
+
+// a font and the glyphs it has rasterized so far, the atlas is uploaded to the textures of the
+// scene under atlas_key when it changes
+pub struct FontAtlas {
+    font: FontVec,
+    pixels: Vec<u8>,
+    // by glyph and pixel size, none for the glyphs without outline like the spaces
+    glyphs: HashMap<(GlyphId, u32), Option<AtlasGlyph>>,
+    // the glyphs are packed in rows, left to right
+    cursor: [u32; 2],
+    row_height: u32,
+    dirty: bool,
+    // how many times the atlas filled up, the vertices built before a clear point to lost glyphs
+    clears: u32,
+}
+
+impl FontAtlas {
+    pub fn load(path: &str) -> Result<Self, EngineError> {
+        let data = fs::read(path).map_err(|err| EngineError::io(path, err))?;
+        return FontAtlas::from_bytes(path, data);
+    }
+
+    // the path only describes the errors
+    pub fn from_bytes(path: &str, data: Vec<u8>) -> Result<Self, EngineError> {
+        let font = FontVec::try_from_vec(data).map_err(|err| EngineError::parse(path, err))?;
+        return Ok(FontAtlas {
+            font,
+            pixels: vec![0; (ATLAS_SIZE * ATLAS_SIZE * 4) as usize],
+            glyphs: HashMap::new(),
+            cursor: [0, 0],
+            row_height: 0,
+            dirty: true,
+            clears: 0,
+        });
+    }
+
+    // the white texture whose alpha is the coverage of the glyphs, none when it hasn't changed
+    // since the last call
+    pub fn take_texture<F: Facade>(&mut self, facade: &F) -> Result<Option<Texture>, EngineError> {
+        if !self.dirty {
+            return Ok(None);
+        }
+        self.dirty = false;
+        let image = RawImage2d::from_raw_rgba(self.pixels.clone(), (ATLAS_SIZE, ATLAS_SIZE));
+        let texture = Texture2d::with_mipmaps(facade, image, MipmapsOption::NoMipmap)
+            .map_err(|err| EngineError::upload("font atlas", err))?;
+        return Ok(Some(Texture::Linear(texture)));
+    }
+
+    fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.glyphs.clear();
+        self.cursor = [0, 0];
+        self.row_height = 0;
+        self.dirty = true;
+        self.clears += 1;
+    }
+
+    // rasterizes the glyph the first time it is used at this size
+    fn glyph(&mut self, id: GlyphId, pixels: u32) -> Option<AtlasGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(id, pixels)) {
+            return *glyph;
+        }
+        let glyph = id.with_scale_and_position(pixels as f32, point(0.0, 0.0));
+        let Some(outlined) = self.font.outline_glyph(glyph) else {
+            self.glyphs.insert((id, pixels), None);
+            return None;
+        };
+        let bounds = outlined.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        // one pixel between the glyphs so that they don't bleed into each other
+        if self.cursor[0] + width + 1 > ATLAS_SIZE {
+            self.cursor = [0, self.cursor[1] + self.row_height + 1];
+            self.row_height = 0;
+        }
+        if self.cursor[1] + height + 1 > ATLAS_SIZE {
+            // the glyphs of the texts laid out before are lost, see lay_out_texts
+            self.clear();
+        }
+        let [x, y] = self.cursor;
+        outlined.draw(|gx, gy, coverage| {
+            let index = (((y + gy) * ATLAS_SIZE + x + gx) * 4) as usize;
+            if let Some(pixel) = self.pixels.get_mut(index..index + 4) {
+                pixel.copy_from_slice(&[255, 255, 255, (coverage.clamp(0.0, 1.0) * 255.0) as u8]);
+            }
+        });
+        self.cursor[0] += width + 1;
+        self.row_height = self.row_height.max(height);
+        self.dirty = true;
+        let size = ATLAS_SIZE as f32;
+        let atlas_glyph = AtlasGlyph {
+            min: [bounds.min.x, bounds.min.y],
+            max: [bounds.min.x + width as f32, bounds.min.y + height as f32],
+            uv: [
+                x as f32 / size,
+                y as f32 / size,
+                (x + width) as f32 / size,
+                (y + height) as f32 / size,
+            ],
+        };
+        self.glyphs.insert((id, pixels), Some(atlas_glyph));
+        return Some(atlas_glyph);
+    }
+
+    // the lines of the text at the pixel size, with the ascent and the height of a line
+    fn layout_lines(&self, component: &TextComponent, pixels: f32) -> (f32, f32, Vec<TextLine>) {
+        let scaled = self.font.as_scaled(PxScale::from(pixels));
+        let advance = |previous: Option<char>, character: char| {
+            let id = scaled.glyph_id(character);
+            let kerning = previous.map(|previous| scaled.kern(scaled.glyph_id(previous), id)).unwrap_or(0.0);
+            kerning + scaled.h_advance(id)
+        };
+        // the maximum width is in the unit of the size
+        let max_width = component.max_width.map(|width| width * pixels / component.size);
+        let lines = break_lines(&component.text, max_width, advance);
+        let line_height = (scaled.ascent() - scaled.descent() + scaled.line_gap()) * component.line_spacing;
+        return (scaled.ascent(), line_height, lines);
+    }
+
+    // the width and height of the laid out text, in the unit of its size
+    pub fn measure(&self, component: &TextComponent) -> [f32; 2] {
+        let (_, line_height, lines) = self.layout_lines(component, component.size);
+        let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+        return [width, lines.len() as f32 * line_height];
+    }
+
+    // the quads of the glyphs of the text rasterized at the pixel size, place maps the layout,
+    // in pixels with y going down from the anchor, to the position of the vertices
+    pub fn build_vertices(
+        &mut self,
+        component: &TextComponent,
+        pixels: f32,
+        place: impl Fn(f32, f32) -> [f32; 3],
+        vertices: &mut Vec<TextVertex>,
+    ) {
+        let pixels = pixels.round().max(1.0) as u32;
+        let scale = PxScale::from(pixels as f32);
+        let (ascent, line_height, lines) = self.layout_lines(component, pixels as f32);
+        for (row, line) in lines.iter().enumerate() {
+            let mut x = match component.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => -line.width * 0.5,
+                TextAlign::Right => -line.width,
+            };
+            let baseline = ascent + row as f32 * line_height;
+            let mut previous: Option<GlyphId> = None;
+            for character in component.text[line.range.clone()].chars() {
+                let (id, kerning, advance) = {
+                    let scaled = self.font.as_scaled(scale);
+                    let id = scaled.glyph_id(character);
+                    let kerning = previous.map(|previous| scaled.kern(previous, id)).unwrap_or(0.0);
+                    (id, kerning, scaled.h_advance(id))
+                };
+                x += kerning;
+                if let Some(glyph) = self.glyph(id, pixels) {
+                    let [u0, v0, u1, v1] = glyph.uv;
+                    let left = x + glyph.min[0];
+                    let right = x + glyph.max[0];
+                    let top = baseline + glyph.min[1];
+                    let bottom = baseline + glyph.max[1];
+                    let corners = [
+                        (left, top, u0, v0),
+                        (left, bottom, u0, v1),
+                        (right, bottom, u1, v1),
+                        (left, top, u0, v0),
+                        (right, bottom, u1, v1),
+                        (right, top, u1, v0),
+                    ];
+                    for (px, py, u, v) in corners {
+                        vertices.push(TextVertex {
+                            position: place(px, py),
+                            tex_coord: [u, v],
+                            color: component.color,
+                        });
+                    }
+                }
+                x += advance;
+                previous = Some(id);
+            }
+        }
+    }
+}
+
+fn atlas_clears(fonts: &HashMap<String, FontAtlas>) -> u32 {
+    return fonts.values().map(|atlas| atlas.clears).sum();
+}
+
+// lays out the texts of a batch, an atlas which fills up in the middle is cleared and loses the
+// glyphs of the texts laid out before, so they are all laid out again, only once since the glyphs
+// of the batch may not fit in an atlas at all
+pub(crate) fn lay_out_texts<T>(
+    fonts: &mut HashMap<String, FontAtlas>,
+    mut lay_out: impl FnMut(&mut HashMap<String, FontAtlas>) -> T,
+) -> T {
+    let clears = atlas_clears(fonts);
+    let laid_out = lay_out(fonts);
+    if atlas_clears(fonts) == clears {
+        return laid_out;
+    }
+    let clears = atlas_clears(fonts);
+    let laid_out = lay_out(fonts);
+    if atlas_clears(fonts) != clears {
+        warn!(target: RENDER, "the glyphs of the texts don't fit in their atlas, some are missing");
+    }
+    return laid_out;
+}
+
+// maps the pixels of the window, y going down, to the clip space
+pub(crate) fn screen_projection(width: u32, height: u32) -> [[f32; 4]; 4] {
+    return [
+        [2.0 / width as f32, 0.0, 0.0, 0.0],
+        [0.0, -2.0 / height as f32, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0, 1.0],
+    ];
+}
+
+// what the text passes need from the scene
+pub struct TextContext<'a> {
+    pub world: &'a World,
+    pub fonts: &'a mut HashMap<String, FontAtlas>,
+    pub textures: &'a mut HashMap<String, Texture>,
+    pub program: &'a Program,
+}
+
+// draws the text components of one space with a call per font, returns the number of calls
+pub fn draw_texts<F: Facade, S: Surface>(
+    facade: &F,
+    target: &mut S,
+    context: TextContext,
+    camera: &Camera,
+    width: u32,
+    height: u32,
+    screen: bool,
+) -> Result<usize, EngineError> {
+    let view = camera.view_matrix();
+    let matrix: [[f32; 4]; 4] = if screen {
+        screen_projection(width, height)
+    } else {
+        (Matrix4::from(camera.perspective_matrix(width, height)) * Matrix4::from(view)).into()
+    };
+    // the rows of the view matrix are the axes of the camera
+    let right = Vector4::new(view[0][0], view[1][0], view[2][0], 0.0);
+    let up = Vector4::new(view[0][1], view[1][1], view[2][1], 0.0);
+    let forward = Vector4::new(view[0][2], view[1][2], view[2][2], 0.0);
+
+    let world = context.world;
+    let batches = lay_out_texts(context.fonts, |fonts| {
+        let mut batches: HashMap<&str, Vec<TextVertex>> = HashMap::new();
+        let mut query = <(&TextComponent, Option<&Transform>)>::query();
+        for (component, transform) in query.iter(world) {
+            if component.is_screen_space() != screen || component.text.is_empty() {
+                continue;
+            }
+            let Some(atlas) = fonts.get_mut(&component.font) else {
+                continue;
+            };
+            let vertices = batches.entry(component.font.as_str()).or_default();
+            match component.space {
+                TextSpace::Screen([x, y]) => {
+                    atlas.build_vertices(component, component.size, |px, py| [x + px, y + py, 0.0], vertices);
+                }
+                TextSpace::World { billboard } => {
+                    let Some(transform) = transform else {
+                        continue;
+                    };
+                    let model = if billboard {
+                        let position = transform.get_position();
+                        Matrix4::from_cols(right, up, forward, position.extend(1.0))
+                    } else {
+                        Matrix4::from(transform.uniform_matrix())
+                    };
+                    // the pixels of the layout become world units, with y going up
+                    let scale = component.size / WORLD_TEXT_PIXELS;
+                    let place = |px: f32, py: f32| {
+                        let position = model * Vector4::new(px * scale, -py * scale, 0.0, 1.0);
+                        [position.x, position.y, position.z]
+                    };
+                    atlas.build_vertices(component, WORLD_TEXT_PIXELS, place, vertices);
+                }
+            }
+        }
+        return batches;
+    });
+
+    let params = glium::DrawParameters {
+        depth: glium::Depth {
+            test: if screen {
+                glium::draw_parameters::DepthTest::Overwrite
+            } else {
+                glium::draw_parameters::DepthTest::IfLess
+            },
+            write: false,
+            ..Default::default()
+        },
+        blend: glium::Blend::alpha_blending(),
+        ..Default::default()
+    };
+    let mut draw_calls = 0;
+    for (font, vertices) in batches {
+        let key = atlas_key(font);
+        if let Some(atlas) = context.fonts.get_mut(font) {
+            if let Some(texture) = atlas.take_texture(facade)? {
+                context.textures.insert(key.clone(), texture);
+            }
+        }
+        let (Some(texture), false) = (context.textures.get(&key), vertices.is_empty()) else {
+            continue;
+        };
+        let buffer = VertexBuffer::new(facade, &vertices).map_err(|err| EngineError::upload("text", err))?;
+        let sampler = SamplerBehavior {
+            wrap_function: (SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp),
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            ..Default::default()
+        };
+        let mut uniforms = UniformBag::new();
+        uniforms.set("u_matrix", UniformValue::Mat4(matrix));
+        uniforms.set("u_atlas", texture.uniform_value(Some(sampler)));
+        target.draw(&buffer, NoIndices(PrimitiveType::TrianglesList), context.program, &uniforms, &params)?;
+        draw_calls += 1;
+    }
+    return Ok(draw_calls);
+}
+
+// draws the texts placed in the world over the objects of the scene
+pub struct WorldTextPass;
+
+impl RenderPass for WorldTextPass {
+    fn name(&self) -> &str {
+        return WORLD_TEXT_PASS;
+    }
+
+    fn setup(&self, info: &FrameInfo, builder: &mut PassBuilder) {
+        builder.write_scene(info);
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<(), EngineError> {
+        let PassContext {
+            scene,
+            display,
+            frame,
+            info,
+            resources,
+        } = context;
+        let mut target = resources.target(*display, frame, info.scene_target(), Some(SCENE_DEPTH))?;
+        return scene.draw_texts(display, &mut target, info.camera, info.width, info.height, false);
+    }
+}
+
+// draws the texts of the screen over the final image, after the post processing
+pub struct ScreenTextPass;
+
+impl RenderPass for ScreenTextPass {
+    fn name(&self) -> &str {
+        return SCREEN_TEXT_PASS;
+    }
+
+    fn setup(&self, _info: &FrameInfo, builder: &mut PassBuilder) {
+        builder.write(BACKBUFFER);
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<(), EngineError> {
+        let info = context.info;
+        let frame = &mut *context.frame;
+        return context.scene.draw_texts(context.display, frame, info.camera, info.width, info.height, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monospace(_: Option<char>, _: char) -> f32 {
+        return 1.0;
+    }
+
+    fn line_texts<'a>(text: &'a str, lines: &[TextLine]) -> Vec<&'a str> {
+        return lines.iter().map(|line| &text[line.range.clone()]).collect();
+    }
+
+    #[test]
+    fn lines_break_between_words() {
+        let text = "hello big world";
+        let lines = break_lines(text, Some(9.0), monospace);
+        assert_eq!(line_texts(text, &lines), vec!["hello big", "world"]);
+        assert_eq!(lines[0].width, 9.0);
+        assert_eq!(lines[1].width, 5.0);
+        // without maximum only the line breaks split the text
+        let text = "one\ntwo  three";
+        assert_eq!(line_texts(text, &break_lines(text, None, monospace)), vec!["one", "two  three"]);
+        // the long words stay whole
+        let text = "a verylongword b";
+        assert_eq!(line_texts(text, &break_lines(text, Some(4.0), monospace)), vec!["a", "verylongword", "b"]);
+    }
+
+    #[test]
+    fn kerning_is_part_of_the_width() {
+        // the v pulls the a closer
+        let kerned = |previous: Option<char>, character: char| match (previous, character) {
+            (Some('v'), 'a') => 0.5,
+            _ => 1.0,
+        };
+        assert_eq!(break_lines("vav", None, kerned)[0].width, 2.5);
+    }
+
+    #[test]
+    fn invalid_fonts_are_parse_errors() {
+        let result = FontAtlas::from_bytes("broken.ttf", vec![0; 16]);
+        assert!(matches!(result, Err(EngineError::Parse { .. })));
+    }
+}
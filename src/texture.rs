@@ -0,0 +1,932 @@
+#![allow(dead_code)]
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::NoIndices;
+use glium::index::PrimitiveType;
+use glium::texture::CompressedFormat;
+use glium::texture::CompressedMipmapsOption;
+use glium::texture::CompressedSrgbFormat;
+use glium::texture::CompressedSrgbTexture2d;
+use glium::texture::CompressedTexture2d;
+use glium::texture::CubeLayer;
+use glium::texture::Cubemap;
+use glium::texture::MipmapsOption;
+use glium::texture::RawImage2d;
+use glium::texture::SrgbCubemap;
+use glium::texture::SrgbFormat;
+use glium::texture::SrgbTexture2d;
+use glium::texture::Texture2d;
+use glium::texture::UncompressedFloatFormat;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
+use glium::uniforms::SamplerBehavior;
+use glium::uniforms::SamplerWrapFunction;
+use glium::uniforms::UniformValue;
+use glium::vertex::EmptyVertexAttributes;
+use glium::Rect;
+use glium::Surface;
+
+use image::io::Reader as ImageReader;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use tracing::debug;
+
+use crate::error::EngineError;
+use crate::graphic_component::load_shaders;
+use crate::logging::ASSETS;
+use crate::shader::ShaderVariant;
+use crate::tonemapping::FULLSCREEN_VERTEX_SHADER;
+use crate::uniforms::UniformBag;
+
+const CUBEMAP_FACE_FRAGMENT_SHADER: &str = "builtin/cubemap_face_fragment.glsl";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+}
+
+// what happens to the uvs outside of [0, 1]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WrapMode {
+    Repeat,
+    // the edge pixels are stretched
+    Clamp,
+    // every other repetition is flipped
+    Mirror,
+    // mirrored once then clamped
+    MirrorClamp,
+}
+
+impl WrapMode {
+    fn wrap_function(self) -> SamplerWrapFunction {
+        match self {
+            WrapMode::Repeat => SamplerWrapFunction::Repeat,
+            WrapMode::Clamp => SamplerWrapFunction::Clamp,
+            WrapMode::Mirror => SamplerWrapFunction::Mirror,
+            WrapMode::MirrorClamp => SamplerWrapFunction::MirrorClamp,
+        }
+    }
+}
+
+// how a texture is created and sampled, materials and graphic components give them per uniform
+// mipmaps and srgb are used when the texture is loaded, so the first settings a texture is loaded
+// with are the ones it keeps, the rest is applied every time it is bound
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureSettings {
+    pub mipmaps: bool,
+    pub min_filter: TextureFilter,
+    // how the mipmap levels are blended, only used with mipmaps
+    pub mipmap_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    // 1 disables anisotropic filtering, clamped to what the hardware supports
+    pub anisotropy: u16,
+    pub wrap_u: WrapMode,
+    pub wrap_v: WrapMode,
+    // colour textures are usually stored in srgb, data textures (normals, roughness...) are not
+    pub srgb: bool,
+}
+
+impl Default for TextureSettings {
+    fn default() -> Self {
+        TextureSettings {
+            mipmaps: true,
+            min_filter: TextureFilter::Linear,
+            mipmap_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            anisotropy: 1,
+            wrap_u: WrapMode::Repeat,
+            wrap_v: WrapMode::Repeat,
+            srgb: false,
+        }
+    }
+}
+
+impl TextureSettings {
+    // sharp pixels, for pixel art and lookup tables
+    pub fn nearest() -> Self {
+        TextureSettings {
+            mipmaps: false,
+            min_filter: TextureFilter::Nearest,
+            mag_filter: TextureFilter::Nearest,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap_u = wrap;
+        self.wrap_v = wrap;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: u16) -> Self {
+        self.anisotropy = anisotropy.max(1);
+        self
+    }
+
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn mipmaps_option(&self) -> MipmapsOption {
+        if self.mipmaps {
+            return MipmapsOption::AutoGeneratedMipmaps;
+        }
+        return MipmapsOption::NoMipmap;
+    }
+
+    pub fn sampler(&self) -> SamplerBehavior {
+        use TextureFilter::*;
+        let minify_filter = match (self.mipmaps, self.min_filter, self.mipmap_filter) {
+            (false, Nearest, _) => MinifySamplerFilter::Nearest,
+            (false, Linear, _) => MinifySamplerFilter::Linear,
+            (true, Nearest, Nearest) => MinifySamplerFilter::NearestMipmapNearest,
+            (true, Nearest, Linear) => MinifySamplerFilter::NearestMipmapLinear,
+            (true, Linear, Nearest) => MinifySamplerFilter::LinearMipmapNearest,
+            (true, Linear, Linear) => MinifySamplerFilter::LinearMipmapLinear,
+        };
+        let magnify_filter = match self.mag_filter {
+            Nearest => MagnifySamplerFilter::Nearest,
+            Linear => MagnifySamplerFilter::Linear,
+        };
+        SamplerBehavior {
+            wrap_function: (
+                self.wrap_u.wrap_function(),
+                self.wrap_v.wrap_function(),
+                self.wrap_v.wrap_function(),
+            ),
+            minify_filter,
+            magnify_filter,
+            max_anisotropy: self.anisotropy.max(1),
+            ..Default::default()
+        }
+    }
+}
+
+// the key of a texture in the textures of a scene, the settings which change the texture itself
+// (srgb and mipmaps) are part of it so that a file can be used both as colour and as data, the
+// others only change the sampler of every binding
+// the builtin textures are created by the engine once and are the same whatever the settings
+pub fn texture_cache_key<'a>(path: &'a str, settings: &TextureSettings) -> Cow<'a, str> {
+    if path.starts_with("builtin/") || (!settings.srgb && settings.mipmaps) {
+        return Cow::Borrowed(path);
+    }
+    let srgb = if settings.srgb { "#srgb" } else { "" };
+    let mipmaps = if settings.mipmaps { "" } else { "#nomips" };
+    return Cow::Owned(format!("{}{}{}", path, srgb, mipmaps));
+}
+
+// the textures of a scene, srgb ones are converted to linear by the gpu when sampled
+// float textures (hdr, exr, bc6h...) are linear textures with a float format
+pub enum Texture {
+    Linear(Texture2d),
+    Srgb(SrgbTexture2d),
+    Compressed(CompressedTexture2d),
+    CompressedSrgb(CompressedSrgbTexture2d),
+    Cubemap(Cubemap),
+    SrgbCubemap(SrgbCubemap),
+}
+
+impl Texture {
+    pub fn uniform_value(&self, sampler: Option<SamplerBehavior>) -> UniformValue<'_> {
+        match self {
+            Texture::Linear(texture) => UniformValue::Texture2d(texture, sampler),
+            Texture::Srgb(texture) => UniformValue::SrgbTexture2d(texture, sampler),
+            Texture::Compressed(texture) => UniformValue::CompressedTexture2d(texture, sampler),
+            Texture::CompressedSrgb(texture) => UniformValue::CompressedSrgbTexture2d(texture, sampler),
+            Texture::Cubemap(texture) => UniformValue::Cubemap(texture, sampler),
+            Texture::SrgbCubemap(texture) => UniformValue::SrgbCubemap(texture, sampler),
+        }
+    }
+
+    // the size of a face for cubemaps
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Texture::Linear(texture) => texture.dimensions(),
+            Texture::Srgb(texture) => texture.dimensions(),
+            Texture::Compressed(texture) => texture.dimensions(),
+            Texture::CompressedSrgb(texture) => texture.dimensions(),
+            Texture::Cubemap(texture) => (texture.dimensions(), texture.dimensions()),
+            Texture::SrgbCubemap(texture) => (texture.dimensions(), texture.dimensions()),
+        }
+    }
+
+    pub fn mipmap_levels(&self) -> u32 {
+        match self {
+            Texture::Linear(texture) => texture.get_mipmap_levels(),
+            Texture::Srgb(texture) => texture.get_mipmap_levels(),
+            Texture::Compressed(texture) => texture.get_mipmap_levels(),
+            Texture::CompressedSrgb(texture) => texture.get_mipmap_levels(),
+            Texture::Cubemap(texture) => texture.get_mipmap_levels(),
+            Texture::SrgbCubemap(texture) => texture.get_mipmap_levels(),
+        }
+    }
+
+    // cubemaps are bound to samplerCube uniforms, the other textures to sampler2D ones
+    pub fn is_cubemap(&self) -> bool {
+        return matches!(self, Texture::Cubemap(_) | Texture::SrgbCubemap(_));
+    }
+}
+
+impl From<Texture2d> for Texture {
+    fn from(texture: Texture2d) -> Self {
+        Texture::Linear(texture)
+    }
+}
+
+// the format is chosen from the extension:
+// - dds and ktx2 files are uploaded with their own mip chain, block compressed formats stay
+//   compressed on the gpu, and the files with six faces become cubemaps
+// - hdr and exr files become float textures, they are never srgb
+// - everything else is decoded to rgba8 by the image crate
+pub fn load_texture<F: Facade>(
+    texture_path: &str,
+    settings: &TextureSettings,
+    facade: &F,
+) -> Result<Texture, EngineError> {
+    debug!(target: ASSETS, path = texture_path, ?settings, "loading texture");
+    let extension = Path::new(texture_path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("dds") => {
+            let data = fs::read(texture_path).map_err(|err| EngineError::io(texture_path, err))?;
+            let image = read_dds(texture_path, &data)?;
+            return upload_container(texture_path, &image, settings, facade);
+        }
+        Some("ktx2") => {
+            let data = fs::read(texture_path).map_err(|err| EngineError::io(texture_path, err))?;
+            let image = read_ktx2(texture_path, &data)?;
+            return upload_container(texture_path, &image, settings, facade);
+        }
+        Some("hdr") | Some("exr") => return load_float_texture(texture_path, settings, facade),
+        _ => {}
+    }
+
+    let image = ImageReader::open(texture_path)
+        .map_err(|err| EngineError::io(texture_path, err))?
+        .decode()
+        .map_err(|err| EngineError::from_image(texture_path, err))?
+        .to_rgba8();
+    let image_dimensions = image.dimensions();
+    let image = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
+    let upload_error = |err| EngineError::upload(texture_path, err);
+    if settings.srgb {
+        let texture = SrgbTexture2d::with_mipmaps(facade, image, settings.mipmaps_option());
+        return Ok(Texture::Srgb(texture.map_err(upload_error)?));
+    }
+    let texture = Texture2d::with_mipmaps(facade, image, settings.mipmaps_option());
+    return Ok(Texture::Linear(texture.map_err(upload_error)?));
+}
+
+// radiance and openexr images, stored as half floats on the gpu
+fn load_float_texture<F: Facade>(
+    texture_path: &str,
+    settings: &TextureSettings,
+    facade: &F,
+) -> Result<Texture, EngineError> {
+    let image = ImageReader::open(texture_path)
+        .map_err(|err| EngineError::io(texture_path, err))?
+        .decode()
+        .map_err(|err| EngineError::from_image(texture_path, err))?
+        .to_rgba32f();
+    let image_dimensions = image.dimensions();
+    let image = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
+    let texture = Texture2d::with_format(
+        facade,
+        image,
+        UncompressedFloatFormat::F16F16F16F16,
+        settings.mipmaps_option(),
+    )
+    .map_err(|err| EngineError::upload(texture_path, err))?;
+    return Ok(Texture::Linear(texture));
+}
+
+// the pixel formats of dds and ktx2 files we know how to upload
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ContainerFormat {
+    Compressed(CompressedFormat),
+    CompressedSrgb(CompressedSrgbFormat),
+    Rgba8 { srgb: bool },
+    Bgra8 { srgb: bool },
+    Rgba16Float,
+    Rgba32Float,
+}
+
+impl ContainerFormat {
+    // width and height in pixels of a block, and its size in bytes
+    fn block(self) -> (u32, usize) {
+        use CompressedFormat::*;
+        match self {
+            ContainerFormat::Compressed(S3tcDxt1Alpha | S3tcDxt1NoAlpha | RgtcFormatU | RgtcFormatI) => {
+                (4, 8)
+            }
+            ContainerFormat::CompressedSrgb(
+                CompressedSrgbFormat::S3tcDxt1Alpha | CompressedSrgbFormat::S3tcDxt1NoAlpha,
+            ) => (4, 8),
+            ContainerFormat::Compressed(_) | ContainerFormat::CompressedSrgb(_) => (4, 16),
+            ContainerFormat::Rgba8 { .. } | ContainerFormat::Bgra8 { .. } => (1, 4),
+            ContainerFormat::Rgba16Float => (1, 8),
+            ContainerFormat::Rgba32Float => (1, 16),
+        }
+    }
+
+    fn level_size(self, width: u32, height: u32) -> usize {
+        let (block, bytes) = self.block();
+        let blocks = width.div_ceil(block) as usize * height.div_ceil(block) as usize;
+        return blocks * bytes;
+    }
+
+    fn is_float(self) -> bool {
+        use CompressedFormat::*;
+        return matches!(
+            self,
+            ContainerFormat::Compressed(BptcSignedFloat3 | BptcUnsignedFloat3)
+                | ContainerFormat::Rgba16Float
+                | ContainerFormat::Rgba32Float
+        );
+    }
+
+    fn is_srgb(self) -> bool {
+        return matches!(
+            self,
+            ContainerFormat::CompressedSrgb(_)
+                | ContainerFormat::Rgba8 { srgb: true }
+                | ContainerFormat::Bgra8 { srgb: true }
+        );
+    }
+
+    // glium refuses compressed levels whose sides aren't multiples of the block size, except
+    // for the last levels which are smaller than a block
+    fn valid_level(self, width: u32, height: u32) -> bool {
+        let (block, _) = self.block();
+        return (width < block || width.is_multiple_of(block)) && (height < block || height.is_multiple_of(block));
+    }
+}
+
+// the images of a dds or ktx2 file, only the first layer of arrays is kept
+struct ContainerImage {
+    format: ContainerFormat,
+    width: u32,
+    height: u32,
+    // one entry per face (1, or 6 for cubemaps) holding the mip chain, largest level first
+    faces: Vec<Vec<Vec<u8>>>,
+}
+
+fn level_dimensions(width: u32, height: u32, level: u32) -> (u32, u32) {
+    return ((width >> level).max(1), (height >> level).max(1));
+}
+
+fn read_dds(path: &str, data: &[u8]) -> Result<ContainerImage, EngineError> {
+    use ddsfile::D3DFormat;
+    use ddsfile::DxgiFormat;
+
+    let dds = ddsfile::Dds::read(data).map_err(|err| EngineError::parse(path, err))?;
+    let format = match (dds.get_dxgi_format(), dds.get_d3d_format()) {
+        (Some(DxgiFormat::BC1_UNorm), _) | (_, Some(D3DFormat::DXT1)) => {
+            ContainerFormat::Compressed(CompressedFormat::S3tcDxt1Alpha)
+        }
+        (Some(DxgiFormat::BC1_UNorm_sRGB), _) => {
+            ContainerFormat::CompressedSrgb(CompressedSrgbFormat::S3tcDxt1Alpha)
+        }
+        (Some(DxgiFormat::BC2_UNorm), _) | (_, Some(D3DFormat::DXT3)) => {
+            ContainerFormat::Compressed(CompressedFormat::S3tcDxt3Alpha)
+        }
+        (Some(DxgiFormat::BC2_UNorm_sRGB), _) => {
+            ContainerFormat::CompressedSrgb(CompressedSrgbFormat::S3tcDxt3Alpha)
+        }
+        (Some(DxgiFormat::BC3_UNorm), _) | (_, Some(D3DFormat::DXT5)) => {
+            ContainerFormat::Compressed(CompressedFormat::S3tcDxt5Alpha)
+        }
+        (Some(DxgiFormat::BC3_UNorm_sRGB), _) => {
+            ContainerFormat::CompressedSrgb(CompressedSrgbFormat::S3tcDxt5Alpha)
+        }
+        (Some(DxgiFormat::BC4_UNorm), _) => ContainerFormat::Compressed(CompressedFormat::RgtcFormatU),
+        (Some(DxgiFormat::BC4_SNorm), _) => ContainerFormat::Compressed(CompressedFormat::RgtcFormatI),
+        (Some(DxgiFormat::BC5_UNorm), _) => ContainerFormat::Compressed(CompressedFormat::RgtcFormatUU),
+        (Some(DxgiFormat::BC5_SNorm), _) => ContainerFormat::Compressed(CompressedFormat::RgtcFormatII),
+        (Some(DxgiFormat::BC6H_UF16), _) => {
+            ContainerFormat::Compressed(CompressedFormat::BptcUnsignedFloat3)
+        }
+        (Some(DxgiFormat::BC6H_SF16), _) => ContainerFormat::Compressed(CompressedFormat::BptcSignedFloat3),
+        (Some(DxgiFormat::BC7_UNorm), _) => ContainerFormat::Compressed(CompressedFormat::BptcUnorm4),
+        (Some(DxgiFormat::BC7_UNorm_sRGB), _) => ContainerFormat::CompressedSrgb(CompressedSrgbFormat::Bptc),
+        (Some(DxgiFormat::R8G8B8A8_UNorm), _) | (_, Some(D3DFormat::A8B8G8R8)) => {
+            ContainerFormat::Rgba8 { srgb: false }
+        }
+        (Some(DxgiFormat::R8G8B8A8_UNorm_sRGB), _) => ContainerFormat::Rgba8 { srgb: true },
+        (Some(DxgiFormat::B8G8R8A8_UNorm), _) | (_, Some(D3DFormat::A8R8G8B8)) => {
+            ContainerFormat::Bgra8 { srgb: false }
+        }
+        (Some(DxgiFormat::B8G8R8A8_UNorm_sRGB), _) => ContainerFormat::Bgra8 { srgb: true },
+        (Some(DxgiFormat::R16G16B16A16_Float), _) | (_, Some(D3DFormat::A16B16G16R16F)) => {
+            ContainerFormat::Rgba16Float
+        }
+        (Some(DxgiFormat::R32G32B32A32_Float), _) | (_, Some(D3DFormat::A32B32G32R32F)) => {
+            ContainerFormat::Rgba32Float
+        }
+        (dxgi, d3d) => {
+            let message = format!("unsupported pixel format (dxgi {:?}, d3d {:?})", dxgi, d3d);
+            return Err(EngineError::parse(path, message));
+        }
+    };
+
+    let is_cubemap = match &dds.header10 {
+        Some(header10) => header10.misc_flag.contains(ddsfile::MiscFlag::TEXTURECUBE),
+        None => dds.header.caps2.contains(ddsfile::Caps2::CUBEMAP),
+    };
+    let (width, height) = (dds.get_width(), dds.get_height());
+    let levels = dds.get_num_mipmap_levels().max(1);
+    let face_count = if is_cubemap { 6 } else { 1 };
+    if dds.get_depth() > 1 {
+        return Err(EngineError::parse(path, "volume textures are not supported"));
+    }
+
+    // the faces follow each other, each with its whole mip chain
+    let mut offset = 0;
+    let mut faces = Vec::with_capacity(face_count);
+    for _ in 0..face_count {
+        let mut chain = Vec::with_capacity(levels as usize);
+        for level in 0..levels {
+            let (level_width, level_height) = level_dimensions(width, height, level);
+            let size = format.level_size(level_width, level_height);
+            let Some(bytes) = dds.data.get(offset..offset + size) else {
+                return Err(EngineError::parse(path, "the file is shorter than its header says"));
+            };
+            chain.push(bytes.to_vec());
+            offset += size;
+        }
+        faces.push(chain);
+    }
+    return Ok(ContainerImage {
+        format,
+        width,
+        height,
+        faces,
+    });
+}
+
+fn read_ktx2(path: &str, data: &[u8]) -> Result<ContainerImage, EngineError> {
+    use ktx2::Format;
+
+    let reader = ktx2::Reader::new(data).map_err(|err| EngineError::parse(path, err))?;
+    let header = reader.header();
+    if let Some(scheme) = header.supercompression_scheme {
+        let message = format!("supercompression {:?} is not supported", scheme);
+        return Err(EngineError::parse(path, message));
+    }
+    let format = match header.format {
+        Some(Format::BC1_RGBA_UNORM_BLOCK) => ContainerFormat::Compressed(CompressedFormat::S3tcDxt1Alpha),
+        Some(Format::BC1_RGB_UNORM_BLOCK) => ContainerFormat::Compressed(CompressedFormat::S3tcDxt1NoAlpha),
+        Some(Format::BC1_RGBA_SRGB_BLOCK) => {
+            ContainerFormat::CompressedSrgb(CompressedSrgbFormat::S3tcDxt1Alpha)
+        }
+        Some(Format::BC1_RGB_SRGB_BLOCK) => {
+            ContainerFormat::CompressedSrgb(CompressedSrgbFormat::S3tcDxt1NoAlpha)
+        }
+        Some(Format::BC2_UNORM_BLOCK) => ContainerFormat::Compressed(CompressedFormat::S3tcDxt3Alpha),
+        Some(Format::BC2_SRGB_BLOCK) => ContainerFormat::CompressedSrgb(CompressedSrgbFormat::S3tcDxt3Alpha),
+        Some(Format::BC3_UNORM_BLOCK) => ContainerFormat::Compressed(CompressedFormat::S3tcDxt5Alpha),
+        Some(Format::BC3_SRGB_BLOCK) => ContainerFormat::CompressedSrgb(CompressedSrgbFormat::S3tcDxt5Alpha),
+        Some(Format::BC4_UNORM_BLOCK) => ContainerFormat::Compressed(CompressedFormat::RgtcFormatU),
+        Some(Format::BC4_SNORM_BLOCK) => ContainerFormat::Compressed(CompressedFormat::RgtcFormatI),
+        Some(Format::BC5_UNORM_BLOCK) => ContainerFormat::Compressed(CompressedFormat::RgtcFormatUU),
+        Some(Format::BC5_SNORM_BLOCK) => ContainerFormat::Compressed(CompressedFormat::RgtcFormatII),
+        Some(Format::BC6H_UFLOAT_BLOCK) => ContainerFormat::Compressed(CompressedFormat::BptcUnsignedFloat3),
+        Some(Format::BC6H_SFLOAT_BLOCK) => ContainerFormat::Compressed(CompressedFormat::BptcSignedFloat3),
+        Some(Format::BC7_UNORM_BLOCK) => ContainerFormat::Compressed(CompressedFormat::BptcUnorm4),
+        Some(Format::BC7_SRGB_BLOCK) => ContainerFormat::CompressedSrgb(CompressedSrgbFormat::Bptc),
+        Some(Format::R8G8B8A8_UNORM) => ContainerFormat::Rgba8 { srgb: false },
+        Some(Format::R8G8B8A8_SRGB) => ContainerFormat::Rgba8 { srgb: true },
+        Some(Format::B8G8R8A8_UNORM) => ContainerFormat::Bgra8 { srgb: false },
+        Some(Format::B8G8R8A8_SRGB) => ContainerFormat::Bgra8 { srgb: true },
+        Some(Format::R16G16B16A16_SFLOAT) => ContainerFormat::Rgba16Float,
+        Some(Format::R32G32B32A32_SFLOAT) => ContainerFormat::Rgba32Float,
+        format => {
+            let message = format!("unsupported pixel format {:?}", format);
+            return Err(EngineError::parse(path, message));
+        }
+    };
+    if header.pixel_depth > 1 {
+        return Err(EngineError::parse(path, "volume textures are not supported"));
+    }
+
+    // every level holds the faces of every layer one after the other, the first layer comes first
+    let face_count = header.face_count.max(1) as usize;
+    let mut faces = vec![Vec::new(); face_count];
+    for (level, data) in reader.levels().enumerate() {
+        let (level_width, level_height) = level_dimensions(header.pixel_width, header.pixel_height, level as u32);
+        let size = format.level_size(level_width, level_height);
+        for (face, chain) in faces.iter_mut().enumerate() {
+            let Some(bytes) = data.data.get(face * size..(face + 1) * size) else {
+                return Err(EngineError::parse(path, "a mip level is shorter than its dimensions say"));
+            };
+            chain.push(bytes.to_vec());
+        }
+    }
+    return Ok(ContainerImage {
+        format,
+        width: header.pixel_width,
+        height: header.pixel_height.max(1),
+        faces,
+    });
+}
+
+fn upload_container<F: Facade>(
+    path: &str,
+    image: &ContainerImage,
+    settings: &TextureSettings,
+    facade: &F,
+) -> Result<Texture, EngineError> {
+    match image.faces.len() {
+        1 => return upload_face(path, image, 0, settings, facade),
+        6 => return upload_cubemap(path, image, settings, facade),
+        count => {
+            let message = format!("{} faces, expected 1 or 6", count);
+            return Err(EngineError::parse(path, message));
+        }
+    }
+}
+
+// converts the uncompressed levels to what glium takes, the dds and ktx2 images are uploaded as
+// they are stored, with the top row first, since blocks can't be flipped like pixels they should
+// be exported flipped vertically when they are used with uvs starting at the bottom
+fn rgba8_level(format: ContainerFormat, bytes: &[u8], dimensions: (u32, u32)) -> RawImage2d<'static, u8> {
+    let mut pixels = bytes.to_vec();
+    if let ContainerFormat::Bgra8 { .. } = format {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    return RawImage2d::from_raw_rgba(pixels, dimensions);
+}
+
+fn float_level(format: ContainerFormat, bytes: &[u8], dimensions: (u32, u32)) -> RawImage2d<'static, f32> {
+    let pixels = match format {
+        ContainerFormat::Rgba16Float => bytes
+            .chunks_exact(2)
+            .map(|half| half::f16::from_le_bytes([half[0], half[1]]).to_f32())
+            .collect(),
+        _ => bytes
+            .chunks_exact(4)
+            .map(|float| f32::from_le_bytes([float[0], float[1], float[2], float[3]]))
+            .collect(),
+    };
+    return RawImage2d::from_raw_rgba(pixels, dimensions);
+}
+
+// uploads one face of the image with its mip chain, when the file has no mip chain, the mipmaps
+// are generated for the uncompressed formats only
+fn upload_face<F: Facade>(
+    path: &str,
+    image: &ContainerImage,
+    face: usize,
+    settings: &TextureSettings,
+    facade: &F,
+) -> Result<Texture, EngineError> {
+    let format = image.format;
+    let chain = &image.faces[face];
+    let levels = chain
+        .iter()
+        .enumerate()
+        .take_while(|(level, _)| {
+            let (width, height) = level_dimensions(image.width, image.height, *level as u32);
+            format.valid_level(width, height)
+        })
+        .count();
+    if levels == 0 {
+        let message = format!("{}x{} is not a multiple of the block size", image.width, image.height);
+        return Err(EngineError::parse(path, message));
+    }
+    let levels = if settings.mipmaps { levels as u32 } else { 1 };
+    let mipmaps = match (levels, settings.mipmaps) {
+        (1, true) => MipmapsOption::AutoGeneratedMipmaps,
+        (1, false) => MipmapsOption::NoMipmap,
+        (levels, _) => MipmapsOption::EmptyMipmapsMax(levels - 1),
+    };
+    let compressed_mipmaps = match levels {
+        1 => CompressedMipmapsOption::NoMipmap,
+        levels => CompressedMipmapsOption::EmptyMipmapsMax(levels - 1),
+    };
+    let dimensions = (image.width, image.height);
+    let upload_error = |err| EngineError::upload(path, err);
+    let level_error = |_| EngineError::upload(path, "mip level rejected");
+    // the rectangle covering a mip level, with its dimensions
+    let level_rect = |level: u32| {
+        let (width, height) = level_dimensions(image.width, image.height, level);
+        let rect = Rect {
+            left: 0,
+            bottom: 0,
+            width,
+            height,
+        };
+        (rect, width, height)
+    };
+
+    match format {
+        ContainerFormat::Compressed(compressed) => {
+            let (width, height) = dimensions;
+            let texture = CompressedTexture2d::with_compressed_data(
+                facade,
+                &chain[0],
+                width,
+                height,
+                compressed,
+                compressed_mipmaps,
+            )
+            .map_err(upload_error)?;
+            for level in 1..levels {
+                let (rect, width, height) = level_rect(level);
+                if let Some(mipmap) = texture.mipmap(level) {
+                    mipmap
+                        .write_compressed_data(rect, &chain[level as usize], width, height, compressed)
+                        .map_err(level_error)?;
+                }
+            }
+            return Ok(Texture::Compressed(texture));
+        }
+        ContainerFormat::CompressedSrgb(compressed) => {
+            let (width, height) = dimensions;
+            let texture = CompressedSrgbTexture2d::with_compressed_data(
+                facade,
+                &chain[0],
+                width,
+                height,
+                compressed,
+                compressed_mipmaps,
+            )
+            .map_err(upload_error)?;
+            for level in 1..levels {
+                let (rect, width, height) = level_rect(level);
+                if let Some(mipmap) = texture.mipmap(level) {
+                    mipmap
+                        .write_compressed_data(rect, &chain[level as usize], width, height, compressed)
+                        .map_err(level_error)?;
+                }
+            }
+            return Ok(Texture::CompressedSrgb(texture));
+        }
+        ContainerFormat::Rgba8 { srgb: true } | ContainerFormat::Bgra8 { srgb: true } => {
+            let base = rgba8_level(format, &chain[0], dimensions);
+            let texture = SrgbTexture2d::with_mipmaps(facade, base, mipmaps).map_err(upload_error)?;
+            for level in 1..levels {
+                let (rect, width, height) = level_rect(level);
+                if let Some(mipmap) = texture.mipmap(level) {
+                    mipmap.write(rect, rgba8_level(format, &chain[level as usize], (width, height)));
+                }
+            }
+            return Ok(Texture::Srgb(texture));
+        }
+        ContainerFormat::Rgba8 { .. } | ContainerFormat::Bgra8 { .. } => {
+            let base = rgba8_level(format, &chain[0], dimensions);
+            let texture = Texture2d::with_mipmaps(facade, base, mipmaps).map_err(upload_error)?;
+            for level in 1..levels {
+                let (rect, width, height) = level_rect(level);
+                if let Some(mipmap) = texture.mipmap(level) {
+                    mipmap.write(rect, rgba8_level(format, &chain[level as usize], (width, height)));
+                }
+            }
+            return Ok(Texture::Linear(texture));
+        }
+        ContainerFormat::Rgba16Float | ContainerFormat::Rgba32Float => {
+            let gpu_format = match format {
+                ContainerFormat::Rgba16Float => UncompressedFloatFormat::F16F16F16F16,
+                _ => UncompressedFloatFormat::F32F32F32F32,
+            };
+            let base = float_level(format, &chain[0], dimensions);
+            let texture = Texture2d::with_format(facade, base, gpu_format, mipmaps).map_err(upload_error)?;
+            for level in 1..levels {
+                let (rect, width, height) = level_rect(level);
+                if let Some(mipmap) = texture.mipmap(level) {
+                    mipmap.write(rect, float_level(format, &chain[level as usize], (width, height)));
+                }
+            }
+            return Ok(Texture::Linear(texture));
+        }
+    }
+}
+
+// glium can't write to the faces of a cubemap, so every face is uploaded as a 2d texture then
+// drawn into the cubemap, level by level, which also means compressed cubemaps are decompressed
+fn upload_cubemap<F: Facade>(
+    path: &str,
+    image: &ContainerImage,
+    settings: &TextureSettings,
+    facade: &F,
+) -> Result<Texture, EngineError> {
+    if image.width != image.height {
+        let message = format!("cubemap faces are {}x{}, they must be square", image.width, image.height);
+        return Err(EngineError::parse(path, message));
+    }
+    let faces = (0..6)
+        .map(|face| upload_face(path, image, face, settings, facade))
+        .collect::<Result<Vec<_>, _>>()?;
+    let levels = faces[0].mipmap_levels();
+    let mipmaps = match levels {
+        1 => MipmapsOption::NoMipmap,
+        levels => MipmapsOption::EmptyMipmapsMax(levels - 1),
+    };
+    let upload_error = |err| EngineError::upload(path, err);
+    let program = load_shaders(&cubemap_face_variant(), facade)?;
+
+    let texture = if image.format.is_float() {
+        let cubemap = Cubemap::empty_with_format(facade, UncompressedFloatFormat::F16F16F16F16, mipmaps, image.width);
+        Texture::Cubemap(cubemap.map_err(upload_error)?)
+    } else if image.format.is_srgb() {
+        let cubemap = SrgbCubemap::empty_with_format(facade, SrgbFormat::U8U8U8U8, mipmaps, image.width);
+        Texture::SrgbCubemap(cubemap.map_err(upload_error)?)
+    } else {
+        let cubemap = Cubemap::empty_with_format(facade, UncompressedFloatFormat::U8U8U8U8, mipmaps, image.width);
+        Texture::Cubemap(cubemap.map_err(upload_error)?)
+    };
+
+    // same order as the faces of dds and ktx2 files
+    let layers = [
+        CubeLayer::PositiveX,
+        CubeLayer::NegativeX,
+        CubeLayer::PositiveY,
+        CubeLayer::NegativeY,
+        CubeLayer::PositiveZ,
+        CubeLayer::NegativeZ,
+    ];
+    let sampler = SamplerBehavior {
+        minify_filter: MinifySamplerFilter::NearestMipmapNearest,
+        magnify_filter: MagnifySamplerFilter::Nearest,
+        ..Default::default()
+    };
+    for level in 0..levels {
+        // a file which declares more levels than its size allows
+        let missing_level = || EngineError::parse(path, format!("the cubemap has no mip level {}", level));
+        for (face, layer) in faces.iter().zip(layers) {
+            let mut framebuffer = match &texture {
+                Texture::Cubemap(cubemap) => {
+                    let image = cubemap.mipmap(level).ok_or_else(missing_level)?.image(layer);
+                    SimpleFrameBuffer::new(facade, image)
+                }
+                Texture::SrgbCubemap(cubemap) => {
+                    let image = cubemap.mipmap(level).ok_or_else(missing_level)?.image(layer);
+                    SimpleFrameBuffer::new(facade, image)
+                }
+                _ => unreachable!(),
+            }
+            .map_err(|err| EngineError::upload(path, err))?;
+            let mut uniforms = UniformBag::new();
+            uniforms.set("u_face", face.uniform_value(Some(sampler)));
+            uniforms.set("u_level", UniformValue::Float(level as f32));
+            framebuffer.draw(
+                EmptyVertexAttributes { len: 3 },
+                NoIndices(PrimitiveType::TrianglesList),
+                &program,
+                &uniforms,
+                &Default::default(),
+            )?;
+        }
+    }
+    return Ok(texture);
+}
+
+fn cubemap_face_variant() -> ShaderVariant {
+    return ShaderVariant::new(FULLSCREEN_VERTEX_SHADER, CUBEMAP_FACE_FRAGMENT_SHADER);
+}
+
+// 1x1 texture of the given colour
+pub fn solid_texture<F: Facade>(facade: &F, rgba: [u8; 4]) -> Result<Texture, EngineError> {
+    let image = RawImage2d::from_raw_rgba(rgba.to_vec(), (1, 1));
+    let texture = Texture2d::with_mipmaps(facade, image, MipmapsOption::NoMipmap)
+        .map_err(|err| EngineError::upload(&format!("solid texture {:?}", rgba), err))?;
+    return Ok(Texture::Linear(texture));
+}
+
+// what the textures which couldn't be loaded are replaced with, impossible to miss
+pub fn missing_texture<F: Facade>(facade: &F) -> Result<Texture, EngineError> {
+    return solid_texture(facade, [255, 0, 255, 255]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ddsfile::AlphaMode;
+    use ddsfile::D3D10ResourceDimension;
+    use ddsfile::Dds;
+    use ddsfile::DxgiFormat;
+    use ddsfile::NewDxgiParams;
+
+    fn dds_file(format: DxgiFormat, size: u32, mipmap_levels: u32, is_cubemap: bool) -> Vec<u8> {
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height: size,
+            width: size,
+            depth: None,
+            format,
+            mipmap_levels: Some(mipmap_levels),
+            array_layers: if is_cubemap { Some(6) } else { None },
+            caps2: None,
+            is_cubemap,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+        // every byte holds its index so that the levels can be told apart
+        for (i, byte) in dds.data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let mut file = Vec::new();
+        dds.write(&mut file).unwrap();
+        return file;
+    }
+
+    #[test]
+    fn settings_become_samplers() {
+        let sampler = TextureSettings::default().sampler();
+        assert_eq!(sampler.minify_filter, MinifySamplerFilter::LinearMipmapLinear);
+        assert_eq!(sampler.magnify_filter, MagnifySamplerFilter::Linear);
+        assert_eq!(sampler.max_anisotropy, 1);
+        assert_eq!(sampler.wrap_function.0, SamplerWrapFunction::Repeat);
+
+        let sampler = TextureSettings::nearest().with_wrap(WrapMode::Clamp).sampler();
+        assert_eq!(sampler.minify_filter, MinifySamplerFilter::Nearest);
+        assert_eq!(sampler.magnify_filter, MagnifySamplerFilter::Nearest);
+        assert_eq!(sampler.wrap_function.0, SamplerWrapFunction::Clamp);
+        assert_eq!(sampler.wrap_function.1, SamplerWrapFunction::Clamp);
+
+        let settings = TextureSettings {
+            min_filter: TextureFilter::Nearest,
+            mipmap_filter: TextureFilter::Linear,
+            wrap_u: WrapMode::Mirror,
+            wrap_v: WrapMode::MirrorClamp,
+            ..Default::default()
+        };
+        let sampler = settings.with_anisotropy(16).sampler();
+        assert_eq!(sampler.minify_filter, MinifySamplerFilter::NearestMipmapLinear);
+        assert_eq!(sampler.max_anisotropy, 16);
+        assert_eq!(sampler.wrap_function.0, SamplerWrapFunction::Mirror);
+        assert_eq!(sampler.wrap_function.1, SamplerWrapFunction::MirrorClamp);
+        // anisotropy can't go below 1, which disables it
+        assert_eq!(settings.with_anisotropy(0).sampler().max_anisotropy, 1);
+    }
+
+    #[test]
+    fn settings_which_change_the_texture_are_cached_apart() {
+        let linear = TextureSettings::default();
+        let srgb = TextureSettings::default().with_srgb(true);
+        assert_eq!(texture_cache_key("mask.png", &linear), "mask.png");
+        assert_eq!(texture_cache_key("mask.png", &srgb), "mask.png#srgb");
+        assert_eq!(texture_cache_key("mask.png", &TextureSettings::nearest()), "mask.png#nomips");
+        // only the sampler changes
+        let clamped = linear.with_wrap(WrapMode::Clamp).with_anisotropy(8);
+        assert_eq!(texture_cache_key("mask.png", &clamped), "mask.png");
+        assert_eq!(texture_cache_key("builtin/white", &srgb), "builtin/white");
+    }
+
+    #[test]
+    fn block_compressed_level_sizes() {
+        let bc1 = ContainerFormat::Compressed(CompressedFormat::S3tcDxt1Alpha);
+        let bc7 = ContainerFormat::Compressed(CompressedFormat::BptcUnorm4);
+        assert_eq!(bc1.level_size(16, 16), 128);
+        assert_eq!(bc7.level_size(16, 16), 256);
+        // the last levels still take a whole block
+        assert_eq!(bc1.level_size(2, 1), 8);
+        assert!(bc1.valid_level(2, 1));
+        assert!(!bc1.valid_level(6, 8));
+        assert_eq!(ContainerFormat::Rgba16Float.level_size(3, 2), 48);
+    }
+
+    #[test]
+    fn dds_mip_chain() {
+        let file = dds_file(DxgiFormat::BC1_UNorm_sRGB, 16, 5, false);
+        let image = read_dds("test.dds", &file).unwrap();
+        assert_eq!(image.format, ContainerFormat::CompressedSrgb(CompressedSrgbFormat::S3tcDxt1Alpha));
+        assert_eq!((image.width, image.height), (16, 16));
+        assert_eq!(image.faces.len(), 1);
+        let sizes: Vec<usize> = image.faces[0].iter().map(|level| level.len()).collect();
+        assert_eq!(sizes, vec![128, 32, 8, 8, 8]);
+        assert_eq!(image.faces[0][1][0], 128);
+    }
+
+    #[test]
+    fn dds_cubemap_faces() {
+        let file = dds_file(DxgiFormat::R16G16B16A16_Float, 4, 1, true);
+        let image = read_dds("test.dds", &file).unwrap();
+        assert!(image.format.is_float());
+        assert_eq!(image.faces.len(), 6);
+        assert!(image.faces.iter().all(|face| face.len() == 1 && face[0].len() == 128));
+        assert_eq!(image.faces[1][0][0], 128);
+    }
+
+    #[test]
+    fn truncated_dds_is_rejected() {
+        let mut file = dds_file(DxgiFormat::BC7_UNorm, 16, 1, false);
+        file.truncate(file.len() - 1);
+        assert!(matches!(read_dds("test.dds", &file), Err(EngineError::Parse { .. })));
+    }
+}
@@ -0,0 +1,149 @@
+#![allow(dead_code)]
+
+use glium::index::NoIndices;
+use glium::index::PrimitiveType;
+use glium::texture::Texture2d;
+use glium::uniform;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
+use glium::vertex::EmptyVertexAttributes;
+use glium::Program;
+use glium::Surface;
+
+use crate::error::EngineError;
+use crate::shader::ShaderVariant;
+
+pub const FULLSCREEN_VERTEX_SHADER: &str = "builtin/fullscreen_vertex.glsl";
+pub const TONEMAP_FRAGMENT_SHADER: &str = "builtin/tonemap_fragment.glsl";
+
+pub fn tonemap_variant() -> ShaderVariant {
+    return ShaderVariant::new(FULLSCREEN_VERTEX_SHADER, TONEMAP_FRAGMENT_SHADER);
+}
+
+// how the colours of the hdr target are brought back to the displayable range
+// the values must match assets/shaders/tonemap_fragment.glsl
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tonemapping {
+    // clamps the colours
+    None,
+    Reinhard,
+    Aces,
+}
+
+impl Tonemapping {
+    fn shader_index(self) -> i32 {
+        match self {
+            Tonemapping::None => 0,
+            Tonemapping::Reinhard => 1,
+            Tonemapping::Aces => 2,
+        }
+    }
+}
+
+// draws the hdr colours on the surface with the tonemapping and exposure of the camera
+pub fn tonemap<S: Surface>(
+    surface: &mut S,
+    source: &Texture2d,
+    program: &Program,
+    tonemapping: Tonemapping,
+    exposure: f32,
+) -> Result<(), EngineError> {
+    let sampled = source
+        .sampled()
+        .minify_filter(MinifySamplerFilter::Nearest)
+        .magnify_filter(MagnifySamplerFilter::Nearest);
+    surface
+        .draw(
+            EmptyVertexAttributes { len: 3 },
+            NoIndices(PrimitiveType::TrianglesList),
+            program,
+            &uniform! {
+                u_hdr_color: sampled,
+                u_exposure: exposure,
+                u_tonemapping: tonemapping.shader_index(),
+            },
+            &Default::default(),
+        )?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::camera::Camera;
+    use crate::shader::preprocess;
+
+    const EPSILON: f32 = 1e-4;
+
+    // reference implementation of the curves of the shader, to check their shape on the cpu
+    fn reference_tonemap(tonemapping: Tonemapping, color: [f32; 3], exposure: f32) -> [f32; 3] {
+        return color.map(|c| {
+            let exposed = c * exposure;
+            match tonemapping {
+                Tonemapping::None => exposed.clamp(0.0, 1.0),
+                Tonemapping::Reinhard => exposed / (exposed + 1.0),
+                Tonemapping::Aces => reference_aces(exposed),
+            }
+        });
+    }
+
+    // fit of the aces filmic curve by Krzysztof Narkowicz, as in the shader
+    fn reference_aces(x: f32) -> f32 {
+        let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+        return ((x * (a * x + b)) / (x * (c * x + d) + e)).clamp(0.0, 1.0);
+    }
+
+    #[test]
+    fn indices_match_the_shader() {
+        let variant = tonemap_variant();
+        let shader = preprocess(TONEMAP_FRAGMENT_SHADER, &variant).unwrap().source;
+        // the indices the shader branches on, the others fall through to the clamp of the else
+        let branches: Vec<i32> = shader
+            .lines()
+            .filter_map(|line| line.split("if (u_tonemapping == ").nth(1))
+            .filter_map(|rest| rest.split(')').next()?.trim().parse().ok())
+            .collect();
+        assert_eq!(branches, vec![Tonemapping::Reinhard.shader_index(), Tonemapping::Aces.shader_index()]);
+        assert!(shader.contains("mapped = exposed / (exposed + 1.0);"));
+        assert!(shader.contains("mapped = aces(exposed);"));
+        assert!(shader.contains("} else {"));
+        assert!(!branches.contains(&Tonemapping::None.shader_index()));
+    }
+
+    #[test]
+    fn without_tonemapping_colours_are_clamped() {
+        assert_eq!(reference_tonemap(Tonemapping::None, [0.5, 2.0, -1.0], 1.0), [0.5, 1.0, 0.0]);
+        assert_eq!(reference_tonemap(Tonemapping::None, [0.25, 0.5, 1.0], 2.0), [0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn reinhard_compresses_towards_white() {
+        let [black, middle, bright] = reference_tonemap(Tonemapping::Reinhard, [0.0, 1.0, 1000.0], 1.0);
+        assert_eq!(black, 0.0);
+        assert!((middle - 0.5).abs() < EPSILON);
+        assert!(bright < 1.0 && bright > 0.99);
+        // the exposure scales the colour before the curve
+        assert_eq!(reference_tonemap(Tonemapping::Reinhard, [0.5, 0.5, 0.5], 2.0), [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn aces_is_monotonic_and_saturates() {
+        let [black, grey, white] = reference_tonemap(Tonemapping::Aces, [0.0, 0.18, 100.0], 1.0);
+        assert_eq!(black, 0.0);
+        assert!((grey - 0.2669).abs() < 1e-3);
+        assert_eq!(white, 1.0);
+        let curve: Vec<f32> = (0..100).map(|i| reference_aces(i as f32 * 0.1)).collect();
+        assert!(curve.windows(2).all(|pair| pair[0] <= pair[1]));
+        // brighter than reinhard in the mid tones, the curve has more contrast
+        assert!(reference_aces(1.0) > 0.5);
+    }
+
+    #[test]
+    fn cameras_start_with_aces_at_unit_exposure() {
+        let camera = Camera::new();
+        assert!(!camera.hdr);
+        assert_eq!(camera.exposure, 1.0);
+        assert_eq!(camera.tonemapping, Tonemapping::Aces);
+    }
+}
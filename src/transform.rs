@@ -11,8 +11,13 @@ use libm::asinf;
 use libm::atan2f;
 use libm::cosf;
 use libm::sinf;
+
+use tracing::debug;
+use tracing::warn;
 use num::Float;
 
+use crate::logging::SYSTEMS;
+
 // TODO genrealise functions beyond f32 (not urgent, not important, would be nice tho)
 
 // norm of a vector2
@@ -81,7 +86,7 @@ pub fn quaternion_normalised(quat: Quaternion<f32>) -> Quaternion<f32> {
         quat.s * quat.s + quat.v.x * quat.v.x + quat.v.y * quat.v.y + quat.v.z * quat.v.z,
     );
     if norm.is_zero() {
-        println!("hmm");
+        warn!(target: SYSTEMS, "cannot normalise a zero quaternion");
         return quat;
     } else {
         // should be allowed to do this
@@ -231,15 +236,16 @@ impl Transform {
     }
 
     pub fn print_transform(self) -> () {
-        let euler_rot = quaternion_to_euler(self.rotation_quat) * (360.0 / (2.0 * 3.141592));
-        println!(
+        let euler_rot = quaternion_to_euler(self.rotation_quat) * (360.0 / (2.0 * std::f32::consts::PI));
+        debug!(
+            target: SYSTEMS,
             "rotation - x={}, y={}, z={}",
             euler_rot.x, euler_rot.y, euler_rot.z
         );
-        println!(
+        debug!(
+            target: SYSTEMS,
             "position - x={}, y={}, z={}",
             self.position.x, self.position.y, self.position.z
         );
-        println!("");
     }
 }
@@ -0,0 +1,1170 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use glium::backend::Facade;
+use glium::index::NoIndices;
+use glium::index::PrimitiveType;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
+use glium::uniforms::SamplerBehavior;
+use glium::uniforms::SamplerWrapFunction;
+use glium::uniforms::UniformValue;
+use glium::winit::event::ElementState;
+use glium::winit::keyboard::KeyCode;
+use glium::Program;
+use glium::Surface;
+use glium::VertexBuffer;
+
+use legion::Entity;
+
+use taffy::style_helpers::length;
+use taffy::style_helpers::percent;
+use taffy::AlignItems;
+use taffy::AvailableSpace;
+use taffy::Dimension;
+use taffy::FlexDirection;
+use taffy::FlexWrap;
+use taffy::JustifyContent;
+use taffy::NodeId;
+use taffy::Rect;
+use taffy::Size;
+use taffy::Style;
+use taffy::TaffyError;
+use taffy::TaffyTree;
+
+use crate::error::EngineError;
+use crate::input::KeyboardState;
+use crate::material::WHITE_TEXTURE;
+use crate::render_graph::FrameInfo;
+use crate::render_graph::PassBuilder;
+use crate::render_graph::PassContext;
+use crate::render_graph::RenderPass;
+use crate::render_graph::BACKBUFFER;
+use crate::shader::ShaderVariant;
+use crate::text::atlas_key;
+use crate::text::lay_out_texts;
+use crate::text::screen_projection;
+use crate::text::FontAtlas;
+use crate::text::TextAlign;
+use crate::text::TextComponent;
+use crate::text::TextVertex;
+use crate::text::TEXT_VERTEX_SHADER;
+use crate::texture::texture_cache_key;
+use crate::texture::Texture;
+use crate::texture::TextureSettings;
+use crate::texture::WrapMode;
+use crate::uniforms::UniformBag;
+
+pub const UI_FRAGMENT_SHADER: &str = "builtin/ui_fragment.glsl";
+
+// the pass of RenderGraph::forward, after the post processing and before the texts of the screen
+pub const UI_PASS: &str = "ui";
+
+// the buttons are lighter when hovered or focused and darker when pressed
+const HOVER_FACTOR: f32 = 1.25;
+const PRESSED_FACTOR: f32 = 0.75;
+
+// the quads use the vertices and the vertex shader of the texts
+pub fn ui_variant() -> ShaderVariant {
+    return ShaderVariant::new(TEXT_VERTEX_SHADER, UI_FRAGMENT_SHADER);
+}
+
+// the images of the interface are colours which aren't repeated
+pub fn ui_image_settings() -> TextureSettings {
+    return TextureSettings::default().with_srgb(true).with_wrap(WrapMode::Clamp);
+}
+
+// where a root node is placed on the screen, 0 is the left or top edge and 1 the right or bottom
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UiAnchor {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl UiAnchor {
+    pub const TOP_LEFT: UiAnchor = UiAnchor { x: 0.0, y: 0.0 };
+    pub const TOP: UiAnchor = UiAnchor { x: 0.5, y: 0.0 };
+    pub const TOP_RIGHT: UiAnchor = UiAnchor { x: 1.0, y: 0.0 };
+    pub const LEFT: UiAnchor = UiAnchor { x: 0.0, y: 0.5 };
+    pub const CENTER: UiAnchor = UiAnchor { x: 0.5, y: 0.5 };
+    pub const RIGHT: UiAnchor = UiAnchor { x: 1.0, y: 0.5 };
+    pub const BOTTOM_LEFT: UiAnchor = UiAnchor { x: 0.0, y: 1.0 };
+    pub const BOTTOM: UiAnchor = UiAnchor { x: 0.5, y: 1.0 };
+    pub const BOTTOM_RIGHT: UiAnchor = UiAnchor { x: 1.0, y: 1.0 };
+}
+
+// how the units of the interface become pixels
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UiScaling {
+    // a unit is this many pixels whatever the size of the window
+    Fixed(f32),
+    // the units are the pixels of a window of this size, the interface grows and shrinks with the
+    // window while keeping its proportions
+    ReferenceSize([f32; 2]),
+}
+
+impl Default for UiScaling {
+    fn default() -> Self {
+        return UiScaling::ReferenceSize([1280.0, 720.0]);
+    }
+}
+
+impl UiScaling {
+    pub fn factor(self, width: u32, height: u32) -> f32 {
+        match self {
+            UiScaling::Fixed(factor) => factor,
+            UiScaling::ReferenceSize([reference_width, reference_height]) => {
+                (width as f32 / reference_width).min(height as f32 / reference_height)
+            }
+        }
+    }
+}
+
+// a rectangle of the screen in pixels from the top left corner
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct UiRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl UiRect {
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        return point[0] >= self.x
+            && point[0] < self.x + self.width
+            && point[1] >= self.y
+            && point[1] < self.y + self.height;
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        return [self.x + self.width * 0.5, self.y + self.height * 0.5];
+    }
+}
+
+// a length in the units of the interface, or relative to the parent
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum UiLength {
+    // from the content and the flexbox of the parent
+    #[default]
+    Auto,
+    Units(f32),
+    // between 0 and 1
+    Percent(f32),
+}
+
+impl UiLength {
+    fn dimension(self) -> Dimension {
+        match self {
+            UiLength::Auto => Dimension::auto(),
+            UiLength::Units(units) => length(units),
+            UiLength::Percent(fraction) => percent(fraction),
+        }
+    }
+}
+
+// how the children are placed along the direction of their parent
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UiJustify {
+    #[default]
+    Start,
+    Center,
+    End,
+    SpaceBetween,
+    SpaceAround,
+}
+
+// how the children are placed across the direction of their parent
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UiAlign {
+    Start,
+    Center,
+    End,
+    #[default]
+    Stretch,
+}
+
+// the flexbox of a node, turned into the style of taffy when laying out
+// the sides are left, right, top and bottom
+#[derive(Clone, Debug, PartialEq)]
+pub struct UiStyle {
+    // the children go down rather than to the right
+    pub column: bool,
+    pub wrap: bool,
+    pub width: UiLength,
+    pub height: UiLength,
+    pub padding: [f32; 4],
+    pub margin: [f32; 4],
+    // between the children
+    pub gap: f32,
+    pub grow: f32,
+    pub shrink: f32,
+    pub justify: UiJustify,
+    pub align: UiAlign,
+}
+
+impl Default for UiStyle {
+    fn default() -> Self {
+        return UiStyle {
+            column: false,
+            wrap: false,
+            width: UiLength::Auto,
+            height: UiLength::Auto,
+            padding: [0.0; 4],
+            margin: [0.0; 4],
+            gap: 0.0,
+            grow: 0.0,
+            shrink: 1.0,
+            justify: UiJustify::Start,
+            align: UiAlign::Stretch,
+        };
+    }
+}
+
+impl UiStyle {
+    pub fn taffy_style(&self) -> Style {
+        let sides = |[left, right, top, bottom]: [f32; 4]| Rect {
+            left: length(left),
+            right: length(right),
+            top: length(top),
+            bottom: length(bottom),
+        };
+        return Style {
+            flex_direction: if self.column { FlexDirection::Column } else { FlexDirection::Row },
+            flex_wrap: if self.wrap { FlexWrap::Wrap } else { FlexWrap::NoWrap },
+            size: Size {
+                width: self.width.dimension(),
+                height: self.height.dimension(),
+            },
+            padding: sides(self.padding),
+            margin: Rect {
+                left: length(self.margin[0]),
+                right: length(self.margin[1]),
+                top: length(self.margin[2]),
+                bottom: length(self.margin[3]),
+            },
+            gap: length(self.gap),
+            flex_grow: self.grow,
+            flex_shrink: self.shrink,
+            justify_content: Some(match self.justify {
+                UiJustify::Start => JustifyContent::Start,
+                UiJustify::Center => JustifyContent::Center,
+                UiJustify::End => JustifyContent::End,
+                UiJustify::SpaceBetween => JustifyContent::SpaceBetween,
+                UiJustify::SpaceAround => JustifyContent::SpaceAround,
+            }),
+            align_items: Some(match self.align {
+                UiAlign::Start => AlignItems::Start,
+                UiAlign::Center => AlignItems::Center,
+                UiAlign::End => AlignItems::End,
+                UiAlign::Stretch => AlignItems::Stretch,
+            }),
+            ..Default::default()
+        };
+    }
+}
+
+// what a node draws in its rectangle
+#[derive(Clone, Debug, PartialEq)]
+pub enum UiWidget {
+    // only lays out its children
+    Container,
+    Panel {
+        color: [f32; 4],
+    },
+    // the image is stretched over the node, its size isn't used by the layout
+    Image {
+        texture: String,
+        tint: [f32; 4],
+    },
+    // the size and the maximum width of the text are in the units of the interface, its position
+    // is ignored and the node is as big as the text unless its style says otherwise
+    Text(TextComponent),
+    // a panel whose colour follows the pointer and the focus
+    Button {
+        color: [f32; 4],
+        hover_color: [f32; 4],
+        pressed_color: [f32; 4],
+    },
+    // filled from the left up to the value between 0 and 1, like a health bar
+    Bar {
+        value: f32,
+        background: [f32; 4],
+        fill: [f32; 4],
+    },
+}
+
+// an element of the interface, the nodes without parent are placed on the screen by their anchor
+// and the children are laid out inside their parent by the flexbox of taffy, in the order they
+// were added
+// the sizes are in the units of the interface, see UiScaling
+#[derive(Clone, Debug, PartialEq)]
+pub struct UiNode {
+    pub parent: Option<Entity>,
+    pub style: UiStyle,
+    pub anchor: UiAnchor,
+    // added to the anchored position of a root node, ignored for the children
+    pub offset: [f32; 2],
+    pub widget: UiWidget,
+    // the node receives the events of the pointer, the buttons do
+    pub interactive: bool,
+    // the keyboard and gamepad navigation can focus the node, the buttons can be
+    pub focusable: bool,
+    // a hidden node hides its children as well
+    pub visible: bool,
+}
+
+impl UiNode {
+    pub fn new(widget: UiWidget) -> Self {
+        let button = matches!(widget, UiWidget::Button { .. });
+        return UiNode {
+            parent: None,
+            style: UiStyle::default(),
+            anchor: UiAnchor::TOP_LEFT,
+            offset: [0.0, 0.0],
+            widget,
+            interactive: button,
+            focusable: button,
+            visible: true,
+        };
+    }
+
+    pub fn container() -> Self {
+        return UiNode::new(UiWidget::Container);
+    }
+
+    pub fn panel(color: [f32; 4]) -> Self {
+        return UiNode::new(UiWidget::Panel { color });
+    }
+
+    pub fn image(texture: &str) -> Self {
+        return UiNode::new(UiWidget::Image {
+            texture: texture.to_string(),
+            tint: [1.0; 4],
+        });
+    }
+
+    pub fn text(text: TextComponent) -> Self {
+        return UiNode::new(UiWidget::Text(text));
+    }
+
+    pub fn button(color: [f32; 4]) -> Self {
+        let shade = |factor: f32| [color[0] * factor, color[1] * factor, color[2] * factor, color[3]];
+        return UiNode::new(UiWidget::Button {
+            color,
+            hover_color: shade(HOVER_FACTOR),
+            pressed_color: shade(PRESSED_FACTOR),
+        });
+    }
+
+    pub fn bar(value: f32, background: [f32; 4], fill: [f32; 4]) -> Self {
+        return UiNode::new(UiWidget::Bar {
+            value,
+            background,
+            fill,
+        });
+    }
+
+    pub fn child_of(mut self, parent: Entity) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn anchored(mut self, anchor: UiAnchor, offset: [f32; 2]) -> Self {
+        self.anchor = anchor;
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_style(mut self, style: UiStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.style.width = UiLength::Units(width);
+        self.style.height = UiLength::Units(height);
+        self
+    }
+
+    pub fn with_padding(mut self, padding: f32) -> Self {
+        self.style.padding = [padding; 4];
+        self
+    }
+
+    pub fn with_gap(mut self, gap: f32) -> Self {
+        self.style.gap = gap;
+        self
+    }
+
+    // the children side by side, the default of flexbox
+    pub fn row(mut self) -> Self {
+        self.style.column = false;
+        self
+    }
+
+    pub fn column(mut self) -> Self {
+        self.style.column = true;
+        self
+    }
+
+    pub fn with_focusable(mut self, focusable: bool) -> Self {
+        self.focusable = focusable;
+        self
+    }
+
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    pub fn layout_node(&self, key: Entity) -> LayoutNode<Entity> {
+        return LayoutNode {
+            key,
+            parent: self.parent,
+            style: self.style.taffy_style(),
+            anchor: self.anchor,
+            offset: self.offset,
+            measured: matches!(self.widget, UiWidget::Text(_)),
+        };
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UiEventKind {
+    HoverStart,
+    HoverEnd,
+    Press,
+    Release,
+    // released over the node it was pressed on, or activated by the navigation
+    Click,
+    FocusGained,
+    FocusLost,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UiEvent {
+    pub entity: Entity,
+    pub kind: UiEventKind,
+}
+
+// the events of the interface caused by the current event of the window, the systems get them as
+// a resource
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UiEvents {
+    pub events: Vec<UiEvent>,
+}
+
+impl UiEvents {
+    pub fn has(&self, entity: Entity, kind: UiEventKind) -> bool {
+        return self.events.iter().any(|event| event.entity == entity && event.kind == kind);
+    }
+
+    pub fn clicked(&self, entity: Entity) -> bool {
+        return self.has(entity, UiEventKind::Click);
+    }
+}
+
+// how the focus moves between the focusable nodes, from the keyboard, see keyboard_navigation, or
+// from a gamepad
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UiNavigation {
+    // in the order the nodes are drawn
+    Next,
+    Previous,
+    // to the nearest node in the direction
+    Up,
+    Down,
+    Left,
+    Right,
+    // clicks the focused node
+    Activate,
+    // drops the focus
+    Cancel,
+}
+
+// tab moves the focus, the arrows, enter, space and escape only act on the interface while it has
+// the focus, so that they are left to the game otherwise
+pub fn keyboard_navigation(key: KeyCode, shift: bool, focused: bool) -> Option<UiNavigation> {
+    match key {
+        KeyCode::Tab if shift => Some(UiNavigation::Previous),
+        KeyCode::Tab => Some(UiNavigation::Next),
+        _ if !focused => None,
+        KeyCode::ArrowUp => Some(UiNavigation::Up),
+        KeyCode::ArrowDown => Some(UiNavigation::Down),
+        KeyCode::ArrowLeft => Some(UiNavigation::Left),
+        KeyCode::ArrowRight => Some(UiNavigation::Right),
+        KeyCode::Enter | KeyCode::NumpadEnter | KeyCode::Space => Some(UiNavigation::Activate),
+        KeyCode::Escape => Some(UiNavigation::Cancel),
+        _ => None,
+    }
+}
+
+// the keys which move through the interface are kept from the game, the others and every release
+// reach it, so that no key stays pressed when the focus changes
+pub fn route_key(
+    keyboard_state: &mut KeyboardState,
+    key_state: ElementState,
+    key: KeyCode,
+    focused: bool,
+) -> Option<UiNavigation> {
+    let shift = keyboard_state.is_pressed(KeyCode::ShiftLeft) || keyboard_state.is_pressed(KeyCode::ShiftRight);
+    let navigation = match key_state {
+        ElementState::Pressed => keyboard_navigation(key, shift, focused),
+        ElementState::Released => None,
+    };
+    if navigation.is_none() {
+        keyboard_state.process_event(key_state, key);
+    }
+    return navigation;
+}
+
+// the node the focus moves to from the focused one, among the candidates in drawing order
+pub fn next_focus<K: Copy + PartialEq>(
+    candidates: &[(K, UiRect)],
+    focused: Option<K>,
+    navigation: UiNavigation,
+) -> Option<K> {
+    let count = candidates.len();
+    let current = focused.and_then(|focused| candidates.iter().position(|(key, _)| *key == focused));
+    let Some(current) = current else {
+        return match navigation {
+            UiNavigation::Previous => candidates.last().map(|(key, _)| *key),
+            UiNavigation::Activate | UiNavigation::Cancel => None,
+            _ => candidates.first().map(|(key, _)| *key),
+        };
+    };
+    let center = candidates[current].1.center();
+    // the distance along the direction and across it
+    let along_across = |point: [f32; 2]| {
+        let delta = [point[0] - center[0], point[1] - center[1]];
+        match navigation {
+            UiNavigation::Up => Some((-delta[1], delta[0].abs())),
+            UiNavigation::Down => Some((delta[1], delta[0].abs())),
+            UiNavigation::Left => Some((-delta[0], delta[1].abs())),
+            UiNavigation::Right => Some((delta[0], delta[1].abs())),
+            _ => None,
+        }
+    };
+    match navigation {
+        UiNavigation::Next => Some(candidates[(current + 1) % count].0),
+        UiNavigation::Previous => Some(candidates[(current + count - 1) % count].0),
+        UiNavigation::Activate | UiNavigation::Cancel => focused,
+        _ => {
+            // the nodes across the direction are farther than the ones along it
+            let best = candidates
+                .iter()
+                .filter_map(|(key, rect)| {
+                    let (along, across) = along_across(rect.center())?;
+                    (along > 0.0).then_some((*key, along + across * 2.0))
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            // the focus stays when there is nothing in that direction
+            return best.map(|(key, _)| key).or(focused);
+        }
+    }
+}
+
+// a node as seen by the layout, the key identifies it and its parent
+#[derive(Clone, Debug)]
+pub struct LayoutNode<K> {
+    pub key: K,
+    pub parent: Option<K>,
+    pub style: Style,
+    pub anchor: UiAnchor,
+    pub offset: [f32; 2],
+    // the size of its content is given by the measure of compute_layout, for the texts
+    pub measured: bool,
+}
+
+fn layout_error(err: TaffyError) -> EngineError {
+    return EngineError::UiLayout {
+        message: err.to_string(),
+    };
+}
+
+// the rectangles of the nodes in pixels, in drawing order, the parents before their children
+// the nodes whose parent isn't in the list aren't laid out, measure gives the size of the content
+// of the measured nodes in the units of the interface
+pub fn compute_layout<K: Copy + Eq + Hash>(
+    nodes: &[LayoutNode<K>],
+    screen: [f32; 2],
+    scale: f32,
+    mut measure: impl FnMut(K) -> [f32; 2],
+) -> Result<Vec<(K, UiRect)>, EngineError> {
+    let mut tree: TaffyTree<K> = TaffyTree::new();
+    // the positions are rounded once they are pixels
+    tree.disable_rounding();
+    let mut ids = HashMap::new();
+    let mut keys = HashMap::new();
+    for node in nodes {
+        let id = if node.measured {
+            tree.new_leaf_with_context(node.style.clone(), node.key)
+        } else {
+            tree.new_leaf(node.style.clone())
+        }
+        .map_err(layout_error)?;
+        ids.insert(node.key, id);
+        keys.insert(id, node.key);
+    }
+    let mut roots = Vec::new();
+    for node in nodes {
+        match node.parent.map(|parent| ids.get(&parent)) {
+            None => roots.push(node),
+            Some(Some(parent)) => tree.add_child(*parent, ids[&node.key]).map_err(layout_error)?,
+            Some(None) => (),
+        }
+    }
+
+    let available = [screen[0] / scale, screen[1] / scale];
+    let mut rects = Vec::new();
+    for root in roots {
+        let id = ids[&root.key];
+        let space = Size {
+            width: AvailableSpace::Definite(available[0]),
+            height: AvailableSpace::Definite(available[1]),
+        };
+        let measure_node = |known: Size<Option<f32>>, _: Size<AvailableSpace>, _: NodeId, key: Option<&mut K>, _: &Style| {
+            let Some(key) = key else {
+                return Size::ZERO;
+            };
+            let [width, height] = measure(*key);
+            Size {
+                width: known.width.unwrap_or(width),
+                height: known.height.unwrap_or(height),
+            }
+        };
+        tree.compute_layout_with_measure(id, space, measure_node).map_err(layout_error)?;
+        let size = tree.layout(id).map_err(layout_error)?.size;
+        let origin = [
+            root.anchor.x * (available[0] - size.width) + root.offset[0],
+            root.anchor.y * (available[1] - size.height) + root.offset[1],
+        ];
+        collect_rects(&tree, &keys, id, origin, scale, &mut rects).map_err(layout_error)?;
+    }
+    return Ok(rects);
+}
+
+fn collect_rects<K: Copy>(
+    tree: &TaffyTree<K>,
+    keys: &HashMap<NodeId, K>,
+    id: NodeId,
+    origin: [f32; 2],
+    scale: f32,
+    rects: &mut Vec<(K, UiRect)>,
+) -> Result<(), TaffyError> {
+    let layout = tree.layout(id)?;
+    let position = [origin[0] + layout.location.x, origin[1] + layout.location.y];
+    // the edges are rounded rather than the size, so that neighbours stay in contact
+    let left = (position[0] * scale).round();
+    let top = (position[1] * scale).round();
+    let right = ((position[0] + layout.size.width) * scale).round();
+    let bottom = ((position[1] + layout.size.height) * scale).round();
+    rects.push((
+        keys[&id],
+        UiRect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        },
+    ));
+    for child in tree.children(id)? {
+        collect_rects(tree, keys, child, position, scale, rects)?;
+    }
+    return Ok(());
+}
+
+// a node of the last layout
+#[derive(Copy, Clone, Debug)]
+struct UiTarget {
+    entity: Entity,
+    rect: UiRect,
+    interactive: bool,
+    focusable: bool,
+}
+
+// the layout of the last frame, what the pointer and the focus are on, and the events they caused
+pub struct UiState {
+    pub scaling: UiScaling,
+    // in drawing order
+    targets: Vec<UiTarget>,
+    hovered: Option<Entity>,
+    pressed: Option<Entity>,
+    focused: Option<Entity>,
+    button_down: bool,
+    events: Vec<UiEvent>,
+}
+
+impl UiState {
+    pub fn new() -> Self {
+        return UiState {
+            scaling: UiScaling::default(),
+            targets: Vec::new(),
+            hovered: None,
+            pressed: None,
+            focused: None,
+            button_down: false,
+            events: Vec::new(),
+        };
+    }
+
+    pub fn hovered(&self) -> Option<Entity> {
+        return self.hovered;
+    }
+
+    pub fn pressed(&self) -> Option<Entity> {
+        return self.pressed;
+    }
+
+    pub fn focused(&self) -> Option<Entity> {
+        return self.focused;
+    }
+
+    // where the node was drawn last frame
+    pub fn rect(&self, entity: Entity) -> Option<UiRect> {
+        return self.targets.iter().find(|target| target.entity == entity).map(|target| target.rect);
+    }
+
+    pub fn events(&self) -> UiEvents {
+        return UiEvents {
+            events: self.events.clone(),
+        };
+    }
+
+    fn push_event(&mut self, entity: Entity, kind: UiEventKind) {
+        self.events.push(UiEvent { entity, kind });
+    }
+
+    // the nodes which are no longer drawn lose the pointer and the focus
+    pub(crate) fn set_layout(&mut self, targets: Vec<(Entity, UiRect, bool, bool)>) {
+        self.targets = targets
+            .into_iter()
+            .map(|(entity, rect, interactive, focusable)| UiTarget {
+                entity,
+                rect,
+                interactive,
+                focusable,
+            })
+            .collect();
+        let drawn = |entity: &Entity| self.targets.iter().any(|target| target.entity == *entity);
+        let focus_lost = self.focused.filter(|entity| !drawn(entity));
+        self.hovered = self.hovered.filter(drawn);
+        self.pressed = self.pressed.filter(drawn);
+        if focus_lost.is_some() {
+            self.set_focus(None);
+        }
+    }
+
+    fn set_focus(&mut self, entity: Option<Entity>) {
+        if entity == self.focused {
+            return;
+        }
+        if let Some(previous) = self.focused {
+            self.push_event(previous, UiEventKind::FocusLost);
+        }
+        if let Some(entity) = entity {
+            self.push_event(entity, UiEventKind::FocusGained);
+        }
+        self.focused = entity;
+    }
+
+    // called for every event of the window before the systems, with the position of the cursor in
+    // pixels and whether the button is held, the events of the previous call are dropped
+    pub fn update_pointer(&mut self, position: [f32; 2], down: bool) {
+        self.events.clear();
+        let hovered = self
+            .targets
+            .iter()
+            .rev()
+            .find(|target| target.interactive && target.rect.contains(position))
+            .map(|target| target.entity);
+        if hovered != self.hovered {
+            if let Some(previous) = self.hovered {
+                self.push_event(previous, UiEventKind::HoverEnd);
+            }
+            if let Some(entity) = hovered {
+                self.push_event(entity, UiEventKind::HoverStart);
+            }
+            self.hovered = hovered;
+        }
+        match (self.button_down, down, hovered) {
+            (false, true, Some(entity)) => {
+                self.pressed = Some(entity);
+                self.push_event(entity, UiEventKind::Press);
+                let focusable = self.targets.iter().any(|target| target.entity == entity && target.focusable);
+                if focusable {
+                    self.set_focus(Some(entity));
+                }
+            }
+            // a click beside the interface
+            (false, true, None) => self.set_focus(None),
+            (true, false, _) => {
+                if let Some(entity) = self.pressed.take() {
+                    self.push_event(entity, UiEventKind::Release);
+                    if hovered == Some(entity) {
+                        self.push_event(entity, UiEventKind::Click);
+                    }
+                }
+            }
+            _ => (),
+        }
+        self.button_down = down;
+    }
+
+    pub fn navigate(&mut self, navigation: UiNavigation) {
+        match (navigation, self.focused) {
+            (UiNavigation::Activate, Some(entity)) => self.push_event(entity, UiEventKind::Click),
+            (UiNavigation::Cancel, _) => self.set_focus(None),
+            _ => {
+                let candidates: Vec<(Entity, UiRect)> = self
+                    .targets
+                    .iter()
+                    .filter(|target| target.focusable)
+                    .map(|target| (target.entity, target.rect))
+                    .collect();
+                let next = next_focus(&candidates, self.focused, navigation);
+                self.set_focus(next);
+            }
+        }
+    }
+}
+
+// the vertices of the nodes drawn with the same texture one after the other
+pub struct UiBatch {
+    pub texture: String,
+    pub vertices: Vec<TextVertex>,
+}
+
+fn batch<'a>(batches: &'a mut Vec<UiBatch>, texture: &str) -> &'a mut Vec<TextVertex> {
+    if batches.last().map(|batch| batch.texture != texture).unwrap_or(true) {
+        batches.push(UiBatch {
+            texture: texture.to_string(),
+            vertices: Vec::new(),
+        });
+    }
+    return &mut batches.last_mut().unwrap().vertices;
+}
+
+fn push_quad(vertices: &mut Vec<TextVertex>, rect: UiRect, color: [f32; 4]) {
+    let (left, top) = (rect.x, rect.y);
+    let (right, bottom) = (rect.x + rect.width, rect.y + rect.height);
+    let corners = [
+        (left, top, 0.0, 0.0),
+        (left, bottom, 0.0, 1.0),
+        (right, bottom, 1.0, 1.0),
+        (left, top, 0.0, 0.0),
+        (right, bottom, 1.0, 1.0),
+        (right, top, 1.0, 0.0),
+    ];
+    for (x, y, u, v) in corners {
+        vertices.push(TextVertex {
+            position: [x, y, 0.0],
+            tex_coord: [u, v],
+            color,
+        });
+    }
+}
+
+// the quads of the laid out nodes, the texts add their glyphs to the atlas of their font
+pub fn ui_batches(
+    rects: &[(Entity, UiRect)],
+    nodes: &HashMap<Entity, &UiNode>,
+    state: &UiState,
+    fonts: &mut HashMap<String, FontAtlas>,
+    scale: f32,
+) -> Vec<UiBatch> {
+    return lay_out_texts(fonts, |fonts| layout_batches(rects, nodes, state, fonts, scale));
+}
+
+fn layout_batches(
+    rects: &[(Entity, UiRect)],
+    nodes: &HashMap<Entity, &UiNode>,
+    state: &UiState,
+    fonts: &mut HashMap<String, FontAtlas>,
+    scale: f32,
+) -> Vec<UiBatch> {
+    let mut batches = Vec::new();
+    for (entity, rect) in rects {
+        let Some(node) = nodes.get(entity) else {
+            continue;
+        };
+        match &node.widget {
+            UiWidget::Container => (),
+            UiWidget::Panel { color } => push_quad(batch(&mut batches, WHITE_TEXTURE), *rect, *color),
+            UiWidget::Image { texture, tint } => {
+                let key = texture_cache_key(texture, &ui_image_settings());
+                push_quad(batch(&mut batches, &key), *rect, *tint);
+            }
+            UiWidget::Button {
+                color,
+                hover_color,
+                pressed_color,
+            } => {
+                let color = if state.pressed == Some(*entity) {
+                    pressed_color
+                } else if state.hovered == Some(*entity) || state.focused == Some(*entity) {
+                    hover_color
+                } else {
+                    color
+                };
+                push_quad(batch(&mut batches, WHITE_TEXTURE), *rect, *color);
+            }
+            UiWidget::Bar {
+                value,
+                background,
+                fill,
+            } => {
+                let vertices = batch(&mut batches, WHITE_TEXTURE);
+                push_quad(vertices, *rect, *background);
+                let filled = UiRect {
+                    width: (rect.width * value.clamp(0.0, 1.0)).round(),
+                    ..*rect
+                };
+                push_quad(vertices, filled, *fill);
+            }
+            UiWidget::Text(text) => {
+                let Some(atlas) = fonts.get_mut(&text.font) else {
+                    continue;
+                };
+                // the lines are aligned inside the node
+                let x = match text.align {
+                    TextAlign::Left => rect.x,
+                    TextAlign::Center => rect.x + rect.width * 0.5,
+                    TextAlign::Right => rect.x + rect.width,
+                };
+                let y = rect.y;
+                let vertices = batch(&mut batches, &atlas_key(&text.font));
+                atlas.build_vertices(text, text.size * scale, |px, py| [x + px, y + py, 0.0], vertices);
+            }
+        }
+    }
+    return batches;
+}
+
+// draws the batches over the target, returns the number of draw calls
+pub fn draw_ui_batches<F: Facade, S: Surface>(
+    facade: &F,
+    target: &mut S,
+    batches: &[UiBatch],
+    textures: &HashMap<String, Texture>,
+    program: &Program,
+    width: u32,
+    height: u32,
+) -> Result<usize, EngineError> {
+    let params = glium::DrawParameters {
+        depth: glium::Depth {
+            test: glium::draw_parameters::DepthTest::Overwrite,
+            write: false,
+            ..Default::default()
+        },
+        blend: glium::Blend::alpha_blending(),
+        ..Default::default()
+    };
+    let sampler = SamplerBehavior {
+        wrap_function: (SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp),
+        minify_filter: MinifySamplerFilter::Linear,
+        magnify_filter: MagnifySamplerFilter::Linear,
+        ..Default::default()
+    };
+    let matrix = screen_projection(width, height);
+    let mut draw_calls = 0;
+    for batch in batches {
+        let (Some(texture), false) = (textures.get(&batch.texture), batch.vertices.is_empty()) else {
+            continue;
+        };
+        let buffer = VertexBuffer::new(facade, &batch.vertices).map_err(|err| EngineError::upload("ui", err))?;
+        let mut uniforms = UniformBag::new();
+        uniforms.set("u_matrix", UniformValue::Mat4(matrix));
+        uniforms.set("u_texture", texture.uniform_value(Some(sampler)));
+        target.draw(&buffer, NoIndices(PrimitiveType::TrianglesList), program, &uniforms, &params)?;
+        draw_calls += 1;
+    }
+    return Ok(draw_calls);
+}
+
+// draws the nodes of the interface over the final image
+pub struct UiPass;
+
+impl RenderPass for UiPass {
+    fn name(&self) -> &str {
+        return UI_PASS;
+    }
+
+    fn setup(&self, _info: &FrameInfo, builder: &mut PassBuilder) {
+        builder.write(BACKBUFFER);
+    }
+
+    fn execute(&mut self, context: &mut PassContext) -> Result<(), EngineError> {
+        let info = context.info;
+        let frame = &mut *context.frame;
+        return context.scene.draw_ui(context.display, frame, info.width, info.height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use legion::World;
+
+    fn rect(x: f32, y: f32) -> UiRect {
+        return UiRect {
+            x,
+            y,
+            width: 10.0,
+            height: 10.0,
+        };
+    }
+
+    fn node(key: usize, parent: Option<usize>, style: UiStyle) -> LayoutNode<usize> {
+        return LayoutNode {
+            key,
+            parent,
+            style: style.taffy_style(),
+            anchor: UiAnchor::TOP_LEFT,
+            offset: [0.0, 0.0],
+            measured: false,
+        };
+    }
+
+    fn sized(width: f32, height: f32) -> UiStyle {
+        return UiStyle {
+            width: UiLength::Units(width),
+            height: UiLength::Units(height),
+            ..Default::default()
+        };
+    }
+
+    #[test]
+    fn roots_are_anchored_and_scaled() {
+        let mut root = node(0, None, sized(100.0, 20.0));
+        root.anchor = UiAnchor::BOTTOM_RIGHT;
+        root.offset = [-10.0, -10.0];
+        // a reference of 640 by 360 in a window twice as big
+        let scale = UiScaling::ReferenceSize([640.0, 360.0]).factor(1280, 720);
+        let rects = compute_layout(&[root], [1280.0, 720.0], scale, |_| [0.0; 2]).unwrap();
+        assert_eq!(
+            rects[0].1,
+            UiRect {
+                x: 1060.0,
+                y: 660.0,
+                width: 200.0,
+                height: 40.0
+            }
+        );
+    }
+
+    #[test]
+    fn children_follow_the_flexbox_of_their_parent() {
+        let row = UiStyle {
+            padding: [5.0; 4],
+            gap: 10.0,
+            align: UiAlign::Start,
+            ..sized(100.0, 50.0)
+        };
+        let nodes = [
+            node(0, None, row),
+            node(1, Some(0), sized(20.0, 20.0)),
+            // measured like a text
+            LayoutNode {
+                measured: true,
+                ..node(2, Some(0), UiStyle::default())
+            },
+            // the parent isn't laid out
+            node(3, Some(7), sized(20.0, 20.0)),
+        ];
+        let rects = compute_layout(&nodes, [800.0, 600.0], 1.0, |_| [30.0, 12.0]).unwrap();
+        let keys: Vec<usize> = rects.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, vec![0, 1, 2]);
+        assert_eq!(
+            rects[1].1,
+            UiRect {
+                x: 5.0,
+                y: 5.0,
+                width: 20.0,
+                height: 20.0
+            }
+        );
+        assert_eq!(rects[2].1.x, 35.0);
+        assert_eq!(rects[2].1.width, 30.0);
+    }
+
+    #[test]
+    fn focus_moves_in_order_and_in_directions() {
+        // a grid of two rows of two nodes
+        let candidates = [(0, rect(0.0, 0.0)), (1, rect(20.0, 0.0)), (2, rect(0.0, 20.0)), (3, rect(20.0, 20.0))];
+        assert_eq!(next_focus(&candidates, None, UiNavigation::Next), Some(0));
+        assert_eq!(next_focus(&candidates, Some(3), UiNavigation::Next), Some(0));
+        assert_eq!(next_focus(&candidates, Some(0), UiNavigation::Previous), Some(3));
+        assert_eq!(next_focus(&candidates, Some(0), UiNavigation::Right), Some(1));
+        assert_eq!(next_focus(&candidates, Some(0), UiNavigation::Down), Some(2));
+        assert_eq!(next_focus(&candidates, Some(3), UiNavigation::Up), Some(1));
+        // nothing on the left
+        assert_eq!(next_focus(&candidates, Some(2), UiNavigation::Left), Some(2));
+    }
+
+    #[test]
+    fn keys_which_navigate_are_kept_from_the_game() {
+        let mut keyboard_state = KeyboardState::new();
+        // without focus the arrows move the game, tab still moves the focus
+        assert_eq!(route_key(&mut keyboard_state, ElementState::Pressed, KeyCode::ArrowUp, false), None);
+        assert!(keyboard_state.is_pressed(KeyCode::ArrowUp));
+        route_key(&mut keyboard_state, ElementState::Released, KeyCode::ArrowUp, false);
+        assert_eq!(
+            route_key(&mut keyboard_state, ElementState::Pressed, KeyCode::Tab, false),
+            Some(UiNavigation::Next)
+        );
+        assert!(!keyboard_state.is_pressed(KeyCode::Tab));
+        // with focus the arrows and space only navigate
+        assert_eq!(
+            route_key(&mut keyboard_state, ElementState::Pressed, KeyCode::ArrowUp, true),
+            Some(UiNavigation::Up)
+        );
+        assert!(!keyboard_state.is_pressed(KeyCode::ArrowUp));
+        assert_eq!(
+            route_key(&mut keyboard_state, ElementState::Pressed, KeyCode::Space, true),
+            Some(UiNavigation::Activate)
+        );
+        assert!(!keyboard_state.is_pressed(KeyCode::Space));
+        // the other keys still reach it
+        assert_eq!(route_key(&mut keyboard_state, ElementState::Pressed, KeyCode::KeyW, true), None);
+        assert!(keyboard_state.is_pressed(KeyCode::KeyW));
+        // a key held before the focus is still released
+        route_key(&mut keyboard_state, ElementState::Pressed, KeyCode::ArrowLeft, false);
+        route_key(&mut keyboard_state, ElementState::Released, KeyCode::ArrowLeft, true);
+        assert!(!keyboard_state.is_pressed(KeyCode::ArrowLeft));
+        // shift reverses tab
+        route_key(&mut keyboard_state, ElementState::Pressed, KeyCode::ShiftLeft, true);
+        assert_eq!(
+            route_key(&mut keyboard_state, ElementState::Pressed, KeyCode::Tab, true),
+            Some(UiNavigation::Previous)
+        );
+    }
+
+    #[test]
+    fn clicks_need_the_release_over_the_pressed_node() {
+        let mut world = World::default();
+        let button = world.push(());
+        let mut state = UiState::new();
+        state.set_layout(vec![(button, rect(0.0, 0.0), true, true)]);
+        state.update_pointer([5.0, 5.0], false);
+        assert!(state.events().has(button, UiEventKind::HoverStart));
+        state.update_pointer([5.0, 5.0], true);
+        assert!(state.events().has(button, UiEventKind::Press));
+        assert!(state.events().has(button, UiEventKind::FocusGained));
+        state.update_pointer([5.0, 5.0], false);
+        assert!(state.events().clicked(button));
+        // released beside it
+        state.update_pointer([5.0, 5.0], true);
+        state.update_pointer([50.0, 5.0], false);
+        let events = state.events();
+        assert!(events.has(button, UiEventKind::Release));
+        assert!(!events.clicked(button));
+        // the keyboard clicks the focused node
+        state.navigate(UiNavigation::Activate);
+        assert!(state.events().clicked(button));
+    }
+}
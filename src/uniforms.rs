@@ -0,0 +1,35 @@
+use glium::uniforms::UniformValue;
+use glium::uniforms::Uniforms;
+
+// a set of uniforms which is only known at runtime, unlike what the uniform! macro produces
+// setting a uniform twice replaces the previous value, which is how per instance values
+// override the ones of the material
+#[derive(Clone)]
+pub struct UniformBag<'a> {
+    values: Vec<(String, UniformValue<'a>)>,
+}
+
+impl<'a> UniformBag<'a> {
+    pub fn new() -> Self {
+        UniformBag { values: Vec::new() }
+    }
+
+    pub fn set(&mut self, name: &str, value: UniformValue<'a>) {
+        match self.values.iter_mut().find(|(n, _)| n == name) {
+            Some((_, old_value)) => *old_value = value,
+            None => self.values.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.iter().any(|(n, _)| n == name)
+    }
+}
+
+impl Uniforms for UniformBag<'_> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut output: F) {
+        for (name, value) in &self.values {
+            output(name, *value);
+        }
+    }
+}